@@ -0,0 +1,51 @@
+use rustyline_async::{Readline, ReadlineEvent};
+use std::io::Write;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+// Type "page" to view some text in $PAGER.  Messages written while the pager
+// is open are printed once it exits.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+	let (mut rl, mut stdout) = Readline::new("> ".into())?;
+
+	let mut ticker = stdout.clone();
+	tokio::spawn(async move {
+		for i in 0.. {
+			tokio::time::sleep(Duration::from_secs(2)).await;
+			if writeln!(ticker, "Tick {i}").is_err() {
+				break;
+			}
+		}
+	});
+	// Print output even while the main loop is waiting for the pager
+	rl.spawn_flusher();
+
+	loop {
+		match rl.readline().await {
+			Ok(ReadlineEvent::Line(line)) if line == "page" => {
+				let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".into());
+				let guard = rl.pause()?;
+				let mut child = Command::new(&pager).stdin(Stdio::piped()).spawn()?;
+				let text: String = (0..200).map(|i| format!("Line {i}\n")).collect();
+				let mut stdin = child.stdin.take().unwrap();
+				// The pager may exit before reading everything
+				let _ = stdin.write_all(text.as_bytes()).await;
+				drop(stdin);
+				child.wait().await?;
+				guard.resume()?;
+			}
+			Ok(ReadlineEvent::Line(line)) => writeln!(stdout, "You entered: {line:?}")?,
+			Ok(ReadlineEvent::Eof) | Ok(ReadlineEvent::Interrupted) => break,
+			Ok(_) => {}
+			Err(e) => {
+				writeln!(stdout, "Error: {e:?}")?;
+				break;
+			}
+		}
+	}
+	rl.flush()?;
+	Ok(())
+}
@@ -1,4 +1,5 @@
 use async_std::stream;
+use crossterm::event::{KeyCode, KeyEvent};
 use rustyline_async::{Readline, ReadlineError, ReadlineEvent};
 
 use std::{io::Write, time::Duration};
@@ -14,6 +15,8 @@ async fn main() -> Result<(), ReadlineError> {
 	// Options:
 	// rl.should_print_line_on(false, false);
 	// rl.set_max_history(10);
+	// F1 lists the commands without submitting the input
+	rl.bind_event(KeyEvent::from(KeyCode::F(1)), "help");
 
 	simplelog::WriteLogger::init(
 		log::LevelFilter::Debug,
@@ -80,6 +83,8 @@ help")?;
 				},
 				Ok(ReadlineEvent::Eof) => { writeln!(stdout, "Exiting...")?; break },
 				Ok(ReadlineEvent::Interrupted) => writeln!(stdout, "^C")?,
+				Ok(ReadlineEvent::Custom(_)) => writeln!(stdout, "Commands: start, stop, info, help")?,
+				Ok(_) => {}
 				// Err(ReadlineError::Closed) => break, // Readline was closed via one way or another, cleanup other futures here and break out of the loop
 				Err(err) => {
 					writeln!(stdout, "Received err: {:?}", err)?;
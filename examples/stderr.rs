@@ -0,0 +1,38 @@
+//! The prompt and messages go to stderr, results to stdout, so that the
+//! results can be piped into another program while the prompt stays usable:
+//!
+//! ```sh
+//! cargo run --example stderr | cat
+//! ```
+
+use rustyline_async::{Readline, ReadlineEvent};
+use std::io::Write;
+use std::time::Duration;
+use tokio::time::sleep;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+	let (mut rl, mut log) = Readline::new_on_stderr("upcase> ".into())?;
+
+	loop {
+		tokio::select! {
+			_ = sleep(Duration::from_secs(3)) => {
+				writeln!(log, "Still waiting for input...")?;
+			}
+			cmd = rl.readline() => match cmd {
+				Ok(ReadlineEvent::Line(line)) => {
+					rl.add_history_entry(line.clone());
+					println!("{}", line.to_uppercase());
+				}
+				Ok(ReadlineEvent::Eof) | Ok(ReadlineEvent::Interrupted) => break,
+				Ok(_) => {}
+				Err(e) => {
+					writeln!(log, "Error: {e:?}")?;
+					break;
+				}
+			}
+		}
+	}
+	rl.close().await?;
+	Ok(())
+}
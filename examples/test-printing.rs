@@ -39,6 +39,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 				break;
 			}
 			Ok(ReadlineEvent::Interrupted) => writeln!(stdout, "^C")?,
+			Ok(_) => {}
 			Err(err) => {
 				writeln!(stdout, "Received err: {:?}", err)?;
 				break;
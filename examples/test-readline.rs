@@ -30,6 +30,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 					// writeln!(stdout, "^C")?;
 					continue;
 				}
+				Ok(_) => {}
 				Err(e) => {
 					writeln!(stdout, "Error: {e:?}")?;
 					break;
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use async_std::stream;
+use futures_util::{select, FutureExt, StreamExt};
+use rustyline_async::{Readline, ReadlineError, ReadlineEvent};
+
+#[async_std::main]
+async fn main() -> Result<(), ReadlineError> {
+	let mut periodic_timer = stream::interval(Duration::from_secs(1));
+
+	let (mut rl, stdout) = Readline::new("> ".to_owned()).unwrap();
+
+	tracing_subscriber::fmt()
+		.with_writer(stdout)
+		.with_max_level(tracing::Level::DEBUG)
+		.init();
+
+	let mut ticks = 0;
+	loop {
+		select! {
+			_ = periodic_timer.next().fuse() => {
+				ticks += 1;
+				tracing::debug!(ticks, "Timer went off");
+			}
+			command = rl.readline().fuse() => match command {
+				Ok(ReadlineEvent::Line(line)) => {
+					rl.add_history_entry(line.clone());
+					tracing::info!(%line, "Received a line");
+				}
+				Ok(ReadlineEvent::Eof) | Ok(ReadlineEvent::Interrupted) => {
+					tracing::warn!("Exiting...");
+					break;
+				}
+				Ok(_) => {}
+				Err(err) => {
+					tracing::error!(?err, "Received an error");
+					break;
+				}
+			}
+		}
+	}
+
+	rl.flush()?;
+	Ok(())
+}
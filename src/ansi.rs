@@ -0,0 +1,199 @@
+use unicode_width::UnicodeWidthChar;
+
+/// Where the stripper is inside an escape sequence
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum State {
+	#[default]
+	Ground,
+	// Just saw ESC
+	Escape,
+	// ESC followed by intermediate bytes (e.g. `ESC ( B`)
+	EscapeIntermediate,
+	// Inside `ESC [` parameters
+	Csi,
+	// Inside an OSC/DCS/APC/PM/SOS string, terminated by BEL or ST
+	String,
+	// Saw ESC inside a string, may be the start of ST (`ESC \`)
+	StringEscape,
+}
+
+/// Streaming filter that removes ANSI escape sequences (CSI, OSC and other
+/// escape strings) from a byte stream while leaving text content intact.
+///
+/// State is kept between calls so sequences split across two chunks are
+/// still removed completely.
+#[derive(Debug, Default, Clone)]
+pub struct AnsiStripper {
+	state: State,
+}
+
+impl AnsiStripper {
+	/// Strip escape sequences from `data`, appending the remaining text to `out`
+	pub fn strip(&mut self, data: &[u8], out: &mut Vec<u8>) {
+		for &byte in data {
+			self.state = match (self.state, byte) {
+				(State::Ground, 0x1b) => State::Escape,
+				(State::Ground, _) => {
+					out.push(byte);
+					State::Ground
+				}
+				(State::Escape, b'[') => State::Csi,
+				(State::Escape, b']' | b'P' | b'X' | b'^' | b'_') => State::String,
+				(State::Escape, 0x20..=0x2f) => State::EscapeIntermediate,
+				(State::Escape, 0x1b) => State::Escape,
+				// Any other byte finishes a two-byte escape sequence
+				(State::Escape, _) => State::Ground,
+				(State::EscapeIntermediate, 0x20..=0x2f) => State::EscapeIntermediate,
+				(State::EscapeIntermediate, _) => State::Ground,
+				// Final byte of a CSI sequence
+				(State::Csi, 0x40..=0x7e) => State::Ground,
+				(State::Csi, _) => State::Csi,
+				(State::String, 0x07) => State::Ground,
+				(State::String, 0x1b) => State::StringEscape,
+				(State::String, _) => State::String,
+				(State::StringEscape, b'\\') => State::Ground,
+				(State::StringEscape, 0x1b) => State::StringEscape,
+				(State::StringEscape, _) => State::String,
+			}
+		}
+	}
+}
+
+/// Cut `text` off after `width` columns, keeping escape sequences intact
+pub(crate) fn clip_to_width(text: &str, width: usize) -> String {
+	let mut stripper = AnsiStripper::default();
+	let mut visible = Vec::new();
+	let mut clipped = String::new();
+	let mut used = 0;
+	for c in text.chars() {
+		visible.clear();
+		stripper.strip(c.encode_utf8(&mut [0; 4]).as_bytes(), &mut visible);
+		if visible.is_empty() {
+			// Part of an escape sequence, which takes up no space
+			clipped.push(c);
+		} else {
+			let c_width = UnicodeWidthChar::width(c).unwrap_or(0);
+			if used + c_width <= width {
+				used += c_width;
+				clipped.push(c);
+			}
+		}
+	}
+	clipped
+}
+
+/// Insert `separator` into `text` in front of the visible text at each of
+/// `offsets`, counted in bytes without escape sequences, in ascending order
+pub(crate) fn insert_at_visible(text: &str, offsets: &[usize], separator: &str) -> String {
+	let mut stripper = AnsiStripper::default();
+	let mut visible = Vec::new();
+	let mut inserted = String::new();
+	let mut offsets = offsets.iter().peekable();
+	for c in text.chars() {
+		let before = visible.len();
+		stripper.strip(c.encode_utf8(&mut [0; 4]).as_bytes(), &mut visible);
+		if visible.len() > before {
+			while offsets.next_if(|&&offset| offset <= before).is_some() {
+				inserted += separator;
+			}
+		}
+		inserted.push(c);
+	}
+	// Past the end of the text
+	for _ in offsets {
+		inserted += separator;
+	}
+	inserted
+}
+
+/// Column the cursor ends up in after printing `data`, starting at `column`
+/// on a terminal `width` columns wide
+///
+/// `data` must not contain newlines, carriage returns go back to the start
+/// of the row.  Terminals only wrap when the next character is printed, so
+/// this returns `width` if the last row was filled exactly.
+pub(crate) fn advance_column(
+	stripper: &mut AnsiStripper,
+	data: &[u8],
+	mut column: usize,
+	width: usize,
+) -> usize {
+	let mut text = Vec::new();
+	stripper.strip(data, &mut text);
+	for c in String::from_utf8_lossy(&text).chars() {
+		if c == '\r' {
+			column = 0;
+			continue;
+		}
+		let c_width = UnicodeWidthChar::width(c).unwrap_or(0);
+		if column + c_width > width {
+			column = 0;
+		}
+		column += c_width;
+	}
+	column
+}
+
+#[cfg(test)]
+#[test]
+fn test_strip_sgr_and_osc() {
+	let mut stripper = AnsiStripper::default();
+	let mut out = Vec::new();
+	stripper.strip(
+		b"\x1b[1;31merror\x1b[0m: \x1b]8;;http://example.com\x07link\x1b]8;;\x1b\\ done\n",
+		&mut out,
+	);
+	assert_eq!(out, b"error: link done\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_strip_split_sequence() {
+	let mut stripper = AnsiStripper::default();
+	let mut out = Vec::new();
+	stripper.strip(b"foo \x1b[3", &mut out);
+	stripper.strip(b"8;5;12mbar\x1b", &mut out);
+	stripper.strip(b"[0m baz\x1b]0;tit", &mut out);
+	stripper.strip(b"le\x1b", &mut out);
+	stripper.strip(b"\\\n", &mut out);
+	assert_eq!(out, b"foo bar baz\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_clip_to_width() {
+	assert_eq!(clip_to_width("hello world", 5), "hello");
+	assert_eq!(
+		clip_to_width("\x1b[32mhello\x1b[0m world", 3),
+		"\x1b[32mhel\x1b[0m"
+	);
+	// Wide characters that don't fit entirely are dropped
+	assert_eq!(clip_to_width("日本語", 5), "日本");
+}
+
+#[cfg(test)]
+#[test]
+fn test_advance_column() {
+	let mut stripper = AnsiStripper::default();
+	assert_eq!(advance_column(&mut stripper, b"hello", 2, 10), 7);
+	assert_eq!(advance_column(&mut stripper, b"hello world!", 0, 5), 2);
+	assert_eq!(advance_column(&mut stripper, b"hello", 0, 5), 5);
+	// Wide characters don't get split over two rows
+	assert_eq!(advance_column(&mut stripper, "ab日".as_bytes(), 1, 4), 2);
+	assert_eq!(advance_column(&mut stripper, b"50%\r60", 0, 10), 2);
+	assert_eq!(
+		advance_column(&mut stripper, b"\x1b[31mab\x1b[0m", 0, 10),
+		2
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_insert_at_visible() {
+	let text = "\x1b[1mab\x1b[0mcd";
+	assert_eq!(
+		insert_at_visible(text, &[1, 2, 4], "|"),
+		"\x1b[1ma|b\x1b[0m|cd|"
+	);
+	assert_eq!(insert_at_visible(text, &[], "|"), text);
+}
@@ -0,0 +1,213 @@
+use clap::{Arg, Command};
+
+use crate::{current_word, split_words, Candidate, Completer};
+
+/// Completes the subcommands, flags and argument values of a
+/// [`clap::Command`], see [`Readline::set_completer()`]
+///
+/// The line is read as the arguments without the binary name, like with
+/// [`Command::no_binary_name()`].  Values are completed for arguments with
+/// possible values, such as value enums.  Hidden subcommands, flags and
+/// values aren't offered.
+///
+/// [`Readline::set_completer()`]: crate::Readline::set_completer
+pub struct ClapCompleter {
+	command: Command,
+}
+
+impl ClapCompleter {
+	pub fn new(mut command: Command) -> Self {
+		// Adds the help and version flags, and works out how many values
+		// each argument takes
+		command.build();
+		Self { command }
+	}
+}
+
+impl Completer for ClapCompleter {
+	fn complete(&mut self, line: &str, pos: usize) -> (usize, Vec<Candidate>) {
+		let word = current_word(line, pos);
+		let mut command = &self.command;
+		// Option waiting for its value
+		let mut option: Option<&Arg> = None;
+		let mut positionals = 0;
+		let mut only_positionals = false;
+		for previous in split_words(&line[..word.start]) {
+			let previous = previous.text.as_str();
+			if let Some(arg) = option.take() {
+				if arg.get_num_args().is_some_and(|num| num.max_values() > 1) {
+					option = Some(arg);
+				}
+				continue;
+			}
+			if only_positionals || !previous.starts_with('-') || previous == "-" {
+				match command.find_subcommand(previous) {
+					Some(subcommand) if positionals == 0 => command = subcommand,
+					_ => positionals += 1,
+				}
+			} else if previous == "--" {
+				only_positionals = true;
+			} else if let Some(long) = previous.strip_prefix("--") {
+				if !long.contains('=') {
+					option = find_long(command, long).filter(|arg| takes_values(arg));
+				}
+			} else {
+				// A group of short flags, the last one may take a value
+				let short = previous.chars().last().unwrap();
+				if previous.len() == 2 {
+					option = find_short(command, short).filter(|arg| takes_values(arg));
+				}
+			}
+		}
+
+		let text = word.text.as_str();
+		let mut candidates = Vec::new();
+		if let Some(arg) = option {
+			candidates = values(arg, "");
+		} else if let Some((long, _)) = text
+			.strip_prefix("--")
+			.and_then(|long| long.split_once('='))
+			.filter(|_| !only_positionals)
+		{
+			if let Some(arg) = find_long(command, long) {
+				candidates = values(arg, &format!("--{long}="));
+			}
+		} else if text.starts_with('-') && !only_positionals {
+			for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+				let help = arg.get_help().map(ToString::to_string);
+				let flags = arg.get_long().map(|long| format!("--{long}")).into_iter();
+				let flags = flags.chain(arg.get_short().map(|short| format!("-{short}")));
+				for flag in flags {
+					let mut candidate = Candidate::new(flag);
+					candidate.description = help.clone();
+					candidates.push(candidate);
+				}
+			}
+		} else {
+			if positionals == 0 {
+				for subcommand in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+					let mut candidate = Candidate::new(subcommand.get_name());
+					candidate.description = subcommand.get_about().map(ToString::to_string);
+					candidates.push(candidate);
+				}
+			}
+			if let Some(arg) = positional(command, positionals) {
+				candidates.extend(values(arg, ""));
+			}
+		}
+		candidates.retain(|candidate| candidate.replacement.starts_with(text));
+		for candidate in &mut candidates {
+			candidate.replacement = word.escape(&candidate.replacement);
+		}
+		(word.start, candidates)
+	}
+}
+
+fn takes_values(arg: &Arg) -> bool {
+	arg.get_num_args().is_some_and(|num| num.takes_values())
+}
+
+fn find_long<'a>(command: &'a Command, long: &str) -> Option<&'a Arg> {
+	command.get_arguments().find(|arg| {
+		arg.get_long() == Some(long)
+			|| arg
+				.get_all_aliases()
+				.is_some_and(|aliases| aliases.contains(&long))
+	})
+}
+
+fn find_short(command: &Command, short: char) -> Option<&Arg> {
+	command
+		.get_arguments()
+		.find(|arg| arg.get_short() == Some(short))
+}
+
+// The positional argument after `count` others, or the last one if it takes
+// any number of values
+fn positional(command: &Command, count: usize) -> Option<&Arg> {
+	let mut seen = 0;
+	let mut last = None;
+	for arg in command.get_positionals() {
+		let values = arg.get_num_args().map_or(1, |num| num.max_values());
+		if count < seen + values {
+			return Some(arg);
+		}
+		seen = seen.saturating_add(values);
+		last = Some(arg);
+	}
+	last.filter(|arg| {
+		arg.get_num_args()
+			.is_some_and(|num| num.max_values() == usize::MAX)
+	})
+}
+
+// The possible values of `arg`, each after `prefix`
+fn values(arg: &Arg, prefix: &str) -> Vec<Candidate> {
+	arg.get_possible_values()
+		.into_iter()
+		.filter(|value| !value.is_hide_set())
+		.map(|value| {
+			let mut candidate = Candidate::new(format!("{prefix}{}", value.get_name()));
+			candidate.description = value.get_help().map(ToString::to_string);
+			candidate
+		})
+		.collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_clap_completer() {
+	let command = Command::new("repl")
+		.subcommand(
+			Command::new("export")
+				.about("Export the data")
+				.disable_help_flag(true)
+				.arg(
+					Arg::new("format")
+						.long("format")
+						.short('f')
+						.value_parser(["json", "csv"]),
+				)
+				.arg(
+					Arg::new("verbose")
+						.long("verbose")
+						.action(clap::ArgAction::SetTrue),
+				)
+				.arg(Arg::new("target").value_parser(["file", "stdout"])),
+		)
+		.subcommand(Command::new("exit").about("Leave"))
+		.subcommand(Command::new("secret").hide(true));
+	let mut completer = ClapCompleter::new(command);
+	let mut complete = |line: &str| {
+		let (start, candidates) = completer.complete(line, line.len());
+		let candidates: Vec<String> = candidates
+			.into_iter()
+			.map(|candidate| candidate.replacement)
+			.collect();
+		(start, candidates)
+	};
+
+	assert_eq!(complete("ex"), (0, vec!["export".into(), "exit".into()]));
+	assert_eq!(
+		complete("export --"),
+		(7, vec!["--format".into(), "--verbose".into()])
+	);
+	assert_eq!(
+		complete("export -f "),
+		(10, vec!["json".into(), "csv".into()])
+	);
+	assert_eq!(
+		complete("export --format=c"),
+		(7, vec!["--format=csv".into()])
+	);
+	assert_eq!(
+		complete("export --format csv "),
+		(20, vec!["file".into(), "stdout".into()])
+	);
+	assert_eq!(complete("export --verbose s"), (17, vec!["stdout".into()]));
+	assert_eq!(complete("export file "), (12, vec![]));
+	assert_eq!(complete("s"), (0, vec![]));
+
+	let (_, candidates) = completer.complete("exi", 3);
+	assert_eq!(candidates[0].description.as_deref(), Some("Leave"));
+}
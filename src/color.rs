@@ -0,0 +1,333 @@
+use std::borrow::Cow;
+
+use crossterm::style::{Color, ContentStyle};
+
+/// How much styling the terminal shows, see
+/// [`ReadlineBuilder::color_support()`][crate::ReadlineBuilder::color_support]
+///
+/// Colors in the prompt, the input, hints and the rest of the [`Theme`] are
+/// converted to the nearest ones the terminal has, or left out.  Output from
+/// [`SharedWriter`]s is left alone, see
+/// [`Readline::set_strip_ansi()`][crate::Readline::set_strip_ansi].
+///
+/// [`Theme`]: crate::Theme
+/// [`SharedWriter`]: crate::SharedWriter
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+	/// No colors or text attributes at all, e.g. for `NO_COLOR` or logs
+	Monochrome,
+	/// The 16 basic colors
+	Basic,
+	/// The 256 color palette
+	Ansi256,
+	/// 24-bit colors, everything is passed through
+	#[default]
+	TrueColor,
+}
+
+// The basic colors as xterm shows them, for finding the nearest one
+const BASIC: [(u8, u8, u8); 16] = [
+	(0, 0, 0),
+	(205, 0, 0),
+	(0, 205, 0),
+	(205, 205, 0),
+	(0, 0, 238),
+	(205, 0, 205),
+	(0, 205, 205),
+	(229, 229, 229),
+	(127, 127, 127),
+	(255, 0, 0),
+	(0, 255, 0),
+	(255, 255, 0),
+	(92, 92, 255),
+	(255, 0, 255),
+	(0, 255, 255),
+	(255, 255, 255),
+];
+
+// The basic colors in the same order, as crossterm names them
+const NAMED: [Color; 16] = [
+	Color::Black,
+	Color::DarkRed,
+	Color::DarkGreen,
+	Color::DarkYellow,
+	Color::DarkBlue,
+	Color::DarkMagenta,
+	Color::DarkCyan,
+	Color::Grey,
+	Color::DarkGrey,
+	Color::Red,
+	Color::Green,
+	Color::Yellow,
+	Color::Blue,
+	Color::Magenta,
+	Color::Cyan,
+	Color::White,
+];
+
+// Levels of each channel in the 6x6x6 color cube of the 256 color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl ColorSupport {
+	/// Work out what the terminal supports from the environment
+	///
+	/// `NO_COLOR` set to anything but an empty string, a `TERM` of `dumb` or
+	/// no `TERM` at all (except on Windows) turn styling off.  Otherwise a
+	/// `COLORTERM` of `truecolor` or `24bit` means 24-bit colors, and a `TERM`
+	/// containing `256color` the 256 color palette.
+	pub fn detect() -> Self {
+		let var = |name| std::env::var(name).ok();
+		Self::from_env(
+			var("NO_COLOR").as_deref(),
+			var("TERM").as_deref(),
+			var("COLORTERM").as_deref(),
+		)
+	}
+
+	fn from_env(no_color: Option<&str>, term: Option<&str>, colorterm: Option<&str>) -> Self {
+		if no_color.is_some_and(|no_color| !no_color.is_empty()) {
+			return Self::Monochrome;
+		}
+		match term {
+			None if cfg!(windows) => Self::TrueColor,
+			None | Some("dumb") => Self::Monochrome,
+			_ if matches!(colorterm, Some("truecolor" | "24bit")) => Self::TrueColor,
+			Some(term) if term.contains("256color") => Self::Ansi256,
+			Some(_) => Self::Basic,
+		}
+	}
+
+	/// `text` with its SGR escape sequences converted to what is supported,
+	/// other escape sequences are kept
+	pub(crate) fn downgrade(self, text: &str) -> Cow<'_, str> {
+		if self == Self::TrueColor || !text.contains('\x1b') {
+			return Cow::Borrowed(text);
+		}
+		let mut downgraded = String::new();
+		let mut rest = text;
+		while let Some(start) = rest.find("\x1b[") {
+			downgraded += &rest[..start];
+			let sequence = &rest[start + 2..];
+			let Some(end) = sequence.find(|c| ('\x40'..='\x7e').contains(&c)) else {
+				// Unterminated, leave it to the terminal
+				rest = &rest[start..];
+				break;
+			};
+			if sequence.as_bytes()[end] == b'm' {
+				if let Some(params) = self.sgr_params(&sequence[..end]) {
+					downgraded += &format!("\x1b[{params}m");
+				}
+			} else {
+				downgraded += &rest[start..start + 2 + end + 1];
+			}
+			rest = &sequence[end + 1..];
+		}
+		downgraded += rest;
+		Cow::Owned(downgraded)
+	}
+
+	/// `style` with its colors converted to what is supported, like
+	/// [`downgrade()`][Self::downgrade] does for escape sequences
+	pub(crate) fn downgrade_style(self, style: ContentStyle) -> ContentStyle {
+		match self {
+			Self::TrueColor => style,
+			Self::Monochrome => ContentStyle::new(),
+			_ => ContentStyle {
+				foreground_color: style
+					.foreground_color
+					.map(|color| self.downgrade_color(color)),
+				background_color: style
+					.background_color
+					.map(|color| self.downgrade_color(color)),
+				// Basic colors have no code for the underline color
+				underline_color: style
+					.underline_color
+					.filter(|_| self == Self::Ansi256)
+					.map(|color| self.downgrade_color(color)),
+				attributes: style.attributes,
+			},
+		}
+	}
+
+	fn downgrade_color(self, color: Color) -> Color {
+		let (index, rgb) = match color {
+			Color::Rgb { r, g, b } => (rgb_to_index(r, g, b), (r, g, b)),
+			Color::AnsiValue(index) => (index, index_to_rgb(index)),
+			named => return named,
+		};
+		match (self, index) {
+			(Self::Ansi256, _) => Color::AnsiValue(index),
+			(_, 0..=15) => NAMED[index as usize],
+			_ => NAMED[nearest_basic(rgb) as usize],
+		}
+	}
+
+	// The parameters of an SGR sequence with unsupported colors replaced, or
+	// `None` if nothing is left of it
+	fn sgr_params(self, params: &str) -> Option<String> {
+		if self == Self::Monochrome {
+			return None;
+		}
+		// An empty SGR sequence resets everything
+		if params.is_empty() {
+			return Some(String::new());
+		}
+		let codes: Vec<&str> = params.split(';').collect();
+		let number = |i: usize| codes.get(i).and_then(|code| code.parse::<u8>().ok());
+		let mut kept = Vec::new();
+		let mut i = 0;
+		while i < codes.len() {
+			let target = codes[i];
+			let color = match (target, codes.get(i + 1)) {
+				("38" | "48" | "58", Some(&"5")) => number(i + 2).map(|index| {
+					i += 3;
+					(index, index_to_rgb(index))
+				}),
+				("38" | "48" | "58", Some(&"2")) => {
+					match (number(i + 2), number(i + 3), number(i + 4)) {
+						(Some(r), Some(g), Some(b)) => {
+							i += 5;
+							Some((rgb_to_index(r, g, b), (r, g, b)))
+						}
+						_ => None,
+					}
+				}
+				_ => None,
+			};
+			let Some((index, rgb)) = color else {
+				kept.push(target.to_string());
+				i += 1;
+				continue;
+			};
+			match self {
+				Self::Ansi256 => kept.push(format!("{target};5;{index}")),
+				// Basic colors have no code for the underline color
+				_ if target == "58" => {}
+				_ => {
+					let basic = match index {
+						0..=15 => index,
+						_ => nearest_basic(rgb),
+					};
+					let base = match (target, basic < 8) {
+						("38", true) => 30,
+						("38", false) => 90 - 8,
+						(_, true) => 40,
+						(_, false) => 100 - 8,
+					};
+					kept.push((base + basic).to_string());
+				}
+			}
+		}
+		(!kept.is_empty()).then(|| kept.join(";"))
+	}
+}
+
+fn index_to_rgb(index: u8) -> (u8, u8, u8) {
+	match index {
+		0..=15 => BASIC[index as usize],
+		16..=231 => {
+			let index = index - 16;
+			let level = |i: u8| CUBE[i as usize % 6];
+			(level(index / 36), level(index / 6), level(index))
+		}
+		_ => {
+			let gray = 8 + (index - 232) * 10;
+			(gray, gray, gray)
+		}
+	}
+}
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+	let d = |a: u8, b: u8| (a as i32 - b as i32).unsigned_abs().pow(2);
+	d(r1, r2) + d(g1, g2) + d(b1, b2)
+}
+
+// The nearest color of the cube or the gray ramp of the 256 color palette
+fn rgb_to_index(r: u8, g: u8, b: u8) -> u8 {
+	let level = |c: u8| {
+		(0..6)
+			.min_by_key(|&i| (CUBE[i] as i32 - c as i32).abs())
+			.unwrap() as u8
+	};
+	let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+	let average = (r as u32 + g as u32 + b as u32) / 3;
+	let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+	[cube, gray]
+		.into_iter()
+		.min_by_key(|&index| distance(index_to_rgb(index), (r, g, b)))
+		.unwrap()
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> u8 {
+	(0..16)
+		.min_by_key(|&i| distance(BASIC[i as usize], rgb))
+		.unwrap()
+}
+
+#[cfg(test)]
+#[test]
+fn test_detect_color_support() {
+	use ColorSupport::*;
+	assert_eq!(
+		ColorSupport::from_env(Some("1"), Some("xterm-256color"), None),
+		Monochrome
+	);
+	assert_eq!(
+		ColorSupport::from_env(Some(""), Some("xterm-256color"), None),
+		Ansi256
+	);
+	assert_eq!(
+		ColorSupport::from_env(None, Some("dumb"), Some("truecolor")),
+		Monochrome
+	);
+	assert_eq!(
+		ColorSupport::from_env(None, Some("xterm"), Some("24bit")),
+		TrueColor
+	);
+	assert_eq!(ColorSupport::from_env(None, Some("screen"), None), Basic);
+}
+
+#[cfg(test)]
+#[test]
+fn test_downgrade_colors() {
+	let text = "\x1b[1;38;5;9mred\x1b[0m \x1b[48;2;0;0;130mnavy\x1b[m\x1b[2K";
+	assert_eq!(ColorSupport::TrueColor.downgrade(text), text);
+	assert_eq!(
+		ColorSupport::Ansi256.downgrade(text),
+		"\x1b[1;38;5;9mred\x1b[0m \x1b[48;5;18mnavy\x1b[m\x1b[2K"
+	);
+	assert_eq!(
+		ColorSupport::Basic.downgrade(text),
+		"\x1b[1;91mred\x1b[0m \x1b[44mnavy\x1b[m\x1b[2K"
+	);
+	// Only the other escape sequences are left
+	assert_eq!(ColorSupport::Monochrome.downgrade(text), "red navy\x1b[2K");
+	assert_eq!(ColorSupport::Basic.downgrade("\x1b[58;5;1mx"), "x");
+}
+
+#[cfg(test)]
+#[test]
+fn test_downgrade_style() {
+	use crossterm::style::{Attribute, Stylize};
+
+	let style = *"x"
+		.with(Color::Rgb { r: 0, g: 0, b: 130 })
+		.on(Color::AnsiValue(9))
+		.underline(Color::Rgb { r: 255, g: 0, b: 0 })
+		.bold()
+		.style();
+	assert_eq!(ColorSupport::TrueColor.downgrade_style(style), style);
+	let ansi256 = ColorSupport::Ansi256.downgrade_style(style);
+	assert_eq!(ansi256.foreground_color, Some(Color::AnsiValue(18)));
+	assert_eq!(ansi256.background_color, Some(Color::AnsiValue(9)));
+	assert_eq!(ansi256.underline_color, Some(Color::AnsiValue(196)));
+	let basic = ColorSupport::Basic.downgrade_style(style);
+	assert_eq!(basic.foreground_color, Some(Color::DarkBlue));
+	assert_eq!(basic.background_color, Some(Color::Red));
+	assert_eq!(basic.underline_color, None);
+	assert!(basic.attributes.has(Attribute::Bold));
+	assert_eq!(
+		ColorSupport::Monochrome.downgrade_style(style),
+		ContentStyle::new()
+	);
+}
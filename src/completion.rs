@@ -0,0 +1,349 @@
+use std::{future::Future, ops::Range};
+
+use crossterm::style::ContentStyle;
+use futures_util::future::BoxFuture;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::{
+	matching::Match,
+	theme::{layer, Theme},
+};
+
+/// A completion offered by a [`Completer`]
+///
+/// Strings convert into candidates that are shown as they are inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+	/// Text inserted in place of the text being completed
+	pub replacement: String,
+	/// Shown in the completion menu instead of the replacement
+	pub label: Option<String>,
+	/// Shown next to the candidate in the completion menu, e.g. what a
+	/// command does
+	pub description: Option<String>,
+}
+
+impl Candidate {
+	pub fn new(replacement: impl Into<String>) -> Self {
+		Self {
+			replacement: replacement.into(),
+			label: None,
+			description: None,
+		}
+	}
+	/// Show `label` in the completion menu instead of the replacement
+	pub fn with_label(mut self, label: impl Into<String>) -> Self {
+		self.label = Some(label.into());
+		self
+	}
+	/// Show `description` next to the candidate in the completion menu
+	pub fn with_description(mut self, description: impl Into<String>) -> Self {
+		self.description = Some(description.into());
+		self
+	}
+	/// What the completion menu shows for this candidate
+	pub fn display(&self) -> &str {
+		self.label.as_deref().unwrap_or(&self.replacement)
+	}
+}
+
+impl From<String> for Candidate {
+	fn from(replacement: String) -> Self {
+		Self::new(replacement)
+	}
+}
+
+impl From<&str> for Candidate {
+	fn from(replacement: &str) -> Self {
+		Self::new(replacement)
+	}
+}
+
+/// What Tab does when the candidates share nothing more than what is already
+/// typed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionStyle {
+	/// Show a menu below the input to pick a candidate from
+	#[default]
+	Menu,
+	/// Like bash: ring the bell, and list the candidates above the prompt
+	/// when Tab is pressed again
+	List,
+}
+
+/// Provides candidates for Tab completion, see
+/// [`Readline::set_completer()`][crate::Readline::set_completer]
+///
+/// Implemented for closures with the same signature as
+/// [`complete()`][Completer::complete].
+pub trait Completer: Send {
+	/// Find completions for the input `line`, with the cursor at byte offset
+	/// `pos`
+	///
+	/// Returns the byte offset where the text to be completed starts, usually
+	/// the start of the word before the cursor, and the candidates to replace
+	/// the text from there up to the cursor with.  [`current_word()`] finds
+	/// that word for shell-like input with quotes and escapes.
+	///
+	/// [`current_word()`]: crate::current_word
+	fn complete(&mut self, line: &str, pos: usize) -> (usize, Vec<Candidate>);
+}
+
+impl<F> Completer for F
+where
+	F: FnMut(&str, usize) -> (usize, Vec<Candidate>) + Send,
+{
+	fn complete(&mut self, line: &str, pos: usize) -> (usize, Vec<Candidate>) {
+		self(line, pos)
+	}
+}
+
+/// Provides candidates for Tab completion from a future, e.g. to ask a
+/// language server, see
+/// [`Readline::set_async_completer()`][crate::Readline::set_async_completer]
+///
+/// Implemented for closures with the same signature as
+/// [`complete()`][AsyncCompleter::complete] that return any `Send` future.
+pub trait AsyncCompleter: Send {
+	/// Like [`Completer::complete()`], but returns a future that resolves to
+	/// the completions
+	fn complete(&mut self, line: &str, pos: usize) -> BoxFuture<'static, (usize, Vec<Candidate>)>;
+}
+
+impl<F, Fut> AsyncCompleter for F
+where
+	F: FnMut(&str, usize) -> Fut + Send,
+	Fut: Future<Output = (usize, Vec<Candidate>)> + Send + 'static,
+{
+	fn complete(&mut self, line: &str, pos: usize) -> BoxFuture<'static, (usize, Vec<Candidate>)> {
+		Box::pin(self(line, pos))
+	}
+}
+
+/// Where completions come from
+pub(crate) enum CompletionSource {
+	Sync(Box<dyn Completer>),
+	Async(Box<dyn AsyncCompleter>),
+}
+
+/// Completions being looked up by an [`AsyncCompleter`]
+pub(crate) struct PendingCompletion {
+	// Input and cursor when Tab was pressed, the result is dropped if either
+	// changed in the meantime
+	pub line: String,
+	pub cursor: usize,
+	pub future: BoxFuture<'static, (usize, Vec<Candidate>)>,
+}
+
+/// Most candidates shown below the input at once
+const MENU_ROWS: usize = 8;
+
+/// Candidates shown below the input to choose from
+pub(crate) struct CompletionMenu {
+	// Byte offset of the text the candidates replace, up to the cursor
+	pub start: usize,
+	pub candidates: Vec<Match>,
+	pub selected: Option<usize>,
+	// Width of the widest label, descriptions are aligned after it
+	label_width: usize,
+}
+
+impl CompletionMenu {
+	pub fn new(start: usize, candidates: Vec<Match>) -> Self {
+		let label_width = candidates
+			.iter()
+			.map(|matched| matched.candidate.display().width())
+			.max()
+			.unwrap_or(0);
+		Self {
+			start,
+			candidates,
+			selected: None,
+			label_width,
+		}
+	}
+	/// Select the next candidate, or the first one after the last
+	pub fn select_next(&mut self) {
+		self.selected = Some(match self.selected {
+			Some(selected) if selected + 1 < self.candidates.len() => selected + 1,
+			_ => 0,
+		});
+	}
+	/// Select the previous candidate, or the last one before the first
+	pub fn select_previous(&mut self) {
+		self.selected = Some(match self.selected {
+			Some(selected) if selected > 0 => selected - 1,
+			_ => self.candidates.len() - 1,
+		});
+	}
+	/// The candidates on screen, scrolled so that the selected one is shown
+	pub fn visible(&self) -> Range<usize> {
+		let rows = self.candidates.len().min(MENU_ROWS);
+		let first = self
+			.selected
+			.map_or(0, |selected| (selected + 1).saturating_sub(rows));
+		first..first + rows
+	}
+	/// The row for the candidate at `index`, clipped to `width` columns, in
+	/// the styles of `theme` and with its description in a column after the
+	/// labels
+	pub fn row(&self, index: usize, width: usize, theme: &Theme) -> String {
+		let Match {
+			candidate,
+			positions,
+		} = &self.candidates[index];
+		let style = match self.selected == Some(index) {
+			true => theme.selection,
+			false => theme.menu,
+		};
+		// Runs of text in the same style, without what doesn't fit
+		let mut runs: Vec<(ContentStyle, String)> = Vec::new();
+		let mut column = 0;
+		let mut put = |style: ContentStyle, c: char| {
+			column += c.width().unwrap_or(0);
+			if column > width {
+				return false;
+			}
+			match runs.last_mut() {
+				Some((last, text)) if *last == style => text.push(c),
+				_ => runs.push((style, c.to_string())),
+			}
+			true
+		};
+		let label = candidate.display().char_indices().map(|(offset, c)| {
+			match positions.contains(&offset) {
+				true => (layer(style, theme.matched), c),
+				false => (style, c),
+			}
+		});
+		let description = candidate.description.iter().flat_map(|description| {
+			let padding = self.label_width + 2 - candidate.display().width();
+			let style = layer(style, theme.description);
+			std::iter::repeat_n(' ', padding)
+				.chain(description.chars())
+				.map(move |c| (style, c))
+		});
+		for (style, c) in label.chain(description) {
+			if !put(style, c) {
+				break;
+			}
+		}
+		runs.into_iter()
+			.map(|(style, text)| style.apply(text).to_string())
+			.collect()
+	}
+}
+
+/// The candidates for printing above the prompt, in columns that fit in
+/// `width`, or one per row with their descriptions
+pub(crate) fn format_list(candidates: &[Match], width: usize) -> String {
+	let labels: Vec<&str> = candidates
+		.iter()
+		.map(|matched| matched.candidate.display())
+		.collect();
+	let label_width = labels.iter().map(|label| label.width()).max().unwrap_or(0);
+	let mut list = String::new();
+	if candidates
+		.iter()
+		.any(|matched| matched.candidate.description.is_some())
+	{
+		for (label, matched) in labels.iter().zip(candidates) {
+			let description = matched.candidate.description.as_deref().unwrap_or("");
+			let padding = label_width - label.width();
+			list += format!("{label}{:padding$}  {description}", "").trim_end();
+			list.push('\n');
+		}
+		return list;
+	}
+	let columns = (width / (label_width + 2)).max(1);
+	for row in labels.chunks(columns) {
+		for (i, label) in row.iter().enumerate() {
+			list += label;
+			if i + 1 < row.len() {
+				let padding = label_width + 2 - label.width();
+				list += &" ".repeat(padding);
+			}
+		}
+		list.push('\n');
+	}
+	list
+}
+
+/// The longest prefix shared by all `candidates`
+pub(crate) fn common_prefix(candidates: &[Match]) -> &str {
+	let Some((first, rest)) = candidates.split_first() else {
+		return "";
+	};
+	let first = &first.candidate.replacement;
+	let mut len = first.len();
+	for candidate in rest.iter().map(|matched| &matched.candidate.replacement) {
+		len = first
+			.char_indices()
+			.zip(candidate.chars())
+			.find(|((_, a), b)| a != b)
+			.map_or(len.min(candidate.len()), |((i, _), _)| i.min(len));
+	}
+	&first[..len]
+}
+
+#[cfg(test)]
+#[test]
+fn test_menu_selection() {
+	let candidates = (0..10).map(|i| Match {
+		candidate: i.to_string().into(),
+		positions: Vec::new(),
+	});
+	let mut menu = CompletionMenu::new(0, candidates.collect());
+	assert_eq!(menu.visible(), 0..8);
+	menu.select_previous();
+	assert_eq!(menu.selected, Some(9));
+	assert_eq!(menu.visible(), 2..10);
+	menu.select_next();
+	assert_eq!(menu.selected, Some(0));
+	assert_eq!(menu.visible(), 0..8);
+}
+
+#[cfg(test)]
+#[test]
+fn test_format_list() {
+	let matches = |candidates: Vec<Candidate>| {
+		candidates
+			.into_iter()
+			.map(|candidate| Match {
+				candidate,
+				positions: Vec::new(),
+			})
+			.collect::<Vec<_>>()
+	};
+	let words = matches(
+		["cat", "cd", "chmod", "cp", "cut"]
+			.map(Candidate::from)
+			.into(),
+	);
+	assert_eq!(format_list(&words, 16), "cat    cd\nchmod  cp\ncut\n");
+	assert_eq!(format_list(&words, 4), "cat\ncd\nchmod\ncp\ncut\n");
+	let described = matches(vec![
+		Candidate::new("cat").with_description("Print files"),
+		Candidate::new("chmod"),
+	]);
+	assert_eq!(format_list(&described, 80), "cat    Print files\nchmod\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_common_prefix() {
+	let strings = |s: &[&str]| {
+		s.iter()
+			.map(|&s| Match {
+				candidate: s.into(),
+				positions: Vec::new(),
+			})
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(common_prefix(&strings(&["help", "hello", "helm"])), "hel");
+	assert_eq!(common_prefix(&strings(&["hello", "hell"])), "hell");
+	assert_eq!(common_prefix(&strings(&["été", "éte"])), "ét");
+	assert_eq!(common_prefix(&strings(&["quit"])), "quit");
+	assert_eq!(common_prefix(&strings(&["a", "b"])), "");
+	assert_eq!(common_prefix(&[]), "");
+}
@@ -0,0 +1,103 @@
+use crate::History;
+
+/// When `!` designators in the input are replaced by history entries, like
+/// in bash, see
+/// [`Readline::set_history_expansion()`][crate::Readline::set_history_expansion]
+///
+/// `!!` is the newest entry, `!n` the entry numbered `n` counting the oldest
+/// as 1, `!-n` the `n`-th newest and `!prefix` the newest entry starting with
+/// `prefix`.  A `!` in single quotes, after a backslash or followed by a
+/// space, `=` or `(` is kept as it is.  Off by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryExpansion {
+	/// Expand the input when Enter is pressed, ringing the bell instead if an
+	/// entry isn't found
+	pub on_enter: bool,
+	/// Expand the input when a space is typed at its end, like bash's
+	/// `magic-space`
+	pub on_space: bool,
+	/// Show the input expanded on Enter for editing instead of returning it,
+	/// like bash's `histverify`
+	pub verify: bool,
+}
+
+// Characters that end a `!prefix` designator
+const DELIMITERS: &str = ";&|()<>:\"'";
+
+/// `line` with its designators replaced, or `None` if an entry isn't found
+pub(crate) fn expand(line: &str, history: &History) -> Option<String> {
+	let entries = history.get_entries();
+	let mut expanded = String::new();
+	let mut in_quotes = false;
+	let mut chars = line.char_indices().peekable();
+	while let Some((offset, c)) = chars.next() {
+		match c {
+			'\\' if !in_quotes => {
+				expanded.push(c);
+				if let Some((_, escaped)) = chars.next() {
+					expanded.push(escaped);
+				}
+				continue;
+			}
+			'\'' => in_quotes = !in_quotes,
+			'!' if !in_quotes => {
+				let rest = &line[offset + 1..];
+				let end = rest.find(|c: char| c.is_whitespace() || DELIMITERS.contains(c));
+				let designator = &rest[..end.unwrap_or(rest.len())];
+				let (entry, consumed) = if rest.starts_with('!') {
+					(entries.back(), 1)
+				} else if designator.is_empty() || rest.starts_with('=') {
+					expanded.push(c);
+					continue;
+				} else {
+					let number = designator.strip_prefix('-');
+					let newest = number.is_some();
+					let number = number.unwrap_or(designator);
+					let digits = number.len()
+						- number
+							.trim_start_matches(|c: char| c.is_ascii_digit())
+							.len();
+					if digits != 0 {
+						let n: usize = number[..digits].parse().ok()?;
+						let index = match newest {
+							true => entries.len().checked_sub(n),
+							false => n.checked_sub(1),
+						};
+						(index.and_then(|i| entries.get(i)), newest as usize + digits)
+					} else {
+						let entry = entries
+							.iter()
+							.rev()
+							.find(|entry| entry.line.starts_with(designator));
+						(entry, designator.len())
+					}
+				};
+				expanded += &entry?.line;
+				while chars.next_if(|&(i, _)| i <= offset + consumed).is_some() {}
+				continue;
+			}
+			_ => {}
+		}
+		expanded.push(c);
+	}
+	Some(expanded)
+}
+
+#[cfg(test)]
+#[test]
+fn test_expand() {
+	let mut history = History::default();
+	history.set_entries(["make", "git status", "git push", "ls -l"]);
+	let expand = |line| expand(line, &history);
+	assert_eq!(expand("sudo !!").as_deref(), Some("sudo ls -l"));
+	assert_eq!(expand("!2 && !-1").as_deref(), Some("git status && ls -l"));
+	assert_eq!(expand("!git; !m").as_deref(), Some("git push; make"));
+	assert_eq!(expand("!5"), None);
+	assert_eq!(expand("!-5"), None);
+	assert_eq!(expand("!cargo"), None);
+	// Kept as they are
+	assert_eq!(
+		expand(r"echo '!!' \!! ! a!=b !(x)").as_deref(),
+		Some(r"echo '!!' \!! ! a!=b !(x)")
+	);
+}
@@ -0,0 +1,21 @@
+/// Styles the input for display, e.g. to color keywords and strings, see
+/// [`Readline::set_highlighter()`][crate::Readline::set_highlighter]
+///
+/// Implemented for closures with the same signature as
+/// [`highlight()`][Highlighter::highlight].
+pub trait Highlighter: Send {
+	/// The input `line` with ANSI escape sequences added
+	///
+	/// Only escape sequences may be added, the visible text must stay the
+	/// same, since the cursor is placed by the unstyled input.
+	fn highlight(&mut self, line: &str) -> String;
+}
+
+impl<F> Highlighter for F
+where
+	F: FnMut(&str) -> String + Send,
+{
+	fn highlight(&mut self, line: &str) -> String {
+		self(line)
+	}
+}
@@ -0,0 +1,57 @@
+/// Provides hints shown dimmed after the input, e.g. the arguments expected
+/// by the command being typed, see
+/// [`Readline::set_hinter()`][crate::Readline::set_hinter]
+///
+/// Implemented for closures with the same signature as
+/// [`hint()`][Hinter::hint].
+pub trait Hinter: Send {
+	/// The hint for the input `line`, with the cursor at byte offset `pos`
+	///
+	/// Asked again after every key press.  The keys that accept history
+	/// suggestions insert the hint too, see [`AcceptHint`].
+	fn hint(&mut self, line: &str, pos: usize) -> Option<String>;
+}
+
+impl<F> Hinter for F
+where
+	F: FnMut(&str, usize) -> Option<String> + Send,
+{
+	fn hint(&mut self, line: &str, pos: usize) -> Option<String> {
+		self(line, pos)
+	}
+}
+
+/// How much of the history suggestion or hint after the input a key inserts,
+/// see [`Readline::set_accept_hint_keys()`][crate::Readline::set_accept_hint_keys]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptHint {
+	/// All of it
+	All,
+	/// Up to the end of its next word
+	Word,
+}
+
+impl AcceptHint {
+	/// The part of `hint` to insert
+	pub(crate) fn part(self, hint: &str) -> &str {
+		match self {
+			Self::All => hint,
+			Self::Word => {
+				let start = hint.len() - hint.trim_start().len();
+				let end = hint[start..]
+					.find(char::is_whitespace)
+					.map_or(hint.len(), |end| start + end);
+				&hint[..end]
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_accept_hint() {
+	assert_eq!(AcceptHint::All.part(" test --all"), " test --all");
+	assert_eq!(AcceptHint::Word.part(" test --all"), " test");
+	assert_eq!(AcceptHint::Word.part("go test"), "go");
+	assert_eq!(AcceptHint::Word.part("  end"), "  end");
+}
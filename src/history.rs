@@ -1,37 +1,241 @@
-use std::collections::VecDeque;
+use std::{
+	collections::{vec_deque, VecDeque},
+	fmt,
+	fs::{self, OpenOptions},
+	io::{self, Write},
+	path::Path,
+	time::{Duration, SystemTime},
+};
+
+/// A line in the history, with when it was entered and what the application
+/// recorded about it, see
+/// [`Readline::get_history_entries()`][crate::Readline::get_history_entries]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+	pub line: String,
+	/// When the line was added, if known
+	pub time: Option<SystemTime>,
+	/// Exit status of the command, set by the application, see
+	/// [`Readline::last_history_entry_mut()`][crate::Readline::last_history_entry_mut]
+	pub exit_status: Option<i32>,
+	/// Any label set by the application
+	pub tag: Option<String>,
+}
+
+impl HistoryEntry {
+	/// An entry for `line` without a time or other metadata
+	pub fn new(line: impl Into<String>) -> Self {
+		Self {
+			line: line.into(),
+			..Default::default()
+		}
+	}
+	pub fn as_str(&self) -> &str {
+		&self.line
+	}
+	fn has_metadata(&self) -> bool {
+		self.time.is_some() || self.exit_status.is_some() || self.tag.is_some()
+	}
+}
+
+impl From<String> for HistoryEntry {
+	fn from(line: String) -> Self {
+		Self::new(line)
+	}
+}
+
+impl From<&str> for HistoryEntry {
+	fn from(line: &str) -> Self {
+		Self::new(line)
+	}
+}
+
+impl fmt::Display for HistoryEntry {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.line)
+	}
+}
+
+impl PartialEq<str> for HistoryEntry {
+	fn eq(&self, other: &str) -> bool {
+		self.line == other
+	}
+}
+
+impl PartialEq<String> for HistoryEntry {
+	fn eq(&self, other: &String) -> bool {
+		self.line == *other
+	}
+}
+
+impl PartialEq<&str> for HistoryEntry {
+	fn eq(&self, other: &&str) -> bool {
+		self.line == *other
+	}
+}
+
+/// Which duplicate entries the history keeps, see
+/// [`Readline::set_history_duplicates()`][crate::Readline::set_history_duplicates]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryDuplicates {
+	/// Every entry, even the same line entered twice in a row
+	KeepAll,
+	/// Every entry except one that repeats the newest entry
+	#[default]
+	IgnoreConsecutiveDups,
+	/// Only the newest of equal entries, so that Up finds each line once
+	EraseOldDups,
+}
+
+/// Read-only access to the history, see
+/// [`Readline::history()`][crate::Readline::history]
+///
+/// Entries are numbered from 0 for the oldest one.
+#[derive(Clone, Copy)]
+pub struct HistoryView<'a> {
+	entries: &'a VecDeque<HistoryEntry>,
+}
+
+impl<'a> HistoryView<'a> {
+	/// Number of entries
+	pub fn len(self) -> usize {
+		self.entries.len()
+	}
+	pub fn is_empty(self) -> bool {
+		self.entries.is_empty()
+	}
+	/// The entry at `index`, if there is one
+	pub fn get(self, index: usize) -> Option<&'a HistoryEntry> {
+		self.entries.get(index)
+	}
+	/// The newest entry
+	pub fn last(self) -> Option<&'a HistoryEntry> {
+		self.entries.back()
+	}
+	/// The entries, oldest first
+	pub fn iter(self) -> vec_deque::Iter<'a, HistoryEntry> {
+		self.entries.iter()
+	}
+	/// The entries containing `text` along with their index, oldest first
+	pub fn search(
+		self,
+		text: &'a str,
+	) -> impl DoubleEndedIterator<Item = (usize, &'a HistoryEntry)> + 'a {
+		self.entries
+			.iter()
+			.enumerate()
+			.filter(move |(_, entry)| entry.line.contains(text))
+	}
+}
+
+impl<'a> IntoIterator for HistoryView<'a> {
+	type Item = &'a HistoryEntry;
+	type IntoIter = vec_deque::Iter<'a, HistoryEntry>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.iter()
+	}
+}
+
+impl fmt::Debug for HistoryView<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_list().entries(self.entries).finish()
+	}
+}
+
+// Decides whether a line is left out of the history
+pub type IgnoreRule = Box<dyn Fn(&str) -> bool + Send>;
+// Returns what is kept of a line instead of it, if anything is removed
+pub type RedactRule = Box<dyn Fn(&str) -> Option<String> + Send>;
 
 pub struct History {
 	// Note: old entries in front, new ones at the back.
-	entries: VecDeque<String>,
+	entries: VecDeque<HistoryEntry>,
 	max_size: usize,
+	pub duplicates: HistoryDuplicates,
+	// Whether lines starting with a space are left out, like bash's
+	// `ignorespace`
+	pub ignore_space: bool,
+	// Lines matching any of these are left out
+	pub ignore_rules: Vec<IgnoreRule>,
+	// Applied to lines before anything else
+	pub redact: Option<RedactRule>,
+	// Lines longer than this many bytes are left out
+	pub max_entry_len: Option<usize>,
 	current_position: Option<usize>,
+	// Number of the newest entries added since the history was last saved
+	unsaved: usize,
 }
 impl Default for History {
 	fn default() -> Self {
 		Self {
 			entries: Default::default(),
 			max_size: 1000,
+			duplicates: HistoryDuplicates::default(),
+			ignore_space: false,
+			ignore_rules: Vec::new(),
+			redact: None,
+			max_entry_len: None,
 			current_position: Default::default(),
+			unsaved: 0,
 		}
 	}
 }
 
 impl History {
-	// Update history entries
-	pub fn add_entry(&mut self, line: String) {
+	// Update history entries, returns whether the line was added.
+	pub fn add_entry(&mut self, mut entry: HistoryEntry) -> bool {
+		if let Some(redacted) = self.redact.as_ref().and_then(|redact| redact(&entry.line)) {
+			entry.line = redacted;
+		}
+		let line = &entry.line;
 		// Reset offset to newest entry
 		self.current_position = None;
-		// Don't add entry if last entry was same, or line was empty.
-		if self.entries.back() == Some(&line) || line.is_empty() {
-			return;
+		// Don't add entry if line was empty, too long or ignored, or unless
+		// kept, if last entry was same.
+		if line.is_empty()
+			|| self.max_entry_len.is_some_and(|max| line.len() > max)
+			|| self.ignore_space && line.starts_with(' ')
+			|| self.ignore_rules.iter().any(|ignore| ignore(line))
+			|| self.duplicates != HistoryDuplicates::KeepAll
+				&& self.entries.back().map(|last| &last.line) == Some(line)
+		{
+			return false;
+		}
+		if self.duplicates == HistoryDuplicates::EraseOldDups {
+			// Remove earlier occurrences, counting the unsaved ones among them
+			let first_unsaved = self.entries.len() - self.unsaved();
+			let mut unsaved = self.unsaved();
+			let mut index = 0;
+			self.entries.retain(|entry| {
+				let keep = entry.line != *line;
+				if !keep && index >= first_unsaved {
+					unsaved -= 1;
+				}
+				index += 1;
+				keep
+			});
+			self.unsaved = unsaved;
 		}
 		// Add entry to back of history
-		self.entries.push_back(line);
+		self.entries.push_back(entry);
+		self.unsaved += 1;
 		// Check if already have enough entries
 		if self.entries.len() > self.max_size {
 			// Remove oldest entry
 			self.entries.pop_front();
 		}
+		true
+	}
+
+	// Returns the most entries kept.
+	pub fn max_size(&self) -> usize {
+		self.max_size
+	}
+
+	// Returns how many entries were added since the history was last saved.
+	pub fn unsaved(&self) -> usize {
+		self.unsaved.min(self.entries.len())
 	}
 
 	// Changes the history size.
@@ -48,37 +252,135 @@ impl History {
 	}
 
 	// Returns the current history entries.
-	pub fn get_entries(&self) -> &VecDeque<String> {
+	pub fn get_entries(&self) -> &VecDeque<HistoryEntry> {
 		&self.entries
 	}
 
+	// Returns read-only access to the entries.
+	pub fn view(&self) -> HistoryView<'_> {
+		HistoryView {
+			entries: &self.entries,
+		}
+	}
+
+	// Returns the newest entry, for recording more about it.
+	pub fn last_entry_mut(&mut self) -> Option<&mut HistoryEntry> {
+		self.entries.back_mut()
+	}
+
 	// Replaces the current history entries.
-	pub fn set_entries(&mut self, entries: impl IntoIterator<Item = String>) {
+	pub fn set_entries(&mut self, entries: impl IntoIterator<Item = impl Into<HistoryEntry>>) {
 		self.entries.clear();
 
 		// Using `add_entry` will respect `max_size` and remove duplicate lines etc.
 		for entry in entries.into_iter() {
-			self.add_entry(entry);
+			self.add_entry(entry.into());
 		}
+		self.unsaved = 0;
 
 		self.reset_position();
 	}
 
+	// Replaces the entries with the ones in the file at `path`, in the format
+	// written by `save()`.
+	pub fn load(&mut self, path: &Path) -> io::Result<()> {
+		let text = fs::read_to_string(path)?;
+		let mut entries = Vec::new();
+		// Metadata for the entry on the next line
+		let mut metadata = None;
+		for line in text.lines() {
+			if let Some(fields) = line.strip_prefix('#') {
+				metadata = Some(parse_metadata(fields));
+				continue;
+			}
+			let mut entry: HistoryEntry = metadata.take().unwrap_or_default();
+			entry.line = unescape(line);
+			entries.push(entry);
+		}
+		self.set_entries(entries);
+		Ok(())
+	}
+
+	// Writes the entries to the file at `path` as UTF-8 text, oldest first and
+	// one per line.  Backslashes, newlines and carriage returns in entries are
+	// written as `\\`, `\n` and `\r`, and a `#` at the start as `\#`.
+	//
+	// Entries with metadata are preceded by a line starting with `#` and
+	// followed by space separated fields: `time=` and the Unix time in
+	// seconds, `status=` and the exit status, and last `tag=` and the tag,
+	// escaped like entries, up to the end of the line.  Unknown fields are
+	// ignored.
+	pub fn save(&mut self, path: &Path) -> io::Result<()> {
+		fs::write(path, format_entries(self.entries.iter()))?;
+		self.unsaved = 0;
+		Ok(())
+	}
+
+	// Adds the entries added since the history was last saved to the end of
+	// the file at `path`, creating it if needed.
+	pub fn append_unsaved(&mut self, path: &Path) -> io::Result<()> {
+		let unsaved = self
+			.entries
+			.iter()
+			.skip(self.entries.len() - self.unsaved());
+		let text = format_entries(unsaved);
+		OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(path)?
+			.write_all(text.as_bytes())?;
+		self.unsaved = 0;
+		Ok(())
+	}
+
 	// Sets the history position back to the start.
 	pub fn reset_position(&mut self) {
 		self.current_position = None;
 	}
 
+	// Returns the index of the entry Up and Down last went to, if any.
+	pub fn position(&self) -> Option<usize> {
+		self.current_position
+	}
+
+	// Makes Up and Down continue from the entry at `position`.
+	pub fn set_position(&mut self, position: Option<usize>) {
+		self.current_position = position.filter(|&index| index < self.entries.len());
+	}
+
+	// Returns the index of the nearest entry containing `text`, from the one
+	// at `from` towards older entries, or newer ones if `forward` is set.
+	pub fn find(&self, text: &str, from: usize, forward: bool) -> Option<usize> {
+		let matches = |index: &usize| self.entries[*index].line.contains(text);
+		match forward {
+			true => (from..self.entries.len()).find(matches),
+			false => (0..self.entries.len().min(from + 1)).rev().find(matches),
+		}
+	}
+
+	// Returns the newest entry that continues `prefix`, for suggesting the
+	// rest of it while typing.
+	pub fn suggest(&self, prefix: &str) -> Option<&str> {
+		if prefix.is_empty() {
+			return None;
+		}
+		self.entries
+			.iter()
+			.rev()
+			.find(|entry| entry.line.len() > prefix.len() && entry.line.starts_with(prefix))
+			.map(HistoryEntry::as_str)
+	}
+
 	// Find next history that matches a given string from an index
 	pub fn search_next(&mut self, _current: &str) -> Option<&str> {
 		if let Some(index) = &mut self.current_position {
 			if *index > 0 {
 				*index -= 1;
 			}
-			Some(&self.entries[*index])
+			Some(&self.entries[*index].line)
 		} else if let Some(last) = self.entries.back() {
 			self.current_position = Some(self.entries.len() - 1);
-			Some(last)
+			Some(&last.line)
 		} else {
 			None
 		}
@@ -92,13 +394,100 @@ impl History {
 				return Some("");
 			}
 			*index += 1;
-			Some(&self.entries[*index])
+			Some(&self.entries[*index].line)
 		} else {
 			None
 		}
 	}
 }
 
+fn format_entries<'a>(entries: impl Iterator<Item = &'a HistoryEntry>) -> String {
+	let mut text = String::new();
+	for entry in entries {
+		if entry.has_metadata() {
+			let mut fields = Vec::new();
+			if let Some(time) = entry.time {
+				let seconds = time
+					.duration_since(SystemTime::UNIX_EPOCH)
+					.unwrap_or_default();
+				fields.push(format!("time={}", seconds.as_secs()));
+			}
+			if let Some(status) = entry.exit_status {
+				fields.push(format!("status={status}"));
+			}
+			if let Some(tag) = &entry.tag {
+				fields.push(format!("tag={}", escape(tag)));
+			}
+			text += &format!("#{}\n", fields.join(" "));
+		}
+		text += &escape(&entry.line);
+		text.push('\n');
+	}
+	text
+}
+
+// Reads the fields of a metadata line written by `format_entries()`
+fn parse_metadata(mut fields: &str) -> HistoryEntry {
+	let mut entry = HistoryEntry::default();
+	while !fields.is_empty() {
+		if let Some(tag) = fields.strip_prefix("tag=") {
+			entry.tag = Some(unescape(tag));
+			break;
+		}
+		let (field, rest) = fields.split_once(' ').unwrap_or((fields, ""));
+		match field.split_once('=') {
+			Some(("time", seconds)) => {
+				entry.time = seconds
+					.parse()
+					.ok()
+					.map(|seconds| SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+			}
+			Some(("status", status)) => entry.exit_status = status.parse().ok(),
+			_ => {}
+		}
+		fields = rest;
+	}
+	entry
+}
+
+fn escape(entry: &str) -> String {
+	let mut escaped = String::new();
+	for c in entry.chars() {
+		match c {
+			'#' if escaped.is_empty() => escaped += "\\#",
+			'\\' => escaped += "\\\\",
+			'\n' => escaped += "\\n",
+			'\r' => escaped += "\\r",
+			_ => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+// Reverses `escape()`, other backslashes are kept as they are
+fn unescape(line: &str) -> String {
+	let mut entry = String::new();
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			entry.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('\\') => entry.push('\\'),
+			Some('n') => entry.push('\n'),
+			Some('r') => entry.push('\r'),
+			Some('#') => entry.push('#'),
+			Some(other) => {
+				entry.push('\\');
+				entry.push(other);
+			}
+			None => entry.push('\\'),
+		}
+	}
+	entry
+}
+
 #[cfg(test)]
 #[test]
 fn test_history() {
@@ -141,6 +530,17 @@ fn test_history() {
 	}
 }
 
+#[cfg(test)]
+#[test]
+fn test_history_suggest() {
+	let mut history = History::default();
+	history.set_entries(["git status", "git commit", "git", "ls"].map(String::from));
+	assert_eq!(history.suggest("git"), Some("git commit"));
+	assert_eq!(history.suggest("git s"), Some("git status"));
+	assert_eq!(history.suggest("ls"), None);
+	assert_eq!(history.suggest(""), None);
+}
+
 #[cfg(test)]
 #[test]
 fn test_history_limit() {
@@ -215,3 +615,153 @@ fn test_history_export() {
 	assert_eq!(Some("a"), history.search_next(""));
 	assert_eq!(Some("a"), history.search_next(""));
 }
+
+#[cfg(test)]
+#[test]
+fn test_history_file() {
+	let path = std::env::temp_dir().join(format!("rustyline-async-{}.history", std::process::id()));
+	let entries = ["ls", "echo 'a\nb'", r"printf '\n'", "cr\r"];
+	let mut history = History::default();
+	history.set_entries(entries.map(String::from));
+	history.save(&path).unwrap();
+	assert_eq!(
+		fs::read_to_string(&path).unwrap(),
+		"ls\necho 'a\\nb'\nprintf '\\\\n'\ncr\\r\n"
+	);
+
+	let mut loaded = History::default();
+	loaded.load(&path).unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(loaded.get_entries(), &entries);
+	assert_eq!(unescape(r"a\tb\"), r"a\tb\");
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_append() {
+	let path = std::env::temp_dir().join(format!("rustyline-async-{}.append", std::process::id()));
+	let mut history = History::default();
+	history.set_entries(["old".to_string()]);
+	history.save(&path).unwrap();
+	history.add_entry("new".into());
+	history.add_entry("new".into());
+	history.add_entry("newer".into());
+	assert_eq!(history.unsaved(), 2);
+	history.append_unsaved(&path).unwrap();
+	assert_eq!(history.unsaved(), 0);
+	assert_eq!(fs::read_to_string(&path).unwrap(), "old\nnew\nnewer\n");
+	fs::remove_file(&path).unwrap();
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_duplicates() {
+	let entries = ["ls", "cd", "ls", "ls"].map(String::from);
+	let mut history = History::default();
+	history.set_entries(entries.clone());
+	assert_eq!(history.get_entries(), &["ls", "cd", "ls"]);
+
+	history.duplicates = HistoryDuplicates::KeepAll;
+	history.set_entries(entries.clone());
+	assert_eq!(history.get_entries(), &entries);
+
+	history.duplicates = HistoryDuplicates::EraseOldDups;
+	history.set_entries(entries);
+	assert_eq!(history.get_entries(), &["cd", "ls"]);
+	history.add_entry("cd".into());
+	assert_eq!(history.get_entries(), &["ls", "cd"]);
+	assert_eq!(history.unsaved(), 1);
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_find() {
+	let mut history = History::default();
+	history.set_entries(["cargo build", "git status", "cargo test"]);
+	assert_eq!(history.find("cargo", 2, false), Some(2));
+	assert_eq!(history.find("cargo", 1, false), Some(0));
+	assert_eq!(history.find("cargo", 1, true), Some(2));
+	assert_eq!(history.find("cargo", 5, false), Some(2));
+	assert_eq!(history.find("make", 2, false), None);
+	assert_eq!(history.find("cargo", 3, true), None);
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_view() {
+	let mut history = History::default();
+	history.set_entries(["cargo build", "git status", "cargo test"]);
+	let view = history.view();
+	assert_eq!(view.len(), 3);
+	assert_eq!(view.get(1).unwrap(), "git status");
+	assert_eq!(view.last().unwrap(), "cargo test");
+	assert!(view.get(3).is_none());
+	let found: Vec<_> = view
+		.search("cargo")
+		.rev()
+		.map(|(index, entry)| (index, entry.as_str()))
+		.collect();
+	assert_eq!(found, [(2, "cargo test"), (0, "cargo build")]);
+	assert_eq!(view.into_iter().count(), 3);
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_ignore() {
+	let mut history = History {
+		ignore_space: true,
+		ignore_rules: vec![Box::new(|line| line.contains("password="))],
+		..Default::default()
+	};
+	assert!(history.add_entry("ls".into()));
+	assert!(!history.add_entry(" rm -rf secret".into()));
+	assert!(!history.add_entry("login password=hunter2".into()));
+	assert_eq!(history.get_entries(), &["ls"]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_redaction() {
+	let mut history = History {
+		redact: Some(Box::new(|line| {
+			let (command, _) = line.split_once("--token=")?;
+			Some(format!("{command}--token=***"))
+		})),
+		max_entry_len: Some(20),
+		..Default::default()
+	};
+	assert!(history.add_entry("login --token=abc123".into()));
+	assert!(!history.add_entry("x".repeat(21).into()));
+	assert!(history.add_entry("x".repeat(20).into()));
+	assert_eq!(history.get_entries()[0], "login --token=***");
+	assert_eq!(history.get_entries().len(), 2);
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_metadata() {
+	let path =
+		std::env::temp_dir().join(format!("rustyline-async-{}.metadata", std::process::id()));
+	let mut history = History::default();
+	history.add_entry(HistoryEntry {
+		time: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1700000000)),
+		exit_status: Some(1),
+		tag: Some("deploy prod".into()),
+		..HistoryEntry::new("make deploy")
+	});
+	history.add_entry("#not metadata".into());
+	history.save(&path).unwrap();
+	assert_eq!(
+		fs::read_to_string(&path).unwrap(),
+		"#time=1700000000 status=1 tag=deploy prod\nmake deploy\n\\#not metadata\n"
+	);
+
+	let mut loaded = History::default();
+	loaded.load(&path).unwrap();
+	fs::remove_file(&path).unwrap();
+	assert_eq!(loaded.get_entries(), history.get_entries());
+	assert_eq!(
+		parse_metadata("status=x future=1 time=5").time,
+		Some(SystemTime::UNIX_EPOCH + Duration::from_secs(5))
+	);
+}
@@ -0,0 +1,189 @@
+use std::time::{Duration, SystemTime};
+
+use crate::HistoryEntry;
+
+/// History file formats of other shells, see
+/// [`Readline::import_history()`][crate::Readline::import_history] and
+/// [`Readline::export_history()`][crate::Readline::export_history]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+	/// `~/.bash_history`: one entry per line, preceded by a `#` line with its
+	/// Unix time if `HISTTIMEFORMAT` is set
+	///
+	/// Entries spanning several lines are written as they are, like bash does
+	/// with `lithist`.  Reading them back, the lines up to the next time are
+	/// one entry, and files without times have one entry per line.
+	Bash,
+	/// `~/.zsh_history`, with the times of `EXTENDED_HISTORY` when they are
+	/// known
+	///
+	/// Lines of an entry are joined by a backslash before the newline, and
+	/// bytes that zsh escapes in its history are escaped the same way.
+	Zsh,
+}
+
+// Escapes bytes that zsh uses internally in its history file
+const ZSH_META: u8 = 0x83;
+
+impl HistoryFormat {
+	/// Read the entries of a history file, oldest first
+	pub fn parse(self, data: &[u8]) -> Vec<HistoryEntry> {
+		let mut entries: Vec<HistoryEntry> = Vec::new();
+		match self {
+			Self::Bash => {
+				let text = String::from_utf8_lossy(data);
+				let mut time = None;
+				// Lines after a time continue the entry until the next one
+				let mut continued = false;
+				for line in text.lines() {
+					if let Some(seconds) = line.strip_prefix('#').and_then(parse_seconds) {
+						time = Some(seconds);
+						continued = false;
+						continue;
+					}
+					match entries.last_mut() {
+						Some(last) if continued => {
+							last.line.push('\n');
+							last.line += line;
+						}
+						_ => {
+							continued = time.is_some();
+							entries.push(HistoryEntry {
+								time: time.take(),
+								..HistoryEntry::new(line)
+							});
+						}
+					}
+				}
+			}
+			Self::Zsh => {
+				let text = String::from_utf8_lossy(&unmetafy(data)).into_owned();
+				let mut lines = text.lines();
+				while let Some(mut line) = lines.next().map(str::to_owned) {
+					// Continued on the next line
+					while line.ends_with('\\') {
+						let Some(next) = lines.next() else {
+							break;
+						};
+						line.pop();
+						line.push('\n');
+						line += next;
+					}
+					let mut entry = HistoryEntry::new(line.as_str());
+					// `: <start>:<elapsed>;<command>`
+					if let Some((start, command)) = line
+						.strip_prefix(": ")
+						.and_then(|rest| rest.split_once(';'))
+					{
+						if let Some((start, _)) = start.split_once(':') {
+							if let Some(time) = parse_seconds(start) {
+								entry = HistoryEntry {
+									time: Some(time),
+									..HistoryEntry::new(command)
+								};
+							}
+						}
+					}
+					entries.push(entry);
+				}
+			}
+		}
+		entries
+	}
+
+	/// Write `entries` as a history file, oldest first
+	pub fn format<'a>(self, entries: impl IntoIterator<Item = &'a HistoryEntry>) -> Vec<u8> {
+		let mut text = String::new();
+		for entry in entries {
+			let seconds = entry.time.map(|time| {
+				time.duration_since(SystemTime::UNIX_EPOCH)
+					.unwrap_or_default()
+			});
+			match self {
+				Self::Bash => {
+					if let Some(seconds) = seconds {
+						text += &format!("#{}\n", seconds.as_secs());
+					}
+					text += &entry.line;
+				}
+				Self::Zsh => {
+					if let Some(seconds) = seconds {
+						text += &format!(": {}:0;", seconds.as_secs());
+					}
+					text += &entry.line.replace('\n', "\\\n");
+				}
+			}
+			text.push('\n');
+		}
+		match self {
+			Self::Bash => text.into_bytes(),
+			Self::Zsh => metafy(text.as_bytes()),
+		}
+	}
+}
+
+fn parse_seconds(seconds: &str) -> Option<SystemTime> {
+	if seconds.is_empty() || !seconds.bytes().all(|b| b.is_ascii_digit()) {
+		return None;
+	}
+	let seconds = seconds.parse().ok()?;
+	Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn metafy(data: &[u8]) -> Vec<u8> {
+	let mut escaped = Vec::new();
+	for &byte in data {
+		if byte == 0 || (ZSH_META..=0xa2).contains(&byte) {
+			escaped.extend([ZSH_META, byte ^ 0x20]);
+		} else {
+			escaped.push(byte);
+		}
+	}
+	escaped
+}
+
+fn unmetafy(data: &[u8]) -> Vec<u8> {
+	let mut bytes = data.iter();
+	let mut unescaped = Vec::new();
+	while let Some(&byte) = bytes.next() {
+		match byte {
+			ZSH_META => unescaped.extend(bytes.next().map(|byte| byte ^ 0x20)),
+			_ => unescaped.push(byte),
+		}
+	}
+	unescaped
+}
+
+#[cfg(test)]
+#[test]
+fn test_bash_history() {
+	let entries = HistoryFormat::Bash.parse(b"ls\n#1700000000\ngit status\n#not a time\n");
+	let lines: Vec<_> = entries.iter().map(HistoryEntry::as_str).collect();
+	// Lines up to the next time belong to the same entry
+	assert_eq!(lines, ["ls", "git status\n#not a time"]);
+	assert_eq!(
+		entries[1].time,
+		Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1700000000))
+	);
+	assert_eq!(
+		HistoryFormat::Bash.format(&entries),
+		b"ls\n#1700000000\ngit status\n#not a time\n"
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_zsh_history() {
+	let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1700000000);
+	let entries = [
+		HistoryEntry::new("ls"),
+		HistoryEntry {
+			time: Some(time),
+			..HistoryEntry::new("echo ń\nfor")
+		},
+	];
+	// `ń` is 0xc5 0x84, and zsh escapes 0x84
+	let data = HistoryFormat::Zsh.format(&entries);
+	assert_eq!(data, b"ls\n: 1700000000:0;echo \xc5\x83\xa4\\\nfor\n");
+	assert_eq!(HistoryFormat::Zsh.parse(&data), entries);
+}
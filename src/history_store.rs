@@ -0,0 +1,42 @@
+use std::io;
+
+use futures_util::future::{BoxFuture, FutureExt};
+
+/// A backend that keeps the history, e.g. a database shared between
+/// machines, see [`Readline::set_history_store()`]
+///
+/// The newest entries are loaded into memory for Up, Down and suggestions,
+/// and entries are sent to the store as they are added.  The history is only
+/// kept in memory if no store is set.
+///
+/// [`Readline::set_history_store()`]: crate::Readline::set_history_store
+pub trait HistoryStore: Send {
+	/// The newest `limit` entries, oldest first
+	fn load(&mut self, limit: usize) -> BoxFuture<'static, io::Result<Vec<String>>>;
+
+	/// Add `entry` as the newest entry
+	///
+	/// The future is polled while [`Readline::readline()`] runs, and is
+	/// finished before the next entry is added.
+	///
+	/// [`Readline::readline()`]: crate::Readline::readline
+	fn add(&mut self, entry: String) -> BoxFuture<'static, io::Result<()>>;
+
+	/// Up to `limit` entries that contain `text`, newest first
+	///
+	/// Searches all entries from [`load()`][HistoryStore::load] unless
+	/// implemented, e.g. to search in a database.
+	fn search(&mut self, text: &str, limit: usize) -> BoxFuture<'static, io::Result<Vec<String>>> {
+		let text = text.to_owned();
+		self.load(usize::MAX)
+			.map(move |entries| {
+				Ok(entries?
+					.into_iter()
+					.rev()
+					.filter(|entry| entry.contains(&text))
+					.take(limit)
+					.collect())
+			})
+			.boxed()
+	}
+}
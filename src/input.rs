@@ -0,0 +1,217 @@
+use std::{fmt, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Text of the input line, along with where each grapheme starts and how
+/// wide the text is up to it
+///
+/// Edits only segment the text around the change again, so that moving the
+/// cursor and typing don't get slower as the line gets longer.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct InputLine {
+	text: String,
+	// Byte offset of the start of each grapheme
+	starts: Vec<usize>,
+	// Display width of the text up to the end of each grapheme
+	widths: Vec<usize>,
+	// Changes with every edit, so layouts of the text can tell they're stale
+	generation: u64,
+}
+
+impl InputLine {
+	pub fn as_str(&self) -> &str {
+		&self.text
+	}
+	pub fn is_empty(&self) -> bool {
+		self.text.is_empty()
+	}
+	/// Number of graphemes
+	pub fn count(&self) -> usize {
+		self.starts.len()
+	}
+	/// Display width of the first `index` graphemes
+	pub fn width_to(&self, index: usize) -> usize {
+		match index {
+			0 => 0,
+			_ => self.widths[index.min(self.widths.len()) - 1],
+		}
+	}
+	/// Byte offset of the grapheme at `index`, or the length of the line
+	pub fn offset(&self, index: usize) -> usize {
+		self.starts.get(index).copied().unwrap_or(self.text.len())
+	}
+	/// Index of the grapheme containing the byte at `offset`
+	pub fn index(&self, offset: usize) -> usize {
+		self.starts
+			.partition_point(|&start| start <= offset)
+			.saturating_sub(1)
+	}
+	/// Index of the first grapheme from `from` up to `to` that doesn't fit in
+	/// `width` columns with the ones before it
+	pub fn fit(&self, from: usize, to: usize, width: usize) -> usize {
+		let limit = self.width_to(from) + width;
+		from + self.widths[from..to].partition_point(|&w| w <= limit)
+	}
+	/// Index of the first newline from `from` on, or the number of graphemes
+	pub fn next_newline(&self, from: usize) -> usize {
+		let mut offset = self.offset(from);
+		while let Some(found) = self.text[offset..].find('\n') {
+			let index = self.index(offset + found);
+			// A newline after a carriage return is part of its grapheme
+			if self.grapheme(index).map(|(_, grapheme)| grapheme) == Some("\n") {
+				return index;
+			}
+			offset += found + 1;
+		}
+		self.count()
+	}
+	/// Number that changes whenever the text does
+	pub fn generation(&self) -> u64 {
+		self.generation
+	}
+	/// Byte offset and text of the grapheme at `index`
+	pub fn grapheme(&self, index: usize) -> Option<(usize, &str)> {
+		let start = *self.starts.get(index)?;
+		Some((start, &self.text[start..self.offset(index + 1)]))
+	}
+	pub fn set(&mut self, text: String) {
+		self.text = text;
+		self.generation += 1;
+		self.starts.clear();
+		self.widths.clear();
+		let mut width = 0;
+		for (start, grapheme) in self.text.grapheme_indices(true) {
+			width += grapheme.width();
+			self.starts.push(start);
+			self.widths.push(width);
+		}
+	}
+	pub fn take(&mut self) -> String {
+		self.generation += 1;
+		self.starts.clear();
+		self.widths.clear();
+		std::mem::take(&mut self.text)
+	}
+	pub fn clear(&mut self) {
+		self.take();
+	}
+	/// Replace the bytes in `range` with `with`
+	pub fn replace(&mut self, range: Range<usize>, with: &str) {
+		let old_len = self.text.len();
+		self.text.replace_range(range.clone(), with);
+		self.generation += 1;
+
+		// The graphemes on either side may join with or split from the new
+		// text, so segment from the one before the edit to the one after it
+		let first = self
+			.starts
+			.partition_point(|&start| start < range.start)
+			.saturating_sub(1);
+		let last = (self.starts.partition_point(|&start| start < range.end) + 1).min(self.count());
+		let start = self.starts.get(first).copied().unwrap_or(old_len);
+		let old_end = self.starts.get(last).copied().unwrap_or(old_len);
+		let new_end = old_end + with.len() - range.len();
+		let window = &self.text[start..new_end];
+		// Flags pair up regional indicators from the start of a run, so any
+		// change to them can affect the rest of the line
+		if window
+			.chars()
+			.any(|c| ('\u{1F1E6}'..='\u{1F1FF}').contains(&c))
+		{
+			let text = std::mem::take(&mut self.text);
+			self.set(text);
+			return;
+		}
+
+		let width_before = self.width_to(first);
+		let old_width = self.width_to(last) - width_before;
+		let mut width = width_before;
+		let mut starts = Vec::new();
+		let mut widths = Vec::new();
+		for (offset, grapheme) in window.grapheme_indices(true) {
+			width += grapheme.width();
+			starts.push(start + offset);
+			widths.push(width);
+		}
+		let new_count = starts.len();
+		let new_width = width - width_before;
+		self.starts.splice(first..last, starts);
+		self.widths.splice(first..last, widths);
+		for start in &mut self.starts[first + new_count..] {
+			*start = *start + new_end - old_end;
+		}
+		for width in &mut self.widths[first + new_count..] {
+			*width = *width + new_width - old_width;
+		}
+	}
+}
+
+impl fmt::Display for InputLine {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.text)
+	}
+}
+
+#[cfg(test)]
+fn assert_consistent(line: &InputLine) {
+	let mut expected = InputLine::default();
+	expected.set(line.text.clone());
+	assert_eq!(line.starts, expected.starts);
+	assert_eq!(line.widths, expected.widths);
+}
+
+#[cfg(test)]
+#[test]
+fn test_input_line_edits() {
+	let mut line = InputLine::default();
+	line.set("hello wörld".into());
+	assert_eq!(line.count(), 11);
+
+	// A combining accent joins the grapheme before it
+	line.replace(1..1, "\u{301}");
+	assert_eq!(line.count(), 11);
+	assert_eq!(line.grapheme(0), Some((0, "h\u{301}")));
+	assert_consistent(&line);
+
+	line.replace(0..0, "日本");
+	assert_eq!(line.width_to(line.count()), 15);
+	assert_consistent(&line);
+
+	line.replace(6..9, "");
+	assert_eq!(line.as_str(), "日本ello wörld");
+	assert_consistent(&line);
+
+	// Flags pair up regional indicators
+	line.replace(0..0, "\u{1F1FA}\u{1F1F8}\u{1F1E9}");
+	line.replace(4..4, "\u{1F1EA}");
+	assert_eq!(line.grapheme(1), Some((8, "\u{1F1F8}\u{1F1E9}")));
+	assert_consistent(&line);
+}
+
+#[cfg(test)]
+#[test]
+fn test_input_line_long() {
+	let mut line = InputLine::default();
+	line.set("x".repeat(100_000));
+	for _ in 0..1000 {
+		line.replace(50_000..50_000, "é");
+	}
+	assert_eq!(line.count(), 101_000);
+	assert_eq!(line.offset(51_000), 52_000);
+	assert_eq!(line.width_to(101_000), 101_000);
+	assert_consistent(&line);
+}
+
+#[cfg(test)]
+#[test]
+fn test_input_line_rows() {
+	let mut line = InputLine::default();
+	line.set("ab世界\r\ncd\nef".into());
+	assert_eq!(line.fit(0, 4, 4), 3);
+	assert_eq!(line.fit(0, 4, 5), 3);
+	assert_eq!(line.fit(2, 4, 4), 4);
+	assert_eq!(line.fit(1, 4, 0), 1);
+	assert_eq!(line.next_newline(0), 7);
+	assert_eq!(line.next_newline(8), 10);
+}
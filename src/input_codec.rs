@@ -0,0 +1,239 @@
+use std::{collections::VecDeque, io};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use futures_util::{
+	stream::{self, BoxStream},
+	AsyncRead, AsyncReadExt, StreamExt,
+};
+
+/// Turns bytes typed into a terminal back into key events, for input that
+/// doesn't come from the terminal of the process (e.g. an SSH session)
+///
+/// Understands UTF-8 text, control characters and the escape sequences
+/// common terminals send for cursor and editing keys.  Sequences split
+/// between two chunks are completed with the next chunk, except that an
+/// escape at the very end of a chunk is taken to be the Escape key.
+#[derive(Debug, Default)]
+pub(crate) struct InputDecoder {
+	// Start of a sequence that was cut off at the end of the last chunk
+	pending: Vec<u8>,
+	// Whether the last byte was a carriage return, which may be followed by
+	// a newline or NUL that belongs to the same Enter key
+	after_cr: bool,
+}
+
+// Result of decoding the start of the input
+enum Decoded {
+	Event(Event, usize),
+	Skip(usize),
+	Incomplete,
+}
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+	Event::Key(KeyEvent::new(code, modifiers))
+}
+
+impl InputDecoder {
+	pub fn decode(&mut self, data: &[u8], events: &mut VecDeque<Event>) {
+		self.pending.extend_from_slice(data);
+		let mut start = 0;
+		while start < self.pending.len() {
+			let rest = &self.pending[start..];
+			let after_cr = std::mem::take(&mut self.after_cr);
+			let decoded = match rest[0] {
+				b'\n' | b'\0' if after_cr => Decoded::Skip(1),
+				b'\r' => {
+					self.after_cr = true;
+					Decoded::Event(key(KeyCode::Enter, KeyModifiers::NONE), 1)
+				}
+				b'\x1b' if rest.len() == 1 => {
+					Decoded::Event(key(KeyCode::Esc, KeyModifiers::NONE), 1)
+				}
+				b'\x1b' => decode_escape(rest),
+				_ => decode_char(rest),
+			};
+			match decoded {
+				Decoded::Event(event, len) => {
+					events.push_back(event);
+					start += len;
+				}
+				Decoded::Skip(len) => start += len,
+				Decoded::Incomplete => break,
+			}
+		}
+		self.pending.drain(..start);
+	}
+}
+
+fn decode_char(data: &[u8]) -> Decoded {
+	let (code, modifiers) = match data[0] {
+		b'\n' => (KeyCode::Enter, KeyModifiers::NONE),
+		b'\t' => (KeyCode::Tab, KeyModifiers::NONE),
+		b'\x08' | b'\x7f' => (KeyCode::Backspace, KeyModifiers::NONE),
+		byte @ 1..=26 => (
+			KeyCode::Char((b'a' + byte - 1) as char),
+			KeyModifiers::CONTROL,
+		),
+		0..=31 => return Decoded::Skip(1),
+		_ => {
+			let len = match data[0] {
+				0xc0..=0xdf => 2,
+				0xe0..=0xef => 3,
+				0xf0..=0xf7 => 4,
+				_ => 1,
+			};
+			if data.len() < len {
+				return Decoded::Incomplete;
+			}
+			return match std::str::from_utf8(&data[..len]) {
+				Ok(c) => Decoded::Event(
+					key(KeyCode::Char(c.chars().next().unwrap()), KeyModifiers::NONE),
+					len,
+				),
+				Err(_) => Decoded::Skip(1),
+			};
+		}
+	};
+	Decoded::Event(key(code, modifiers), 1)
+}
+
+// Decodes input starting with an escape, which is at least two bytes long
+fn decode_escape(data: &[u8]) -> Decoded {
+	match data[1] {
+		b'[' => decode_csi(data),
+		b'O' => match data.get(2) {
+			None => Decoded::Incomplete,
+			Some(&final_byte) => match cursor_key(final_byte) {
+				Some(code) => Decoded::Event(key(code, KeyModifiers::NONE), 3),
+				None => Decoded::Skip(3),
+			},
+		},
+		// Escape followed by a key is how terminals send Alt
+		_ => match decode_char(&data[1..]) {
+			Decoded::Event(Event::Key(event), len) => Decoded::Event(
+				key(event.code, event.modifiers | KeyModifiers::ALT),
+				len + 1,
+			),
+			Decoded::Skip(len) => Decoded::Skip(len + 1),
+			decoded => decoded,
+		},
+	}
+}
+
+fn cursor_key(final_byte: u8) -> Option<KeyCode> {
+	Some(match final_byte {
+		b'A' => KeyCode::Up,
+		b'B' => KeyCode::Down,
+		b'C' => KeyCode::Right,
+		b'D' => KeyCode::Left,
+		b'H' => KeyCode::Home,
+		b'F' => KeyCode::End,
+		_ => return None,
+	})
+}
+
+// Decodes `ESC [ parameters final`, e.g. `ESC [ 1 ; 5 C` for Ctrl-Right
+fn decode_csi(data: &[u8]) -> Decoded {
+	let Some(end) = data[2..]
+		.iter()
+		.position(|byte| (0x40..=0x7e).contains(byte))
+	else {
+		return Decoded::Incomplete;
+	};
+	let len = end + 3;
+	let params = std::str::from_utf8(&data[2..len - 1]).unwrap_or_default();
+	let mut params = params
+		.split(';')
+		.map(|param| param.parse::<u8>().unwrap_or(1));
+	let first = params.next().unwrap_or(1);
+	// The modifier parameter is one more than a bitmask of Shift, Alt and Ctrl
+	let mask = params.next().unwrap_or(1).saturating_sub(1);
+	let mut modifiers = KeyModifiers::NONE;
+	if mask & 1 != 0 {
+		modifiers |= KeyModifiers::SHIFT;
+	}
+	if mask & 2 != 0 {
+		modifiers |= KeyModifiers::ALT;
+	}
+	if mask & 4 != 0 {
+		modifiers |= KeyModifiers::CONTROL;
+	}
+	let code = match data[len - 1] {
+		b'~' => match first {
+			1 | 7 => KeyCode::Home,
+			2 => KeyCode::Insert,
+			3 => KeyCode::Delete,
+			4 | 8 => KeyCode::End,
+			5 => KeyCode::PageUp,
+			6 => KeyCode::PageDown,
+			_ => return Decoded::Skip(len),
+		},
+		final_byte => match cursor_key(final_byte) {
+			Some(code) => code,
+			None => return Decoded::Skip(len),
+		},
+	};
+	Decoded::Event(key(code, modifiers), len)
+}
+
+/// Key events decoded from `input`, ending when it does
+pub(crate) fn decode_events(
+	input: impl AsyncRead + Unpin + Send + 'static,
+) -> BoxStream<'static, io::Result<Event>> {
+	let state = (input, InputDecoder::default(), VecDeque::new());
+	stream::unfold(state, |(mut input, mut decoder, mut events)| async move {
+		loop {
+			if let Some(event) = events.pop_front() {
+				return Some((Ok(event), (input, decoder, events)));
+			}
+			let mut buf = [0; 1024];
+			match input.read(&mut buf).await {
+				Ok(0) => return None,
+				Ok(len) => decoder.decode(&buf[..len], &mut events),
+				Err(e) => return Some((Err(e), (input, decoder, events))),
+			}
+		}
+	})
+	.fuse()
+	.boxed()
+}
+
+#[cfg(test)]
+#[test]
+fn test_decode_keys() {
+	let mut decoder = InputDecoder::default();
+	let mut events = VecDeque::new();
+	decoder.decode(
+		"aé\x03\r\n\x7f\x1b[D\x1b[1;5C\x1b[3~\x1bOH\x1bb".as_bytes(),
+		&mut events,
+	);
+	let expected = [
+		key(KeyCode::Char('a'), KeyModifiers::NONE),
+		key(KeyCode::Char('é'), KeyModifiers::NONE),
+		key(KeyCode::Char('c'), KeyModifiers::CONTROL),
+		key(KeyCode::Enter, KeyModifiers::NONE),
+		key(KeyCode::Backspace, KeyModifiers::NONE),
+		key(KeyCode::Left, KeyModifiers::NONE),
+		key(KeyCode::Right, KeyModifiers::CONTROL),
+		key(KeyCode::Delete, KeyModifiers::NONE),
+		key(KeyCode::Home, KeyModifiers::NONE),
+		key(KeyCode::Char('b'), KeyModifiers::ALT),
+	];
+	assert_eq!(events, expected);
+}
+
+#[cfg(test)]
+#[test]
+fn test_decode_split() {
+	let mut decoder = InputDecoder::default();
+	let mut events = VecDeque::new();
+	let data = "日\x1b[1;5D".as_bytes();
+	for chunk in [&data[..1], &data[1..5], &data[5..]] {
+		decoder.decode(chunk, &mut events);
+	}
+	let expected = [
+		key(KeyCode::Char('日'), KeyModifiers::NONE),
+		key(KeyCode::Left, KeyModifiers::CONTROL),
+	];
+	assert_eq!(events, expected);
+}
@@ -0,0 +1,396 @@
+use std::{env, path::PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::{BellStyle, EditAction, EditMode};
+
+/// What is understood of a readline init file, see
+/// [`Readline::load_inputrc()`][crate::Readline::load_inputrc]
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Inputrc {
+	pub bindings: Vec<(Vec<KeyEvent>, EditAction)>,
+	pub edit_mode: Option<EditMode>,
+	pub bell_style: Option<BellStyle>,
+	pub completion_ignore_case: Option<bool>,
+}
+
+/// Where readline looks for the init file: `$INPUTRC`, or `~/.inputrc`
+pub(crate) fn path() -> Option<PathBuf> {
+	if let Some(path) = env::var_os("INPUTRC") {
+		return Some(path.into());
+	}
+	let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"))?;
+	Some(PathBuf::from(home).join(".inputrc"))
+}
+
+impl Inputrc {
+	/// Read the bindings and variables of an init file for a terminal of type
+	/// `term`, while `mode` is the editing mode in use
+	///
+	/// Bindings to unknown functions or of key sequences that can't be
+	/// typed as key events, macros, `$include` and other variables are
+	/// skipped.  `$if` tests the editing mode and the terminal type, other
+	/// tests such as application names are false.  `mode=` tests `mode` until
+	/// the file sets `editing-mode`.
+	pub fn parse(text: &str, mode: EditMode, term: Option<&str>) -> Self {
+		let mut inputrc = Self::default();
+		// For each `$if`, whether its lines are read, and whether the lines
+		// around it are
+		let mut conditions: Vec<(bool, bool)> = Vec::new();
+		// Bindings in the vi command keymap are left to the vi keys
+		let mut keymap_used = true;
+		for line in text.lines().map(str::trim) {
+			let active = conditions.last().is_none_or(|&(taken, _)| taken);
+			if let Some(directive) = line.strip_prefix('$') {
+				let (name, test) = directive
+					.split_once(char::is_whitespace)
+					.unwrap_or((directive, ""));
+				match name {
+					"if" => {
+						let mode = inputrc.edit_mode.unwrap_or(mode);
+						let taken = active && test_condition(test.trim(), mode, term);
+						conditions.push((taken, active));
+					}
+					"else" => {
+						if let Some((taken, outer)) = conditions.last_mut() {
+							*taken = *outer && !*taken;
+						}
+					}
+					"endif" => {
+						conditions.pop();
+					}
+					_ => {}
+				}
+				continue;
+			}
+			if !active || line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+			if let Some(setting) = line.strip_prefix("set ") {
+				let mut words = setting.split_whitespace();
+				let (Some(name), Some(value)) = (words.next(), words.next()) else {
+					continue;
+				};
+				let on = value.eq_ignore_ascii_case("on") || value == "1";
+				match name.to_ascii_lowercase().as_str() {
+					"editing-mode" => match value {
+						"emacs" => inputrc.edit_mode = Some(EditMode::Emacs),
+						"vi" => inputrc.edit_mode = Some(EditMode::Vi),
+						_ => {}
+					},
+					"bell-style" => match value {
+						"none" => inputrc.bell_style = Some(BellStyle::None),
+						"audible" => inputrc.bell_style = Some(BellStyle::Audible),
+						"visible" => inputrc.bell_style = Some(BellStyle::Visible),
+						_ => {}
+					},
+					"completion-ignore-case" => inputrc.completion_ignore_case = Some(on),
+					"keymap" => keymap_used = !matches!(value, "vi" | "vi-command" | "vi-move"),
+					_ => {}
+				}
+				continue;
+			}
+			if !keymap_used {
+				continue;
+			}
+			if let Some(binding) = parse_binding(line) {
+				inputrc.bindings.push(binding);
+			}
+		}
+		inputrc
+	}
+}
+
+fn test_condition(test: &str, mode: EditMode, term: Option<&str>) -> bool {
+	if let Some(wanted) = test.strip_prefix("mode=") {
+		return match mode {
+			EditMode::Emacs => wanted == "emacs",
+			EditMode::Vi => wanted == "vi",
+		};
+	}
+	if let Some(wanted) = test.strip_prefix("term=") {
+		// Either the whole type or the part before the first dash, so that
+		// `xterm` matches `xterm-256color`
+		return term.is_some_and(|term| term == wanted || term.split('-').next() == Some(wanted));
+	}
+	false
+}
+
+// A line like `"\C-x\C-u": undo` or `Meta-d: kill-word`
+fn parse_binding(line: &str) -> Option<(Vec<KeyEvent>, EditAction)> {
+	let (keys, rest) = match line.strip_prefix('"') {
+		Some(quoted) => {
+			let end = quoted
+				.char_indices()
+				.scan(false, |escaped, (i, c)| {
+					let end = !*escaped && c == '"';
+					*escaped = !*escaped && c == '\\';
+					Some((i, end))
+				})
+				.find(|&(_, end)| end)?
+				.0;
+			let keys = parse_sequence(&quoted[..end])?;
+			(keys, quoted[end + 1..].trim_start().strip_prefix(':')?)
+		}
+		None => {
+			let (name, rest) = line.split_once(':')?;
+			(vec![parse_key_name(name.trim())?], rest)
+		}
+	};
+	// Macros are quoted, and comments may follow the function
+	let function = rest.split_whitespace().next()?;
+	Some((keys, action(function)?))
+}
+
+// A key written like `Control-u`, `M-DEL` or `Return`
+fn parse_key_name(name: &str) -> Option<KeyEvent> {
+	let mut modifiers = KeyModifiers::NONE;
+	let mut rest = name;
+	loop {
+		let lower = rest.to_ascii_lowercase();
+		let prefix = ["control-", "c-", "meta-", "m-"]
+			.into_iter()
+			.find(|prefix| lower.starts_with(prefix));
+		let Some(prefix) = prefix else {
+			break;
+		};
+		modifiers |= match prefix.starts_with('c') {
+			true => KeyModifiers::CONTROL,
+			false => KeyModifiers::ALT,
+		};
+		rest = &rest[prefix.len()..];
+	}
+	let code = match rest.to_ascii_lowercase().as_str() {
+		"del" | "rubout" => KeyCode::Backspace,
+		"esc" | "escape" => KeyCode::Esc,
+		"lfd" | "newline" | "ret" | "return" => KeyCode::Enter,
+		"spc" | "space" => KeyCode::Char(' '),
+		"tab" => KeyCode::Tab,
+		_ => {
+			let mut chars = rest.chars();
+			let c = chars.next()?;
+			if chars.next().is_some() {
+				return None;
+			}
+			return Some(char_key(c, modifiers));
+		}
+	};
+	Some(key(code, modifiers))
+}
+
+// The keys of a quoted sequence like `\C-x\C-e`, `\M-d` or `\e[A`
+fn parse_sequence(text: &str) -> Option<Vec<KeyEvent>> {
+	// Characters with the control and meta prefixes applied
+	let mut chars: Vec<(char, KeyModifiers)> = Vec::new();
+	let mut rest = text;
+	let mut modifiers = KeyModifiers::NONE;
+	while let Some(c) = rest.chars().next() {
+		rest = &rest[c.len_utf8()..];
+		if c != '\\' {
+			chars.push((c, modifiers));
+			modifiers = KeyModifiers::NONE;
+			continue;
+		}
+		let escaped = rest.chars().next()?;
+		rest = &rest[escaped.len_utf8()..];
+		match escaped {
+			'C' | 'M' if rest.starts_with('-') => {
+				rest = &rest[1..];
+				modifiers |= match escaped {
+					'C' => KeyModifiers::CONTROL,
+					_ => KeyModifiers::ALT,
+				};
+				continue;
+			}
+			'e' => chars.push(('\x1b', modifiers)),
+			't' => chars.push(('\t', modifiers)),
+			'r' | 'n' => chars.push(('\r', modifiers)),
+			'd' => chars.push(('\x7f', modifiers)),
+			'\\' | '"' | '\'' => chars.push((escaped, modifiers)),
+			_ => return None,
+		}
+		modifiers = KeyModifiers::NONE;
+	}
+
+	let mut keys = Vec::new();
+	let mut i = 0;
+	while i < chars.len() {
+		let (c, modifiers) = chars[i];
+		i += 1;
+		if c == '\x1b' && modifiers.is_empty() && i < chars.len() {
+			// An escape sequence for a special key, or Esc for Meta
+			let sequence: String = chars[i..].iter().map(|&(c, _)| c).collect();
+			if let Some((len, key)) = special_key(&sequence) {
+				keys.push(key);
+				i += len;
+				continue;
+			}
+			let (c, modifiers) = chars[i];
+			i += 1;
+			keys.push(char_key(c, modifiers | KeyModifiers::ALT));
+			continue;
+		}
+		keys.push(char_key(c, modifiers));
+	}
+	(!keys.is_empty()).then_some(keys)
+}
+
+// The key for a character as the terminal sends it, e.g. `\C-m` is Enter
+fn char_key(c: char, modifiers: KeyModifiers) -> KeyEvent {
+	let alt = modifiers & KeyModifiers::ALT;
+	let control = modifiers.contains(KeyModifiers::CONTROL);
+	match (c.to_ascii_lowercase(), control) {
+		('m', true) | ('\r', _) => key(KeyCode::Enter, alt),
+		('i', true) | ('\t', _) => key(KeyCode::Tab, alt),
+		('[', true) | ('\x1b', _) => key(KeyCode::Esc, alt),
+		('?', true) | ('\x7f', _) => key(KeyCode::Backspace, alt),
+		(c, true) => key(KeyCode::Char(c), alt | KeyModifiers::CONTROL),
+		_ => key(KeyCode::Char(c), alt),
+	}
+}
+
+// The key of an escape sequence at the start of `sequence`, which follows
+// an Esc, and the number of characters it takes up
+fn special_key(sequence: &str) -> Option<(usize, KeyEvent)> {
+	let none = KeyModifiers::NONE;
+	let ctrl = KeyModifiers::CONTROL;
+	let keys = [
+		("[1;5C", key(KeyCode::Right, ctrl)),
+		("[1;5D", key(KeyCode::Left, ctrl)),
+		("[3~", key(KeyCode::Delete, none)),
+		("[A", key(KeyCode::Up, none)),
+		("[B", key(KeyCode::Down, none)),
+		("[C", key(KeyCode::Right, none)),
+		("[D", key(KeyCode::Left, none)),
+		("[H", key(KeyCode::Home, none)),
+		("[F", key(KeyCode::End, none)),
+		("OA", key(KeyCode::Up, none)),
+		("OB", key(KeyCode::Down, none)),
+		("OC", key(KeyCode::Right, none)),
+		("OD", key(KeyCode::Left, none)),
+		("OH", key(KeyCode::Home, none)),
+		("OF", key(KeyCode::End, none)),
+	];
+	keys.into_iter()
+		.find(|(sent, _)| sequence.starts_with(sent))
+		.map(|(sent, key)| (sent.len(), key))
+}
+
+fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+	KeyEvent::new(code, modifiers)
+}
+
+// The action for a readline function name
+fn action(function: &str) -> Option<EditAction> {
+	use EditAction::*;
+	Some(match function {
+		"accept-line" => AcceptLine,
+		"end-of-file" => EndOfFile,
+		"clear-screen" => ClearScreen,
+		"backward-char" => MoveLeft,
+		"forward-char" => MoveRight,
+		"shell-backward-word" => MoveWordLeft,
+		"shell-forward-word" => MoveWordRight,
+		"backward-word" => BackwardWord,
+		"forward-word" => ForwardWord,
+		"beginning-of-line" => MoveHome,
+		"end-of-line" => MoveEnd,
+		"backward-delete-char" => DeleteBackward,
+		"delete-char" => DeleteForward,
+		"unix-word-rubout" => DeleteWordBackward,
+		"unix-line-discard" | "backward-kill-line" => KillToStart,
+		"kill-line" => KillLine,
+		"kill-word" => KillWordForward,
+		"backward-kill-word" => KillWordBackward,
+		"transpose-chars" => TransposeChars,
+		"transpose-words" => TransposeWords,
+		"upcase-word" => UppercaseWord,
+		"downcase-word" => LowercaseWord,
+		"capitalize-word" => CapitalizeWord,
+		"yank" => Yank,
+		"yank-pop" => YankPop,
+		"previous-history" | "history-search-backward" => HistoryPrevious,
+		"next-history" | "history-search-forward" => HistoryNext,
+		"reverse-search-history" => ReverseSearch,
+		"forward-search-history" => ForwardSearch,
+		"undo" => Undo,
+		"complete" | "menu-complete" => Complete,
+		"overwrite-mode" => ToggleOverwrite,
+		_ => return None,
+	})
+}
+
+#[cfg(test)]
+#[test]
+fn test_parse_inputrc() {
+	let inputrc = Inputrc::parse(
+		r#"
+# Comment
+set editing-mode vi
+set bell-style visible
+set completion-ignore-case On
+$if mode=vi
+	"\C-x\C-e": kill-line
+	Meta-Rubout: backward-kill-word
+	"\e[1;5D": backward-word
+	"\M-\C-h": upcase-word
+$else
+	C-a: end-of-line
+$endif
+$if term=xterm
+	"\ep": previous-history # Comment
+$endif
+$if Bash
+	TAB: menu-complete
+$endif
+"\C-o": "macro"
+C-q: unknown-function
+set keymap vi-command
+"d": kill-word
+"#,
+		EditMode::Emacs,
+		Some("xterm-256color"),
+	);
+	let ctrl = |c| key(KeyCode::Char(c), KeyModifiers::CONTROL);
+	let alt = |code| key(code, KeyModifiers::ALT);
+	assert_eq!(
+		inputrc,
+		Inputrc {
+			bindings: vec![
+				(vec![ctrl('x'), ctrl('e')], EditAction::KillLine),
+				(vec![alt(KeyCode::Backspace)], EditAction::KillWordBackward),
+				(
+					vec![key(KeyCode::Left, KeyModifiers::CONTROL)],
+					EditAction::BackwardWord
+				),
+				(
+					vec![key(
+						KeyCode::Char('h'),
+						KeyModifiers::CONTROL | KeyModifiers::ALT
+					)],
+					EditAction::UppercaseWord
+				),
+				(vec![alt(KeyCode::Char('p'))], EditAction::HistoryPrevious),
+			],
+			edit_mode: Some(EditMode::Vi),
+			bell_style: Some(BellStyle::Visible),
+			completion_ignore_case: Some(true),
+		}
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_inputrc_current_mode() {
+	let text = "$if mode=vi\nC-a: end-of-line\n$endif\n";
+	let end_of_line = (
+		vec![key(KeyCode::Char('a'), KeyModifiers::CONTROL)],
+		EditAction::MoveEnd,
+	);
+	// Without `editing-mode`, the mode already in use is tested
+	let inputrc = Inputrc::parse(text, EditMode::Vi, None);
+	assert_eq!(inputrc.bindings, [end_of_line]);
+	assert_eq!(inputrc.edit_mode, None);
+	let inputrc = Inputrc::parse(text, EditMode::Emacs, None);
+	assert!(inputrc.bindings.is_empty());
+}
@@ -0,0 +1,284 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// An editing operation that keys are bound to, see
+/// [`Readline::bind()`][crate::Readline::bind]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditAction {
+	/// Return the input from [`readline()`][crate::Readline::readline]
+	AcceptLine,
+	/// Abandon the input and return
+	/// [`ReadlineEvent::Interrupted`][crate::ReadlineEvent::Interrupted]
+	Interrupt,
+	/// Return [`ReadlineEvent::Eof`][crate::ReadlineEvent::Eof]
+	EndOfFile,
+	/// Clear the screen, see
+	/// [`Readline::set_clear_behavior()`][crate::Readline::set_clear_behavior]
+	ClearScreen,
+	MoveLeft,
+	MoveRight,
+	/// Move to the start of the word before the cursor
+	MoveWordLeft,
+	/// Move to the end of the word after the cursor
+	MoveWordRight,
+	/// Move to the start of the word of letters and digits before the cursor,
+	/// like readline's `backward-word`
+	BackwardWord,
+	/// Move to the end of the word of letters and digits after the cursor,
+	/// like readline's `forward-word`
+	ForwardWord,
+	MoveHome,
+	MoveEnd,
+	/// Erase the character before the cursor
+	DeleteBackward,
+	/// Erase the character under the cursor
+	DeleteForward,
+	/// Erase the word before the cursor, up to the previous whitespace, like
+	/// a kill
+	DeleteWordBackward,
+	/// Erase the input before the cursor
+	KillToStart,
+	/// Erase the input from the cursor to the end
+	KillLine,
+	/// Erase the input up to the end of the word of letters and digits after
+	/// the cursor, like readline's `kill-word`
+	KillWordForward,
+	/// Erase the input back to the start of the word of letters and digits
+	/// before the cursor, like readline's `backward-kill-word`
+	KillWordBackward,
+	/// Swap the character before the cursor with the one under it, or the
+	/// two before it at the end of the input, and move past them
+	TransposeChars,
+	/// Swap the word of letters and digits before the cursor with the one
+	/// after it, or the last two at the end of the input, and move past them
+	TransposeWords,
+	/// Uppercase the input from the cursor to the end of the word of letters
+	/// and digits, and move past it
+	UppercaseWord,
+	/// Lowercase the input from the cursor to the end of the word of letters
+	/// and digits, and move past it
+	LowercaseWord,
+	/// Uppercase the first letter or digit from the cursor and lowercase the
+	/// rest of the word, and move past it
+	CapitalizeWord,
+	/// Insert the text erased last by [`KillLine`][Self::KillLine],
+	/// [`KillToStart`][Self::KillToStart] and the other kills
+	Yank,
+	/// Right after [`Yank`][Self::Yank], replace the text inserted with the
+	/// text erased before it
+	YankPop,
+	/// Go to the previous, older history entry
+	HistoryPrevious,
+	/// Go to the next, newer history entry
+	HistoryNext,
+	/// Search older history entries as you type
+	ReverseSearch,
+	/// Search newer history entries as you type
+	ForwardSearch,
+	/// Undo the last change to the input, characters typed one after another
+	/// are undone together
+	Undo,
+	/// Redo the change undone last
+	Redo,
+	/// Complete the text before the cursor, see
+	/// [`Readline::set_completer()`][crate::Readline::set_completer]
+	Complete,
+	/// Switch between inserting typed characters and overwriting the ones
+	/// under the cursor, which shows an underline cursor
+	ToggleOverwrite,
+}
+
+/// How keys edit the input, see
+/// [`ReadlineBuilder::edit_mode()`][crate::ReadlineBuilder::edit_mode]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+	/// Keys do what they are bound to in the keymap, and characters are
+	/// typed
+	#[default]
+	Emacs,
+	/// Like vi: Esc switches from typing to normal mode, where letters are
+	/// commands
+	///
+	/// Normal mode has the motions `h` `l` `w` `b` `e` `0` `^` and `$`, the
+	/// operators `d` `c` and `y` followed by a motion or doubled for the whole
+	/// input, `x` `D` `C` `p` and `P`, `i` `a` `I` and `A` to type again, `j`
+	/// and `k` for the history, counts before commands and `.` to repeat the
+	/// last change.  Other keys such as Enter and Ctrl keys work in both
+	/// modes.
+	Vi,
+}
+
+/// Which keys do which [`EditAction`]
+pub(crate) struct Keymap {
+	// Single keys or sequences of keys, e.g. Ctrl-X Ctrl-U
+	bindings: Vec<(Vec<KeyEvent>, EditAction)>,
+}
+
+/// What keys typed one after another are bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Lookup {
+	Action(EditAction),
+	/// The start of a longer sequence, waiting for the next key
+	Prefix,
+	Unbound,
+}
+
+impl Default for Keymap {
+	fn default() -> Self {
+		use EditAction::*;
+		let key = |code| KeyEvent::from(code);
+		let ctrl = |code| KeyEvent::new(code, KeyModifiers::CONTROL);
+		let alt = |code| KeyEvent::new(code, KeyModifiers::ALT);
+		let mut bindings = vec![
+			(key(KeyCode::Enter), AcceptLine),
+			(ctrl(KeyCode::Char('c')), Interrupt),
+			(ctrl(KeyCode::Char('d')), EndOfFile),
+			(ctrl(KeyCode::Char('l')), ClearScreen),
+			(key(KeyCode::Left), MoveLeft),
+			(key(KeyCode::Right), MoveRight),
+			(ctrl(KeyCode::Left), MoveWordLeft),
+			(ctrl(KeyCode::Right), MoveWordRight),
+			(alt(KeyCode::Char('b')), BackwardWord),
+			(alt(KeyCode::Char('f')), ForwardWord),
+			(key(KeyCode::Home), MoveHome),
+			(key(KeyCode::End), MoveEnd),
+			(key(KeyCode::Backspace), DeleteBackward),
+			(key(KeyCode::Delete), DeleteForward),
+			(ctrl(KeyCode::Char('w')), DeleteWordBackward),
+			(ctrl(KeyCode::Char('u')), KillToStart),
+			(alt(KeyCode::Char('d')), KillWordForward),
+			(alt(KeyCode::Backspace), KillWordBackward),
+			(ctrl(KeyCode::Char('t')), TransposeChars),
+			(alt(KeyCode::Char('t')), TransposeWords),
+			(alt(KeyCode::Char('u')), UppercaseWord),
+			(alt(KeyCode::Char('l')), LowercaseWord),
+			(alt(KeyCode::Char('c')), CapitalizeWord),
+			(ctrl(KeyCode::Char('y')), Yank),
+			(alt(KeyCode::Char('y')), YankPop),
+			(key(KeyCode::Up), HistoryPrevious),
+			(key(KeyCode::Down), HistoryNext),
+			(ctrl(KeyCode::Char('r')), ReverseSearch),
+			(ctrl(KeyCode::Char('s')), ForwardSearch),
+			(key(KeyCode::Tab), Complete),
+			(key(KeyCode::Insert), ToggleOverwrite),
+			(ctrl(KeyCode::Char('_')), Undo),
+			// What most terminals send for Ctrl-_
+			(ctrl(KeyCode::Char('7')), Undo),
+			(alt(KeyCode::Char('_')), Redo),
+		];
+		if cfg!(feature = "emacs") {
+			bindings.extend([
+				(ctrl(KeyCode::Char('a')), MoveHome),
+				(ctrl(KeyCode::Char('e')), MoveEnd),
+				(ctrl(KeyCode::Char('k')), KillLine),
+			]);
+		}
+		let mut bindings: Vec<_> = bindings
+			.into_iter()
+			.map(|(key, action)| (vec![key], action))
+			.collect();
+		bindings.push((
+			vec![ctrl(KeyCode::Char('x')), ctrl(KeyCode::Char('u'))],
+			Undo,
+		));
+		Self { bindings }
+	}
+}
+
+impl Keymap {
+	/// Bind the sequence of `keys` to `action`, replacing what it was bound to
+	pub fn bind(&mut self, keys: Vec<KeyEvent>, action: EditAction) {
+		self.unbind(&keys);
+		self.bindings.push((keys, action));
+	}
+	/// Remove the binding of the sequence of `keys`, returning the action it
+	/// was bound to
+	pub fn unbind(&mut self, keys: &[KeyEvent]) -> Option<EditAction> {
+		let index = self
+			.bindings
+			.iter()
+			.position(|(bound, _)| same_keys(bound, keys))?;
+		Some(self.bindings.remove(index).1)
+	}
+	/// The action a single `key` is bound to
+	pub fn action(&self, key: &KeyEvent) -> Option<EditAction> {
+		match self.lookup(std::slice::from_ref(key)) {
+			Lookup::Action(action) => Some(action),
+			_ => None,
+		}
+	}
+	/// What the sequence of `keys` typed so far is bound to
+	///
+	/// A sequence that starts a longer one waits for the next key even if it
+	/// is bound itself.  Single keys with modifiers other than just Ctrl that
+	/// aren't bound themselves do what the key without modifiers does, e.g.
+	/// Shift-Left moves left.  Ctrl-Alt is used to type some characters on
+	/// many keyboard layouts.
+	pub fn lookup(&self, keys: &[KeyEvent]) -> Lookup {
+		let find = |keys: &[KeyEvent]| {
+			self.bindings
+				.iter()
+				.find(|(bound, _)| same_keys(bound, keys))
+				.map(|&(_, action)| action)
+		};
+		let prefix = self
+			.bindings
+			.iter()
+			.any(|(bound, _)| bound.len() > keys.len() && same_keys(&bound[..keys.len()], keys));
+		if prefix {
+			return Lookup::Prefix;
+		}
+		let action = find(keys).or_else(|| match keys {
+			[key] if !matches!(key.modifiers, KeyModifiers::NONE | KeyModifiers::CONTROL) => {
+				find(&[KeyEvent::from(key.code)])
+			}
+			_ => None,
+		});
+		action.map_or(Lookup::Unbound, Lookup::Action)
+	}
+}
+
+fn same_keys(a: &[KeyEvent], b: &[KeyEvent]) -> bool {
+	a.len() == b.len()
+		&& a.iter()
+			.zip(b)
+			.all(|(a, b)| a.code == b.code && a.modifiers == b.modifiers)
+}
+
+#[cfg(test)]
+#[test]
+fn test_keymap() {
+	let mut keymap = Keymap::default();
+	let ctrl_left = KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL);
+	let shift_left = KeyEvent::new(KeyCode::Left, KeyModifiers::SHIFT);
+	let alt_enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT);
+	assert_eq!(keymap.action(&ctrl_left), Some(EditAction::MoveWordLeft));
+	assert_eq!(keymap.action(&shift_left), Some(EditAction::MoveLeft));
+	assert_eq!(keymap.action(&alt_enter), Some(EditAction::AcceptLine));
+	assert_eq!(
+		keymap.action(&KeyEvent::new(KeyCode::Char('x'), KeyModifiers::CONTROL)),
+		None
+	);
+
+	keymap.bind(vec![alt_enter], EditAction::Complete);
+	assert_eq!(keymap.action(&alt_enter), Some(EditAction::Complete));
+	assert_eq!(
+		keymap.unbind(&[KeyEvent::from(KeyCode::Left)]),
+		Some(EditAction::MoveLeft)
+	);
+	assert_eq!(keymap.action(&shift_left), None);
+	assert_eq!(keymap.action(&ctrl_left), Some(EditAction::MoveWordLeft));
+
+	// Sequences of keys
+	let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+	assert_eq!(keymap.lookup(&[ctrl('x')]), Lookup::Prefix);
+	assert_eq!(
+		keymap.lookup(&[ctrl('x'), ctrl('u')]),
+		Lookup::Action(EditAction::Undo)
+	);
+	assert_eq!(keymap.lookup(&[ctrl('x'), ctrl('a')]), Lookup::Unbound);
+	keymap.bind(vec![ctrl('x'), ctrl('a'), ctrl('a')], EditAction::MoveHome);
+	assert_eq!(keymap.lookup(&[ctrl('x'), ctrl('a')]), Lookup::Prefix);
+	keymap.unbind(&[ctrl('x'), ctrl('u')]);
+	keymap.unbind(&[ctrl('x'), ctrl('a'), ctrl('a')]);
+	assert_eq!(keymap.lookup(&[ctrl('x')]), Lookup::Unbound);
+}
@@ -0,0 +1,103 @@
+use std::{collections::VecDeque, ops::Range};
+
+/// Most killed texts kept, like the default of GNU readline
+const MAX_KILLS: usize = 10;
+
+/// What the previous key did, kills after kills add to the same text and
+/// yank-pop only works after a yank
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+enum LastCommand {
+	#[default]
+	Other,
+	Kill,
+	// The entry yanked and the byte range of the input it was inserted at
+	Yank {
+		index: usize,
+		range: Range<usize>,
+	},
+}
+
+/// Text erased with the kill keys, for putting back with yank
+#[derive(Debug, Default)]
+pub(crate) struct KillRing {
+	// Newest first
+	entries: VecDeque<String>,
+	last: LastCommand,
+	previous: LastCommand,
+}
+
+impl KillRing {
+	/// Called for every key press, before it is handled
+	pub fn start_command(&mut self) {
+		self.previous = std::mem::take(&mut self.last);
+	}
+	/// Add text erased before the cursor (`backward`) or after it
+	///
+	/// Right after another kill it is added to the text killed before
+	/// instead, so that Ctrl-W pressed twice yanks back both words.
+	pub fn kill(&mut self, text: &str, backward: bool) {
+		match self.entries.front_mut() {
+			Some(entry) if self.previous == LastCommand::Kill => match backward {
+				true => entry.insert_str(0, text),
+				false => entry.push_str(text),
+			},
+			_ if !text.is_empty() => {
+				self.entries.push_front(text.to_owned());
+				self.entries.truncate(MAX_KILLS);
+			}
+			_ => {}
+		}
+		self.last = LastCommand::Kill;
+	}
+	/// The text killed last, to insert at byte offset `at`
+	pub fn yank(&mut self, at: usize) -> Option<&str> {
+		let text = self.entries.front()?;
+		self.last = LastCommand::Yank {
+			index: 0,
+			range: at..at + text.len(),
+		};
+		Some(text)
+	}
+	/// Right after a yank, the byte range of the text yanked and the text
+	/// killed before it to replace it with
+	pub fn yank_pop(&mut self) -> Option<(Range<usize>, &str)> {
+		let LastCommand::Yank { index, range } = self.previous.clone() else {
+			return None;
+		};
+		let index = (index + 1) % self.entries.len();
+		let text = &self.entries[index];
+		self.last = LastCommand::Yank {
+			index,
+			range: range.start..range.start + text.len(),
+		};
+		Some((range, text))
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_kill_ring() {
+	let mut ring = KillRing::default();
+	ring.start_command();
+	assert_eq!(ring.yank(0), None);
+	ring.start_command();
+	ring.kill("world", true);
+	ring.start_command();
+	ring.kill("hello ", true);
+	ring.start_command();
+	ring.kill("!", false);
+	// Something else in between starts a new text
+	ring.start_command();
+	ring.start_command();
+	ring.kill("bye", false);
+
+	ring.start_command();
+	assert_eq!(ring.yank(2), Some("bye"));
+	ring.start_command();
+	assert_eq!(ring.yank_pop(), Some((2..5, "hello world!")));
+	ring.start_command();
+	assert_eq!(ring.yank_pop(), Some((2..14, "bye")));
+	ring.start_command();
+	ring.start_command();
+	assert_eq!(ring.yank_pop(), None);
+}
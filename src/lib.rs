@@ -29,40 +29,168 @@
 //!
 //! - Left, Right: Move cursor left/right
 //! - Up, Down: Scroll through input history
+//! - Ctrl-R / Ctrl-S: Search backward/forward through the history as you
+//!   type; Ctrl-R and Ctrl-S again find the next match, Esc or Ctrl-G cancel
+//!   and any other key keeps the entry found.  Ctrl-S freezes output instead
+//!   while flow control is enabled, searching forward then works after
+//!   Ctrl-R.
+//! - Right, End at the end of the input: Accept the suggested history entry,
+//!   when enabled with [`Readline::set_autosuggestions()`], or the hint
+//!     - Alt-Right accepts its next word (see
+//!       [`Readline::set_accept_hint_keys()`])
 //! - Ctrl-W: Erase the input from the cursor to the previous whitespace
+//! - Alt-D / Alt-Backspace: Erase the input up to the end/start of the
+//!   next/previous word of letters and digits, like Alt-F and Alt-B move
 //! - Ctrl-U: Erase the input before the cursor
-//! - Ctrl-L: Clear the screen
+//! - Ctrl-K: Erase the input from the cursor to the end, when the "emacs"
+//!   feature (on by default) is enabled
+//! - Ctrl-T / Alt-T: Swap the characters/words of letters and digits around
+//!   the cursor, or the last two at the end of the input
+//! - Alt-U / Alt-L / Alt-C: Uppercase/lowercase/capitalize the input up to
+//!   the end of the word of letters and digits
+//! - Ctrl-Y: Insert the text erased last by one of the keys above, erasing
+//!   several times in a row adds to the same text
+//!     - Alt-Y right after: Replace it with the text erased before, going
+//!       further back when pressed again
+//! - Insert: Switch between inserting and overwriting typed characters, an
+//!   underline cursor is shown while overwriting
+//! - Ctrl-_ or Ctrl-X Ctrl-U: Undo the last change, Alt-_ redoes it
+//! - Ctrl-L: Clear the screen (see [`Readline::set_clear_behavior()`])
+//! - Ctrl-S / Ctrl-Q: Freeze and release output, when enabled with
+//!   [`Readline::enable_flow_control()`]
 //! - Ctrl-Left / Ctrl-Right: Move to previous/next whitespace
+//! - Alt-B / Alt-F: Move to the start/end of the previous/next word of
+//!   letters and digits
+//! - Tab: Complete the text before the cursor, when a completer is set with
+//!   [`Readline::set_completer()`]
+//!     - In the menu of candidates: Tab, Shift-Tab, Up, Down: Select a
+//!       candidate; Enter: Insert it; Esc: Close the menu
 //! - Home: Jump to the start of the line
 //!     - When the "emacs" feature (on by default) is enabled, Ctrl-A has the
 //!       same effect.
 //! - End: Jump to the end of the line
 //!     - When the "emacs" feature (on by default) is enabled, Ctrl-E has the
 //!       same effect.
+//! - Alt-0 to Alt-9: Repeat the next key that many times, e.g. Alt-3 Left
+//!   moves three characters left; more digits typed right after add to the
+//!   count
 //! - Ctrl-D: Send an `Eof` event
 //! - Ctrl-C: Send an `Interrupt` event
+//!
+//! Keys can be bound to other [`EditAction`]s with [`Readline::bind()`], and
+//! sequences of keys with [`Readline::bind_sequence()`].  This includes
+//! Ctrl-C, Ctrl-D and Ctrl-L, e.g. Ctrl-D can delete forward
+//! ([`EditAction::DeleteForward`]) so that the application is only left with
+//! a command, or [`Readline::unbind()`] can make it do nothing.
+//! Vi keys are available with [`EditMode::Vi`].
 
 use std::{
 	collections::VecDeque,
-	io::{self, stdout, Stdout, Write},
+	fmt,
+	future::Future,
+	io::{self, stderr, stdin, stdout, BufReader, BufWriter, IsTerminal, Stderr, Stdout, Write},
 	ops::DerefMut,
+	path::{Path, PathBuf},
 	pin::Pin,
+	sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError},
 	task::{Context, Poll},
+	time::{Duration, Instant, SystemTime},
 };
 
 use crossterm::{
-	event::EventStream,
+	cursor,
+	event::{Event, EventStream, KeyEvent},
+	style::{Color, StyledContent, Stylize},
 	terminal::{self, disable_raw_mode, Clear},
 	QueueableCommand,
 };
-use futures_util::{pin_mut, ready, select, AsyncWrite, FutureExt, StreamExt};
+use futures_timer::Delay;
+use futures_util::{
+	future::{self, abortable, AbortHandle, BoxFuture},
+	pin_mut, ready, select,
+	stream::{BoxStream, Stream},
+	AsyncRead, AsyncWrite, FutureExt, StreamExt,
+};
 use thingbuf::mpsc::{errors::TrySendError, Receiver, Sender};
 use thiserror::Error;
 
+mod ansi;
+#[cfg(feature = "clap")]
+mod clap_completer;
+mod color;
+mod completion;
+mod expansion;
+mod highlighter;
+mod hinter;
 mod history;
+mod history_format;
+mod history_store;
+mod input;
+mod input_codec;
+#[cfg(feature = "inputrc")]
+mod inputrc;
+mod keymap;
+mod kill_ring;
 mod line;
+mod line_reader;
+#[cfg(feature = "log")]
+mod logger;
+mod matching;
+mod output;
+mod panic_hook;
+mod prefix;
+mod prompt;
+mod search;
+#[cfg(all(unix, feature = "signals"))]
+mod signals;
+mod split;
+mod stream_output;
+mod sync_update;
+mod theme;
+#[cfg(feature = "tracing-subscriber")]
+mod tracing_writer;
+mod undo;
+mod validator;
+mod vi;
+#[cfg(test)]
+mod virtual_terminal;
+mod words;
+mod writers;
+use ansi::AnsiStripper;
+#[cfg(feature = "clap")]
+pub use clap_completer::ClapCompleter;
+pub use color::ColorSupport;
+use completion::CompletionSource;
+pub use completion::{AsyncCompleter, Candidate, Completer, CompletionStyle};
+pub use expansion::HistoryExpansion;
+pub use highlighter::Highlighter;
+pub use hinter::{AcceptHint, Hinter};
 use history::History;
+pub use history::{HistoryDuplicates, HistoryEntry, HistoryView};
+pub use history_format::HistoryFormat;
+pub use history_store::HistoryStore;
+pub use keymap::{EditAction, EditMode};
 use line::LineState;
+pub use line::{BellStyle, ClearBehavior, RenderMode};
+use line_reader::LineReader;
+#[cfg(feature = "log")]
+pub use logger::init_logger;
+pub use matching::MatchStrategy;
+use output::HeldOutput;
+pub use output::OutputOverflow;
+use prefix::LinePrefix;
+pub use prefix::TimestampFormat;
+pub use prompt::{Prompt, PromptProvider};
+pub use split::{ReadlinePrinter, ReadlineReader};
+use stream_output::OutputPump;
+pub use stream_output::StreamOutput;
+pub use theme::Theme;
+#[cfg(feature = "tracing-subscriber")]
+pub use tracing_writer::TracingWriter;
+pub use validator::{Validator, Validity};
+pub use words::{current_word, split_words, Word};
+pub use writers::WriterStats;
+use writers::{lock_buffer, WriterBuffer, Writers};
 
 /// Error returned from [`readline()`][Readline::readline].  Such errors
 /// generally require specific procedures to recover from.
@@ -72,109 +200,350 @@ pub enum ReadlineError {
 	#[error(transparent)]
 	IO(#[from] io::Error),
 
-	/// `readline()` was called after the [`SharedWriter`] was dropped and
-	/// everything written to the `SharedWriter` was already output
+	/// `readline()` was called after all [`SharedWriter`]s were dropped and
+	/// everything written to them was already output.
+	///
+	/// This can be recovered from by creating a new writer with
+	/// [`Readline::new_writer()`], after which `readline()` works again.  It
+	/// is never returned if
+	/// [`set_ignore_writer_close()`][Readline::set_ignore_writer_close] is
+	/// enabled.
 	#[error("line writers closed")]
 	Closed,
 }
 
 /// Events emitted by [`Readline::readline()`]
-#[derive(Debug)]
+///
+/// More kinds of events may be added, so matches on it need a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ReadlineEvent {
 	/// The user entered a line of text
 	Line(String),
-	/// The user pressed Ctrl-D
+	/// The user pressed Ctrl-D, or another key bound to
+	/// [`EditAction::EndOfFile`]
 	Eof,
-	/// The user pressed Ctrl-C
+	/// The user pressed Ctrl-C, or another key bound to
+	/// [`EditAction::Interrupt`]
 	Interrupted,
+	/// The user pressed a key bound with [`Readline::bind_event()`], the
+	/// input is kept for the next [`readline()`][Readline::readline]
+	Custom(String),
+}
+
+/// How long [`Readline::readline_with_timeout()`] waits for an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadlineTimeout {
+	/// Give up after this long, even while the user is typing
+	After(Duration),
+	/// Give up once no key was pressed for this long
+	Idle(Duration),
 }
 
 /// Clonable object that implements [`Write`][std::io::Write] and
 /// [`AsyncWrite`][futures::io::AsyncWrite] and allows for sending data to the
 /// terminal without messing up the readline.
 ///
+/// When the "tokio" feature is enabled, `SharedWriter` also implements
+/// `tokio::io::AsyncWrite`.  When the "tracing-subscriber" feature is enabled,
+/// it implements `MakeWriter`, so it can be passed to
+/// `tracing_subscriber::fmt().with_writer()`.
+///
 /// A `SharedWriter` instance is obtained by calling [`Readline::new()`], which
 /// also returns a [`Readline`] instance associated with the writer.
 ///
 /// Data written to a `SharedWriter` is only output when a line feed (`'\n'`)
 /// has been written and either [`Readline::readline()`] or
 /// [`Readline::flush()`] is executing on the associated `Readline` instance.
-#[pin_project::pin_project]
+#[pin_project::pin_project(PinnedDrop)]
 pub struct SharedWriter {
-	#[pin]
-	buffer: Vec<u8>,
+	// Shared with the `Readline` so that `flush_async()` can reach partial lines
+	buffer: WriterBuffer,
 	sender: Sender<Vec<u8>>,
+	prefix: LinePrefix,
+	writers: Arc<Writers>,
 }
 impl Clone for SharedWriter {
 	fn clone(&self) -> Self {
 		Self {
-			buffer: Vec::new(),
+			buffer: self.writers.add_writer(),
 			sender: self.sender.clone(),
+			prefix: self.prefix.clone(),
+			writers: self.writers.clone(),
 		}
 	}
 }
-impl AsyncWrite for SharedWriter {
-	fn poll_write(
+impl SharedWriter {
+	fn new(sender: Sender<Vec<u8>>, writers: Arc<Writers>) -> Self {
+		Self {
+			buffer: writers.add_writer(),
+			sender,
+			prefix: LinePrefix::default(),
+			writers,
+		}
+	}
+
+	/// Returns counters of the lines sent and writes failed, aggregated over
+	/// all clones of this writer.
+	///
+	/// Useful for checking whether output is being lost, e.g. because of
+	/// `WouldBlock` errors while the channel is full.
+	pub fn stats(&self) -> WriterStats {
+		self.writers.snapshot()
+	}
+
+	/// Prefix every line written to this writer with a timestamp, or pass
+	/// `None` to disable timestamps.
+	///
+	/// The timestamp is added once per line, so a line written in several
+	/// parts is only prefixed once.  Clones made afterwards inherit the
+	/// setting.
+	pub fn set_timestamps(&mut self, format: impl Into<Option<TimestampFormat>>) {
+		self.prefix.timestamps = format.into();
+	}
+
+	/// Prefix every line written to this writer with `[tag] ` so output can
+	/// be told apart by its source.
+	///
+	/// This is meant to be used on clones handed to different parts of a
+	/// program, e.g. `writer.clone().with_tag("net")`.  Clones of a tagged
+	/// writer keep its tag.
+	pub fn with_tag(mut self, tag: impl Into<String>) -> Self {
+		self.prefix.tag = Some(tag.into());
+		self
+	}
+
+	/// Set the color used to print this writer's tag
+	pub fn with_tag_color(mut self, color: Color) -> Self {
+		self.prefix.tag_color = Some(color);
+		self
+	}
+
+	/// Change or remove (with `None`) the tag printed in front of each line
+	pub fn set_tag(&mut self, tag: Option<String>) {
+		self.prefix.tag = tag;
+	}
+
+	/// Print a line prefixed with a green `INFO` label.
+	///
+	/// Accepts anything that implements `Display`, including
+	/// [`format_args!`], in which case no intermediate string is allocated.
+	/// Errors are the same as those returned by [`Write`][io::Write].
+	pub fn info(&mut self, message: impl fmt::Display) -> io::Result<()> {
+		self.print_leveled("INFO".green(), message)
+	}
+
+	/// Print a line prefixed with a yellow `WARN` label.  See
+	/// [`info()`][SharedWriter::info].
+	pub fn warn(&mut self, message: impl fmt::Display) -> io::Result<()> {
+		self.print_leveled("WARN".yellow(), message)
+	}
+
+	/// Print a line prefixed with a red `ERROR` label.  See
+	/// [`info()`][SharedWriter::info].
+	pub fn error(&mut self, message: impl fmt::Display) -> io::Result<()> {
+		self.print_leveled("ERROR".red(), message)
+	}
+
+	fn print_leveled(
+		&mut self,
+		label: StyledContent<&str>,
+		message: impl fmt::Display,
+	) -> io::Result<()> {
+		writeln!(self, "{} {message}", label.bold())
+	}
+}
+impl SharedWriter {
+	// Like `io::Write::write` through `&self`, with `prefix` in place of the
+	// writer's own.  While the channel is full the data stays in the buffer,
+	// which lives as long as this writer, and is sent along with the next line
+	// or printed by `Readline::flush()`.  Used by the logging integrations,
+	// which can't retry writes and shouldn't block the thread logging.
+	#[cfg(any(feature = "log", feature = "tracing-subscriber"))]
+	fn write_buffered(&self, prefix: &mut LinePrefix, buf: &[u8]) -> io::Result<usize> {
+		let complete = {
+			let mut buffer = lock_buffer(&self.buffer);
+			prefix.extend(&mut buffer, buf);
+			buffer.ends_with(b"\n")
+		};
+		if complete {
+			self.send_buffered()?;
+		}
+		Ok(buf.len())
+	}
+
+	// Send the buffer, even if it doesn't end in a newline, if the channel has
+	// room
+	#[cfg(any(feature = "log", feature = "tracing-subscriber"))]
+	fn send_buffered(&self) -> io::Result<()> {
+		let mut buffer = lock_buffer(&self.buffer);
+		if buffer.is_empty() {
+			return Ok(());
+		}
+		match self.sender.try_send_ref() {
+			Ok(mut send_buf) => {
+				std::mem::swap(send_buf.deref_mut(), &mut buffer);
+				self.writers.record_sent(&send_buf);
+				buffer.clear();
+			}
+			Err(TrySendError::Full(_)) => self.writers.record_full(),
+			_ => return Err(self.writers.closed()),
+		}
+		Ok(())
+	}
+
+	// Shared by the `AsyncWrite` implementations of futures and tokio
+	fn poll_write_buffer(
 		self: Pin<&mut Self>,
 		cx: &mut Context<'_>,
 		buf: &[u8],
 	) -> Poll<io::Result<usize>> {
-		let mut this = self.project();
-		this.buffer.extend_from_slice(buf);
-		if this.buffer.ends_with(b"\n") {
+		let this = self.project();
+		let mut buffer = lock_buffer(this.buffer);
+		this.prefix.extend(&mut buffer, buf);
+		if buffer.ends_with(b"\n") {
 			let fut = this.sender.send_ref();
 			pin_mut!(fut);
-			let mut send_buf = ready!(fut.poll_unpin(cx)).map_err(|_| {
-				io::Error::new(io::ErrorKind::Other, "thingbuf receiver has closed")
-			})?;
+			let mut send_buf = ready!(fut.poll_unpin(cx)).map_err(|_| this.writers.closed())?;
 			// Swap buffers
-			std::mem::swap(send_buf.deref_mut(), &mut this.buffer);
-			this.buffer.clear();
+			std::mem::swap(send_buf.deref_mut(), &mut buffer);
+			this.writers.record_sent(&send_buf);
+			buffer.clear();
 			Poll::Ready(Ok(buf.len()))
 		} else {
 			Poll::Ready(Ok(buf.len()))
 		}
 	}
-	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-		let mut this = self.project();
+	fn poll_flush_buffer(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		let this = self.project();
 		let fut = this.sender.send_ref();
 		pin_mut!(fut);
-		let mut send_buf = ready!(fut.poll_unpin(cx))
-			.map_err(|_| io::Error::new(io::ErrorKind::Other, "thingbuf receiver has closed"))?;
+		let mut send_buf = ready!(fut.poll_unpin(cx)).map_err(|_| this.writers.closed())?;
 		// Swap buffers
-		std::mem::swap(send_buf.deref_mut(), &mut this.buffer);
-		this.buffer.clear();
+		let mut buffer = lock_buffer(this.buffer);
+		this.prefix.flush(&mut buffer);
+		std::mem::swap(send_buf.deref_mut(), &mut buffer);
+		this.writers.record_sent(&send_buf);
+		buffer.clear();
 		Poll::Ready(Ok(()))
 	}
+}
+#[pin_project::pinned_drop]
+impl PinnedDrop for SharedWriter {
+	fn drop(self: Pin<&mut Self>) {
+		let this = self.project();
+		if this.writers.remove_writer() == 0 {
+			// Wake up `readline()` with an empty buffer so it notices that
+			// all writers are gone
+			drop(this.sender.try_send_ref());
+		}
+	}
+}
+impl AsyncWrite for SharedWriter {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		self.poll_write_buffer(cx, buf)
+	}
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		self.poll_flush_buffer(cx)
+	}
 	fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
 		Poll::Ready(Ok(()))
 	}
 }
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for SharedWriter {
+	fn poll_write(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		self.poll_write_buffer(cx, buf)
+	}
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		self.poll_flush_buffer(cx)
+	}
+	fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+}
 impl io::Write for SharedWriter {
 	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-		self.buffer.extend_from_slice(buf);
-		if self.buffer.ends_with(b"\n") {
+		let mut buffer = lock_buffer(&self.buffer);
+		self.prefix.extend(&mut buffer, buf);
+		if buffer.ends_with(b"\n") {
 			match self.sender.try_send_ref() {
 				Ok(mut send_buf) => {
-					std::mem::swap(send_buf.deref_mut(), &mut self.buffer);
-					self.buffer.clear();
+					std::mem::swap(send_buf.deref_mut(), &mut buffer);
+					self.writers.record_sent(&send_buf);
+					buffer.clear();
+				}
+				Err(TrySendError::Full(_)) => {
+					self.writers.record_full();
+					return Err(io::ErrorKind::WouldBlock.into());
 				}
-				Err(TrySendError::Full(_)) => return Err(io::ErrorKind::WouldBlock.into()),
 				_ => {
-					return Err(io::Error::new(
-						io::ErrorKind::Other,
-						"thingbuf receiver has closed",
-					));
+					return Err(self.writers.closed());
 				}
 			}
 		}
 		Ok(buf.len())
 	}
 	fn flush(&mut self) -> io::Result<()> {
+		// Printed by `Readline::flush()` along with the rest of a partial line
+		self.prefix.flush(&mut lock_buffer(&self.buffer));
 		Ok(())
 	}
 }
 
+/// Clonable handle for updating the status lines of a [`Readline`] from
+/// other tasks, obtained from [`Readline::status_handle()`]
+pub struct StatusHandle<W: Write = Stdout> {
+	screen: Arc<Mutex<Screen<W>>>,
+}
+
+impl<W: Write> Clone for StatusHandle<W> {
+	fn clone(&self) -> Self {
+		Self {
+			screen: self.screen.clone(),
+		}
+	}
+}
+
+impl<W: Write> StatusHandle<W> {
+	/// Replace the status lines, see [`Readline::set_status_lines()`]
+	pub fn set(&self, lines: Vec<String>) -> Result<(), ReadlineError> {
+		lock(&self.screen).set_status_lines(lines)
+	}
+}
+
+/// Guard returned by [`Readline::pause()`] that resumes the `Readline` when
+/// dropped
+pub struct PauseGuard<'a, W: Write = Stdout> {
+	readline: &'a mut Readline<W>,
+	resumed: bool,
+}
+
+impl<W: Write> PauseGuard<'_, W> {
+	/// Take the terminal back and redraw the prompt, reporting any errors
+	/// that dropping the guard would ignore
+	pub fn resume(mut self) -> Result<(), ReadlineError> {
+		self.resumed = true;
+		self.readline.resume()
+	}
+}
+
+impl<W: Write> Drop for PauseGuard<'_, W> {
+	fn drop(&mut self) {
+		if !self.resumed {
+			let _ = self.readline.resume();
+		}
+	}
+}
+
 /// Structure for reading lines of input from a terminal while lines are output
 /// to the terminal concurrently.
 ///
@@ -185,141 +554,3001 @@ impl io::Write for SharedWriter {
 /// instances.  Lines written to an associated `SharedWriter` are output while
 /// retrieving input with `readline()` or by calling
 /// [`flush()`][Readline::flush].
-pub struct Readline {
-	raw_term: Stdout,
-	event_stream: EventStream, // Stream of events
-	line_receiver: Receiver<Vec<u8>>,
-	line: LineState, // Current line
+///
+/// When a `Readline` is dropped, lines already sent by its writers are printed
+/// before the terminal is restored.  Lines still buffered inside writers are
+/// only printed by [`close()`][Readline::close] or
+/// [`flush_async()`][Readline::flush_async].
+///
+/// The prompt is drawn on stdout unless another output is passed to
+/// [`with_output()`][Readline::with_output].
+pub struct Readline<W: Write = Stdout> {
+	input: Input,
+	// Whether raw mode was enabled on the terminal of the process
+	raw_mode: bool,
+	// Sends drawing to the stream passed to `with_streams()`
+	output_pump: Option<OutputPump>,
+	line_receiver: Arc<Receiver<Vec<u8>>>,
+	// Kept so that new writers can be created, see `new_writer()`
+	line_sender: Sender<Vec<u8>>,
+	writers: Arc<Writers>,
+	ignore_writer_close: bool,
+	// Shared with the task returned by `flusher()`
+	screen: Arc<Mutex<Screen<W>>>,
+	flusher: Option<AbortHandle>,
+	history: History,
+	// Where the history is saved when dropped, see `ReadlineBuilder::history_file()`
+	history_file: Option<PathBuf>,
+	// Debounce interval and most unsaved entries before new entries are
+	// appended to the history file
+	autosave: Option<(Duration, usize)>,
+	autosave_deadline: Option<Instant>,
+	history_store: Option<Box<dyn HistoryStore>>,
+	// Entries being added to the store, in order
+	history_write: Option<BoxFuture<'static, io::Result<()>>>,
+	// Shared with the `ReadlinePrinter` while split, the half dropped last
+	// restores the terminal
+	halves: Option<Arc<()>>,
+	#[cfg(all(unix, feature = "signals"))]
+	suspend_on_ctrl_z: bool,
+	// When the toast currently shown should be removed
+	toast_deadline: Option<Instant>,
+	// When the prompt should stop flashing for a visible bell
+	flash_deadline: Option<Instant>,
+	// When the terminal size should be checked next, if it is polled
+	size_poll_deadline: Option<Instant>,
+	// Wakes `poll_readline()` for the earliest of the deadlines above
+	timer: Option<(Instant, Delay)>,
+	// When input was last received, for `ReadlineTimeout::Idle`
+	last_input: Instant,
 }
 
-impl Readline {
-	/// Create a new `Readline` instance with an associated
-	/// [`SharedWriter`]
-	pub fn new(prompt: String) -> Result<(Self, SharedWriter), ReadlineError> {
-		let (sender, line_receiver) = thingbuf::mpsc::channel(500);
-		terminal::enable_raw_mode()?;
+// Where the prompt is drawn
+enum Terminal<W> {
+	Interactive(W),
+	// Output isn't a terminal, so drawing is dropped and only output from
+	// writers is written, see `Screen::write_output()`
+	Plain(W),
+}
 
-		let line = LineState::new(prompt, terminal::size()?);
+impl<W: Write> Write for Terminal<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		match self {
+			Self::Interactive(term) => term.write(buf),
+			Self::Plain(_) => Ok(buf.len()),
+		}
+	}
 
-		let mut readline = Readline {
-			raw_term: stdout(),
-			event_stream: EventStream::new(),
-			line_receiver,
-			line,
-		};
-		readline.line.render(&mut readline.raw_term)?;
-		readline.raw_term.queue(terminal::EnableLineWrap)?;
-		readline.raw_term.flush()?;
-		Ok((
-			readline,
-			SharedWriter {
-				sender,
-				buffer: Vec::new(),
-			},
-		))
+	fn flush(&mut self) -> io::Result<()> {
+		match self {
+			Self::Interactive(term) | Self::Plain(term) => term.flush(),
+		}
+	}
+}
+
+// Everything needed to draw on the terminal
+struct Screen<W: Write> {
+	// Buffered so that each redraw reaches the terminal in one write when flushed
+	raw_term: BufWriter<Terminal<W>>,
+	line: LineState, // Current line
+	// Set while a `PauseGuard` exists; output is held back until resumed
+	paused: bool,
+	// Set by `pause_output()`
+	output_paused: bool,
+	held: HeldOutput,
+	// Whether `restore()` has already run
+	restored: bool,
+	// Set by `enter_alternate_screen()`
+	alternate_screen: bool,
+}
+
+impl<W: Write> Screen<W> {
+	fn is_interactive(&self) -> bool {
+		matches!(self.raw_term.get_ref(), Terminal::Interactive(_))
 	}
 
-	/// Change the prompt
-	pub fn update_prompt(&mut self, prompt: &str) -> Result<(), ReadlineError> {
-		self.line.update_prompt(prompt, &mut self.raw_term)?;
+	// Writes output above the prompt, or as is if output isn't a terminal
+	fn write_output(&mut self, data: &[u8]) -> Result<(), ReadlineError> {
+		match self.raw_term.get_mut() {
+			Terminal::Interactive(_) => self.line.print_data(data, &mut self.raw_term)?,
+			Terminal::Plain(out) => out.write_all(data)?,
+		}
 		Ok(())
 	}
 
-	/// Clear the screen
-	pub fn clear(&mut self) -> Result<(), ReadlineError> {
-		self.raw_term.queue(Clear(terminal::ClearType::All))?;
-		self.line.clear_and_render(&mut self.raw_term)?;
+	// Prints output above the prompt, or holds it back while paused
+	fn print(&mut self, data: &[u8]) -> Result<(), ReadlineError> {
+		if self.paused || self.output_paused {
+			self.held.push(data, self.output_paused);
+			return Ok(());
+		}
+		if self.line.is_frozen() {
+			self.held.push(data, false);
+			if self.held.len() >= self.held.limit {
+				// Release automatically so writers never get stuck
+				self.line.set_frozen(None, &mut self.raw_term)?;
+				self.print_held()?;
+			} else {
+				self.line
+					.set_frozen(Some(self.held.len()), &mut self.raw_term)?;
+			}
+			self.raw_term.flush()?;
+			return Ok(());
+		}
+		self.write_output(data)?;
 		self.raw_term.flush()?;
 		Ok(())
 	}
 
-	/// Set maximum history length.  The default length is 1000.
-	pub fn set_max_history(&mut self, max_size: usize) {
-		self.line.history.set_max_size(max_size);
+	// Prints output held back while paused
+	fn print_held(&mut self) -> Result<(), ReadlineError> {
+		if self.held.len() != 0 {
+			let held = self.held.take();
+			self.write_output(&held)?;
+		}
+		Ok(())
 	}
 
-	/// Set whether the input line should remain on the screen after
-	/// events.
-	///
-	/// If `enter` is true, then when the user presses "Enter", the prompt
-	/// and the text they entered will remain on the screen, and the cursor
-	/// will move to the next line.  If `enter` is false, the prompt &
-	/// input will be erased instead.
-	///
-	/// `control_c` similarly controls the behavior for when the user
-	/// presses Ctrl-C.
-	///
-	/// The default value for both settings is `true`.
-	pub fn should_print_line_on(&mut self, enter: bool, control_c: bool) {
-		self.line.should_print_line_on_enter = enter;
-		self.line.should_print_line_on_control_c = control_c;
+	// When a deferred redraw of the prompt is due
+	fn redraw_deadline(&self) -> Option<Instant> {
+		if self.paused {
+			return None;
+		}
+		self.line.redraw_deadline()
 	}
 
-	/// Flush all writers to terminal and erase the prompt string
-	pub fn flush(&mut self) -> Result<(), ReadlineError> {
-		while let Ok(buf) = self.line_receiver.try_recv_ref() {
-			self.line.print_data(&buf, &mut self.raw_term)?;
+	fn redraw(&mut self) -> Result<(), ReadlineError> {
+		if !self.paused {
+			self.line.redraw(&mut self.raw_term)?;
+			self.raw_term.flush()?;
 		}
-		self.line.clear(&mut self.raw_term)?;
+		Ok(())
+	}
+
+	// Whether no more output should be received until output is resumed
+	fn holding_full(&self) -> bool {
+		self.output_paused && self.held.is_full()
+	}
+
+	// Leaves the prompt area and undoes the terminal changes made when the
+	// `Readline` was created
+	fn restore(&mut self, raw_mode: bool) -> Result<(), ReadlineError> {
+		if self.restored {
+			return Ok(());
+		}
+		self.restored = true;
+		if !self.is_interactive() {
+			self.raw_term.flush()?;
+			return Ok(());
+		}
+		let Screen { raw_term, line, .. } = self;
+		if std::mem::take(&mut self.alternate_screen) {
+			#[cfg(all(unix, feature = "signals"))]
+			if raw_mode {
+				signals::set_alternate_screen(false);
+			}
+			// The prompt was erased from the main screen when entering
+			raw_term.queue(terminal::LeaveAlternateScreen)?;
+		} else {
+			line.finish(raw_term)?;
+		}
+		raw_term
+			.queue(cursor::SetCursorStyle::DefaultUserShape)?
+			.queue(cursor::Show)?;
+		raw_term.flush()?;
+		if raw_mode {
+			disable_raw_mode()?;
+			#[cfg(all(unix, feature = "signals"))]
+			signals::set_active(false);
+			panic_hook::set_flush(None);
+		}
+		Ok(())
+	}
+
+	fn set_status_lines(&mut self, lines: Vec<String>) -> Result<(), ReadlineError> {
+		if self.paused {
+			// Drawn on resume
+			self.line.set_status_lines(lines, &mut io::sink())?;
+			return Ok(());
+		}
+		self.line.set_status_lines(lines, &mut self.raw_term)?;
 		self.raw_term.flush()?;
 		Ok(())
 	}
+}
 
-	/// Polling function for readline, manages all input and output.
-	/// Returns either an Readline Event or an Error
-	pub async fn readline(&mut self) -> Result<ReadlineEvent, ReadlineError> {
-		loop {
-			select! {
-				event = self.event_stream.next().fuse() => match event {
-					Some(Ok(event)) => {
-						match self.line.handle_event(event, &mut self.raw_term) {
-							Ok(Some(event)) => {
-								self.raw_term.flush()?;
-								return Result::<_, ReadlineError>::Ok(event)
-							},
-							Err(e) => return Err(e),
-							Ok(None) => self.raw_term.flush()?,
-						}
-					}
-					Some(Err(e)) => return Err(e.into()),
-					None => {},
-				},
-				result = self.line_receiver.recv_ref().fuse() => match result {
-					Some(buf) => {
-						self.line.print_data(&buf, &mut self.raw_term)?;
-						self.raw_term.flush()?;
-					},
-					None => return Err(ReadlineError::Closed),
-				},
+// Prints output as it arrives, until the channel is closed
+async fn print_output<W: Write>(
+	receiver: &Receiver<Vec<u8>>,
+	screen: &Mutex<Screen<W>>,
+) -> Result<(), ReadlineError> {
+	loop {
+		// Wait for output to be resumed
+		while lock(screen).holding_full() {
+			Delay::new(Duration::from_millis(50)).await;
+		}
+		let redraw = expire(lock(screen).redraw_deadline());
+		let buf = select! {
+			buf = receiver.recv_ref().fuse() => buf,
+			_ = redraw.fuse() => {
+				lock(screen).redraw()?;
+				continue;
 			}
+		};
+		let Some(buf) = buf else {
+			return Ok(());
+		};
+		let mut batch = buf.to_vec();
+		drop(buf);
+		drain_batch(receiver, &mut batch);
+		// Empty buffers are sent when the last writer is dropped
+		if !batch.is_empty() {
+			lock(screen).print(&batch)?;
 		}
 	}
+}
 
-	/// Add a line to the input history
-	pub fn add_history_entry(&mut self, entry: String) -> Option<()> {
-		self.line.history.add_entry(entry);
-		// Return value to keep compatibility with previous API.
-		Some(())
+// Maximum amount of output printed with a single redraw of the prompt, so
+// that input stays responsive during a flood of output
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+// Appends output that has already been sent to `batch`, up to MAX_BATCH_BYTES
+fn drain_batch(receiver: &Receiver<Vec<u8>>, batch: &mut Vec<u8>) {
+	while batch.len() < MAX_BATCH_BYTES {
+		match receiver.try_recv_ref() {
+			Ok(buf) => batch.extend_from_slice(&buf),
+			Err(_) => break,
+		}
 	}
+}
 
-	/// Returns the entries of the history in the order they were added in.
-	pub fn get_history_entries(&self) -> &VecDeque<String> {
-		self.line.history.get_entries()
+// Prints everything already sent through the channel, returning whether anything was printed
+fn print_pending(
+	receiver: &Receiver<Vec<u8>>,
+	mut print: impl FnMut(&[u8]) -> Result<(), ReadlineError>,
+) -> Result<bool, ReadlineError> {
+	let mut printed = false;
+	loop {
+		let mut batch = Vec::new();
+		drain_batch(receiver, &mut batch);
+		if batch.is_empty() {
+			break;
+		}
+		print(&batch)?;
+		printed = true;
 	}
+	Ok(printed)
+}
 
-	/// Replaces the current history.
-	pub fn set_history_entries(&mut self, entries: impl IntoIterator<Item = String>) {
-		self.line.history.set_entries(entries);
+// How long the prompt is shown in reverse video for a visible bell
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+// How often the terminal size is checked when resize events can't be relied on
+const SIZE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// Where input comes from
+enum Input {
+	Terminal(EventStream),
+	// Decoded from the stream passed to `with_streams()`
+	Events(BoxStream<'static, io::Result<Event>>),
+	// Input isn't a terminal, so it is read line by line
+	Lines(LineReader),
+}
+
+enum Received {
+	Event(io::Result<Event>),
+	// `None` at the end of input
+	Line(io::Result<Option<String>>),
+}
+
+impl Input {
+	fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Received> {
+		Poll::Ready(match self {
+			Self::Terminal(event_stream) => match ready!(event_stream.poll_next_unpin(cx)) {
+				Some(event) => Received::Event(event),
+				None => return Poll::Pending,
+			},
+			Self::Events(events) => match ready!(events.poll_next_unpin(cx)) {
+				Some(event) => Received::Event(event),
+				None => Received::Line(Ok(None)),
+			},
+			Self::Lines(line_reader) => Received::Line(ready!(line_reader.poll_next(cx))),
+		})
 	}
+}
 
-	/// Clears the current history.
-	pub fn clear_history(&mut self) {
-		self.set_history_entries([]);
+// The size of the terminal, if it can tell
+fn terminal_size() -> Option<(u16, u16)> {
+	terminal::size()
+		.ok()
+		.filter(|&(columns, rows)| columns != 0 && rows != 0)
+}
+
+// Size to assume when the terminal doesn't tell, from `COLUMNS` and `LINES`
+// looked up with `var`, or 80x24
+fn default_size(var: impl Fn(&str) -> Option<String>) -> (u16, u16) {
+	let var = |name, default| {
+		var(name)
+			.and_then(|value| value.parse().ok())
+			.filter(|&value| value != 0)
+			.unwrap_or(default)
+	};
+	(var("COLUMNS", 80), var("LINES", 24))
+}
+
+// Completes at `deadline`, or never if there is none
+async fn expire(deadline: Option<Instant>) {
+	match deadline {
+		Some(deadline) => Delay::new(deadline.saturating_duration_since(Instant::now())).await,
+		None => future::pending().await,
 	}
 }
 
-impl Drop for Readline {
-	fn drop(&mut self) {
-		let _ = disable_raw_mode();
+// Let other tasks run before continuing
+async fn yield_now() {
+	let mut yielded = false;
+	future::poll_fn(|cx| {
+		if yielded {
+			Poll::Ready(())
+		} else {
+			yielded = true;
+			cx.waker().wake_by_ref();
+			Poll::Pending
+		}
+	})
+	.await
+}
+
+fn lock<W: Write>(screen: &Mutex<Screen<W>>) -> MutexGuard<'_, Screen<W>> {
+	// Rendering state is still usable after a panic elsewhere
+	screen.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Options for creating a [`Readline`]
+pub struct ReadlineBuilder {
+	prompt: Prompt,
+	interactive: Option<bool>,
+	events: Option<BoxStream<'static, io::Result<Event>>>,
+	theme: Theme,
+	color_support: Option<ColorSupport>,
+	history_file: Option<PathBuf>,
+	edit_mode: EditMode,
+}
+
+impl ReadlineBuilder {
+	/// Start with `prompt`, which may contain escape sequences for styling
+	/// and newlines, like [`Readline::new()`]
+	pub fn new(prompt: String) -> Self {
+		Self {
+			prompt: prompt.into(),
+			interactive: None,
+			events: None,
+			theme: Theme::default(),
+			color_support: None,
+			history_file: None,
+			edit_mode: EditMode::default(),
+		}
+	}
+
+	/// Start with a prompt built from styled pieces of text instead of the
+	/// one given to [`new()`][Self::new], see
+	/// [`Readline::set_styled_prompt()`]
+	pub fn styled_prompt(mut self, prompt: Prompt) -> Self {
+		self.prompt = prompt;
+		self
+	}
+
+	/// Read events from `events` instead of the terminal
+	///
+	/// Raw mode isn't enabled and the terminal size isn't queried (set it
+	/// with [`Readline::set_term_size()`] instead), so this makes it possible
+	/// to drive [`readline()`][Readline::readline] with scripted key presses,
+	/// e.g. in tests together with [`build_with_output()`][Self::build_with_output]
+	/// and a buffer as output.  The end of `events` is reported as
+	/// [`ReadlineEvent::Eof`].
+	///
+	/// The prompt is drawn on the output unless
+	/// [`force_interactive(false)`][Self::force_interactive] is set as well.
+	pub fn event_source(
+		mut self,
+		events: impl Stream<Item = io::Result<Event>> + Unpin + Send + 'static,
+	) -> Self {
+		self.events = Some(events.boxed());
+		self
+	}
+
+	/// Whether to use the terminal interactively
+	///
+	/// By default, this is the case if both stdin and stdout are terminals.
+	/// Otherwise, e.g. when input is piped in or output is redirected to a
+	/// file, [`readline()`][Readline::readline] reads plain lines from stdin
+	/// (returning [`ReadlineEvent::Eof`] at the end) without raw mode, the
+	/// prompt isn't drawn and output from [`SharedWriter`]s is written as is.
+	pub fn force_interactive(mut self, interactive: bool) -> Self {
+		self.interactive = Some(interactive);
+		self
+	}
+
+	/// Set the colors and text attributes of what is drawn around the input
+	///
+	/// See [`Readline::set_theme()`].
+	pub fn theme(mut self, theme: Theme) -> Self {
+		self.theme = theme;
+		self
+	}
+
+	/// Set how much styling the terminal shows, instead of detecting it with
+	/// [`ColorSupport::detect()`]
+	///
+	/// See [`Readline::set_color_support()`].
+	pub fn color_support(mut self, support: ColorSupport) -> Self {
+		self.color_support = Some(support);
+		self
+	}
+
+	/// Edit with emacs-like keys (the default) or vi keys
+	///
+	/// See [`Readline::set_edit_mode()`].
+	pub fn edit_mode(mut self, mode: EditMode) -> Self {
+		self.edit_mode = mode;
+		self
+	}
+
+	/// Load the history from the file at `path`, and save it there when the
+	/// `Readline` is dropped
+	///
+	/// A missing file is created when the history is saved.  See
+	/// [`Readline::save_history()`] for the format.
+	pub fn history_file(mut self, path: impl Into<PathBuf>) -> Self {
+		self.history_file = Some(path.into());
+		self
+	}
+
+	/// Create the `Readline` instance with an associated [`SharedWriter`]
+	pub fn build(mut self) -> Result<(Readline, SharedWriter), ReadlineError> {
+		self.interactive = self
+			.interactive
+			.or_else(|| Some(stdin().is_terminal() && stdout().is_terminal()));
+		let (readline, writer) = self.build_with_output(stdout())?;
+		readline.flush_on_panic();
+		Ok((readline, writer))
+	}
+
+	/// Create the `Readline` instance, drawing the prompt and writing output
+	/// from [`SharedWriter`]s on stderr
+	///
+	/// This leaves stdout to the application, e.g. for results that are
+	/// piped into another program.
+	pub fn build_on_stderr(mut self) -> Result<(Readline<Stderr>, SharedWriter), ReadlineError> {
+		self.interactive = self
+			.interactive
+			.or_else(|| Some(stdin().is_terminal() && stderr().is_terminal()));
+		let (readline, writer) = self.build_with_output(stderr())?;
+		readline.flush_on_panic();
+		Ok((readline, writer))
+	}
+
+	/// Create the `Readline` instance, drawing on `output` instead of stdout
+	///
+	/// Raw mode and the terminal size still apply to the terminal of the
+	/// process.  Unless forced, the terminal is used interactively if stdin
+	/// is a terminal.
+	pub fn build_with_output<W: Write>(
+		mut self,
+		output: W,
+	) -> Result<(Readline<W>, SharedWriter), ReadlineError> {
+		let history = self.load_history()?;
+		if let Some(events) = self.events.take() {
+			let term = match self.interactive {
+				Some(false) => Terminal::Plain(output),
+				_ => Terminal::Interactive(output),
+			};
+			let term_size = default_size(|name| std::env::var(name).ok());
+			return self.build_from(term, Input::Events(events), term_size, history);
+		}
+		let interactive = self.interactive.unwrap_or_else(|| stdin().is_terminal());
+		let term_size = if interactive {
+			#[cfg(all(unix, feature = "signals"))]
+			signals::save_terminal();
+			terminal::enable_raw_mode()?;
+			#[cfg(all(unix, feature = "signals"))]
+			signals::set_active(true);
+			terminal_size().unwrap_or_else(|| default_size(|name| std::env::var(name).ok()))
+		} else {
+			default_size(|name| std::env::var(name).ok())
+		};
+
+		let (term, input) = match interactive {
+			true => (
+				Terminal::Interactive(output),
+				Input::Terminal(EventStream::new()),
+			),
+			false => (
+				Terminal::Plain(output),
+				Input::Lines(LineReader::spawn(BufReader::new(stdin()))),
+			),
+		};
+		let mut readline = self.build_from(term, input, term_size, history)?;
+		readline.0.raw_mode = interactive;
+		readline.0.size_poll_deadline =
+			(interactive && cfg!(windows)).then(|| Instant::now() + SIZE_POLL_INTERVAL);
+		Ok(readline)
+	}
+
+	/// Create a `Readline` instance that reads key presses from `input` and
+	/// draws on `output`, e.g. the channel of an SSH or telnet session
+	///
+	/// See [`Readline::with_streams()`].
+	pub fn build_with_streams(
+		self,
+		input: impl AsyncRead + Unpin + Send + 'static,
+		output: impl AsyncWrite + Unpin + Send + 'static,
+		term_size: (u16, u16),
+	) -> Result<(Readline<StreamOutput>, SharedWriter), ReadlineError> {
+		let history = self.load_history()?;
+		let (output_pump, output) = OutputPump::new(output);
+		let input = Input::Events(input_codec::decode_events(input));
+		let term = Terminal::Interactive(output);
+		let mut readline = self.build_from(term, input, term_size, history)?;
+		readline.0.output_pump = Some(output_pump);
+		Ok(readline)
+	}
+
+	// The history from the history file, if it exists
+	fn load_history(&self) -> Result<History, ReadlineError> {
+		let mut history = History::default();
+		if let Some(path) = &self.history_file {
+			match history.load(path) {
+				Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+				_ => {}
+			}
+		}
+		Ok(history)
+	}
+
+	fn build_from<W: Write>(
+		self,
+		term: Terminal<W>,
+		input: Input,
+		term_size: (u16, u16),
+		history: History,
+	) -> Result<(Readline<W>, SharedWriter), ReadlineError> {
+		let (sender, line_receiver) = thingbuf::mpsc::channel(500);
+		let writers = Arc::new(Writers::default());
+		let mut screen = Screen {
+			raw_term: BufWriter::new(term),
+			line: LineState::new(self.prompt, term_size),
+			paused: false,
+			output_paused: false,
+			held: HeldOutput::default(),
+			restored: false,
+			alternate_screen: false,
+		};
+		screen.line.theme = self.theme;
+		screen.line.color_support = self.color_support.unwrap_or_else(ColorSupport::detect);
+		screen.line.set_edit_mode(self.edit_mode)?;
+		screen.line.render(&mut screen.raw_term)?;
+		screen.raw_term.queue(terminal::EnableLineWrap)?;
+		screen.raw_term.flush()?;
+
+		let readline = Readline {
+			input,
+			raw_mode: false,
+			output_pump: None,
+			line_receiver: Arc::new(line_receiver),
+			line_sender: sender.clone(),
+			writers: writers.clone(),
+			ignore_writer_close: false,
+			screen: Arc::new(Mutex::new(screen)),
+			flusher: None,
+			history,
+			history_file: self.history_file,
+			autosave: None,
+			autosave_deadline: None,
+			history_store: None,
+			history_write: None,
+			halves: None,
+			#[cfg(all(unix, feature = "signals"))]
+			suspend_on_ctrl_z: false,
+			toast_deadline: None,
+			flash_deadline: None,
+			size_poll_deadline: None,
+			timer: None,
+			last_input: Instant::now(),
+		};
+		Ok((readline, SharedWriter::new(sender, writers)))
 	}
 }
+
+impl Readline {
+	/// Create a new `Readline` instance with an associated
+	/// [`SharedWriter`]
+	///
+	/// The prompt may contain escape sequences for styling, and newlines to
+	/// show the input on its last row.
+	///
+	/// See [`ReadlineBuilder`] for more options.
+	pub fn new(prompt: String) -> Result<(Self, SharedWriter), ReadlineError> {
+		ReadlineBuilder::new(prompt).build()
+	}
+
+	/// Restore the terminal before a panic message is printed
+	///
+	/// Wraps the current panic hook so that, if a panic happens while a
+	/// `Readline` is active, raw mode is disabled, the alternate screen is
+	/// left, the cursor is shown and a newline is written before the previous
+	/// hook prints the panic message.
+	/// Otherwise the message is smeared across the raw screen and the shell
+	/// is left without echo.
+	///
+	/// Calling this more than once has no further effect, and it is shared by
+	/// all `Readline` instances.  Use
+	/// [`remove_panic_hook()`][Readline::remove_panic_hook] to disable it.
+	pub fn install_panic_hook() {
+		panic_hook::install();
+	}
+
+	/// Disable the hook installed by
+	/// [`install_panic_hook()`][Readline::install_panic_hook]
+	pub fn remove_panic_hook() {
+		panic_hook::remove();
+	}
+}
+
+impl Readline<Stderr> {
+	/// Create a new `Readline` instance that draws on stderr, with an
+	/// associated [`SharedWriter`] that also writes to stderr
+	///
+	/// See [`ReadlineBuilder::build_on_stderr()`].
+	pub fn new_on_stderr(prompt: String) -> Result<(Self, SharedWriter), ReadlineError> {
+		ReadlineBuilder::new(prompt).build_on_stderr()
+	}
+}
+
+impl Readline<StreamOutput> {
+	/// Create a new `Readline` instance that reads key presses from `input`
+	/// and draws on `output`, with an associated [`SharedWriter`]
+	///
+	/// This serves a prompt over a connection rather than the terminal of the
+	/// process, e.g. in an SSH or telnet server: `input` receives the bytes
+	/// typed by the remote user and `output` is sent back to their terminal.
+	/// The terminal of the process is never touched and raw mode is left
+	/// alone, so any number of instances can run at once.
+	///
+	/// `term_size` is the size of the remote terminal in columns and rows.
+	/// Pass changes on to [`set_term_size()`][Readline::set_term_size], e.g.
+	/// when an SSH `window-change` request arrives.
+	///
+	/// Output is sent while [`readline()`][Readline::readline],
+	/// [`flush_async()`][Readline::flush_async] or
+	/// [`close()`][Readline::close] runs.  The end of `input` is reported as
+	/// [`ReadlineEvent::Eof`].
+	pub fn with_streams(
+		prompt: String,
+		input: impl AsyncRead + Unpin + Send + 'static,
+		output: impl AsyncWrite + Unpin + Send + 'static,
+		term_size: (u16, u16),
+	) -> Result<(Self, SharedWriter), ReadlineError> {
+		ReadlineBuilder::new(prompt).build_with_streams(input, output, term_size)
+	}
+}
+
+impl<W: Write + Send + 'static> Readline<W> {
+	// Lets the panic hook write out drawing still queued for the terminal and
+	// leave the alternate screen before restoring it
+	fn flush_on_panic(&self) {
+		if !self.raw_mode {
+			return;
+		}
+		let screen = Arc::downgrade(&self.screen);
+		panic_hook::set_flush(Some(Box::new(move || {
+			let Some(screen) = screen.upgrade() else {
+				return;
+			};
+			let mut screen = match screen.try_lock() {
+				Ok(screen) => screen,
+				Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+				Err(TryLockError::WouldBlock) => return,
+			};
+			let _ = screen.raw_term.flush();
+			if std::mem::take(&mut screen.alternate_screen) {
+				let _ = screen.raw_term.queue(terminal::LeaveAlternateScreen);
+				let _ = screen.raw_term.flush();
+			}
+		})));
+	}
+}
+
+impl<W: Write> Readline<W> {
+	/// Create a new `Readline` instance that draws on `output` instead of
+	/// stdout, with an associated [`SharedWriter`]
+	///
+	/// See [`ReadlineBuilder::build_with_output()`].
+	pub fn with_output(prompt: String, output: W) -> Result<(Self, SharedWriter), ReadlineError> {
+		ReadlineBuilder::new(prompt).build_with_output(output)
+	}
+
+	/// Whether the terminal is used interactively, see
+	/// [`ReadlineBuilder::force_interactive()`]
+	pub fn is_interactive(&self) -> bool {
+		lock(&self.screen).is_interactive()
+	}
+
+	/// Restore the terminal when the process receives SIGTERM, SIGHUP or
+	/// SIGQUIT
+	///
+	/// No destructors run when a process is killed by a signal, so the
+	/// terminal would be left in raw mode.  This installs handlers (through
+	/// `signal-hook`, so they are chained with handlers the application
+	/// registered the same way) that restore the terminal mode, show the
+	/// cursor, and then perform the signal's default action.
+	///
+	/// Applications that handle these signals themselves, e.g. to shut down
+	/// gracefully, should instead call the function returned by
+	/// [`terminal_restore_fn()`][Readline::terminal_restore_fn] from their
+	/// own handler.
+	///
+	/// Requires the "signals" feature and is only available on unix.
+	#[cfg(all(unix, feature = "signals"))]
+	pub fn restore_terminal_on_signals(&self) -> Result<(), ReadlineError> {
+		signals::register()?;
+		Ok(())
+	}
+
+	/// Set whether pressing Ctrl-Z suspends the program, like it does in a
+	/// terminal that isn't in raw mode
+	///
+	/// When enabled, Ctrl-Z erases the prompt, restores the terminal and
+	/// sends SIGTSTP to the process group, returning control to the shell.
+	/// Once the program is continued (e.g. with `fg`), raw mode is enabled
+	/// again and the prompt is redrawn with the current input.
+	///
+	/// Disabled by default.  Requires the "signals" feature and is only
+	/// available on unix.
+	#[cfg(all(unix, feature = "signals"))]
+	pub fn set_suspend_on_ctrl_z(&mut self, enabled: bool) {
+		self.suspend_on_ctrl_z = enabled;
+	}
+
+	/// Hand the terminal over to another program, such as a subprocess that
+	/// needs it for its own input and output (an editor, pager or `ssh`)
+	///
+	/// Pending output is printed, the prompt is erased, raw mode is disabled
+	/// and no more input is read from the terminal.  Output written to
+	/// [`SharedWriter`]s in the meantime is held back so it doesn't corrupt
+	/// the other program's screen.
+	///
+	/// Dropping the returned guard, or calling [`PauseGuard::resume()`],
+	/// enables raw mode again and redraws the prompt with the input typed so
+	/// far, followed by any output that was held back.
+	pub fn pause(&mut self) -> Result<PauseGuard<'_, W>, ReadlineError> {
+		// Dropping the event stream stops crossterm from reading input meant
+		// for the other program
+		if let Input::Terminal(event_stream) = &mut self.input {
+			*event_stream = EventStream::new();
+		}
+		{
+			let screen = &mut *lock(&self.screen);
+			print_pending(&self.line_receiver, |data| screen.write_output(data))?;
+			let Screen { raw_term, line, .. } = screen;
+			line.erase(raw_term)?;
+			raw_term.queue(cursor::Show)?;
+			raw_term.flush()?;
+			screen.paused = true;
+		}
+		if self.raw_mode {
+			disable_raw_mode()?;
+			#[cfg(all(unix, feature = "signals"))]
+			signals::set_active(false);
+		}
+		Ok(PauseGuard {
+			readline: self,
+			resumed: false,
+		})
+	}
+
+	fn resume(&mut self) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		if self.raw_mode {
+			terminal::enable_raw_mode()?;
+			#[cfg(all(unix, feature = "signals"))]
+			signals::set_active(true);
+		}
+		screen.paused = false;
+		// The window may have been resized in the meantime
+		let size = self.current_size(&screen.line);
+		screen.line.resume(size, &mut screen.raw_term)?;
+		if !screen.output_paused {
+			screen.print_held()?;
+			print_pending(&self.line_receiver, |data| screen.write_output(data))?;
+		}
+		screen.raw_term.flush()?;
+		Ok(())
+	}
+
+	// The size of the terminal of the process, or else the size last set,
+	// e.g. the remote peer's for `with_streams()`
+	fn current_size(&self, line: &LineState) -> (u16, u16) {
+		match self.raw_mode {
+			true => terminal_size().unwrap_or(line.term_size()),
+			false => line.term_size(),
+		}
+	}
+
+	// Hands the terminal back to the shell until the process is continued
+	#[cfg(all(unix, feature = "signals"))]
+	fn suspend(&self) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.erase(raw_term)?;
+		raw_term.queue(cursor::Show)?;
+		raw_term.flush()?;
+		disable_raw_mode()?;
+		signals::set_active(false);
+
+		signals::suspend()?;
+
+		terminal::enable_raw_mode()?;
+		signals::set_active(true);
+		// The window may have been resized while suspended
+		line.resume(terminal_size().unwrap_or(line.term_size()), raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Returns a function that restores the terminal and is safe to call
+	/// from a signal handler
+	///
+	/// It only uses async-signal-safe operations (`tcsetattr` and `write`),
+	/// and does nothing if no `Readline` has the terminal in raw mode.  It
+	/// can't take the `Readline`'s lock, so part of a redraw still queued in
+	/// its output buffer may be lost.
+	///
+	/// Requires the "signals" feature and is only available on unix.
+	#[cfg(all(unix, feature = "signals"))]
+	pub fn terminal_restore_fn(&self) -> fn() {
+		signals::restore_terminal
+	}
+
+	/// Change the prompt
+	///
+	/// Like the prompt given to [`Readline::new()`], it may span several rows
+	/// separated by `\n`, with the input after the last one.  It is replaced
+	/// when drawn while a [`PromptProvider`] is set.
+	pub fn update_prompt(&mut self, prompt: &str) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.update_prompt(prompt.into(), raw_term)?;
+		Ok(())
+	}
+
+	/// Change the prompt to one built from styled pieces of text, instead of
+	/// a string with escape sequences in it
+	pub fn set_styled_prompt(&mut self, prompt: impl Into<Prompt>) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.update_prompt(prompt.into(), raw_term)?;
+		Ok(())
+	}
+
+	/// Clear the screen, in the same way as pressing Ctrl-L
+	///
+	/// See [`set_clear_behavior()`][Readline::set_clear_behavior].
+	pub fn clear(&mut self) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.clear_screen(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Set the shape of the cursor while editing, e.g.
+	/// `SetCursorStyle::SteadyBar`.  `None` leaves it up to the terminal.
+	///
+	/// The terminal's default shape is restored when the `Readline` is
+	/// dropped or closed.
+	pub fn set_cursor_style(
+		&mut self,
+		style: Option<cursor::SetCursorStyle>,
+	) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.cursor_style = style;
+		raw_term.queue(style.unwrap_or(cursor::SetCursorStyle::DefaultUserShape))?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Hide the cursor, e.g. while output is streaming, until
+	/// [`show_cursor()`][Readline::show_cursor] is called
+	pub fn hide_cursor(&mut self) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.cursor_hidden = true;
+		raw_term.queue(cursor::Hide)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Show the cursor after [`hide_cursor()`][Readline::hide_cursor]
+	pub fn show_cursor(&mut self) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.cursor_hidden = false;
+		raw_term.queue(cursor::Show)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Set the feedback given when a key can't do anything, e.g. Backspace on
+	/// an empty line or Up at the oldest history entry.  Defaults to
+	/// [`BellStyle::None`].
+	pub fn set_bell(&mut self, style: BellStyle) {
+		lock(&self.screen).line.bell_style = style;
+	}
+
+	/// Complete the text before the cursor with `completer` when Tab is
+	/// pressed
+	///
+	/// A single candidate is inserted.  With several, the prefix they share
+	/// is inserted, and once there is nothing more to insert, they are shown
+	/// in a menu below the input.  Tab and the arrow keys move through the
+	/// menu, Enter inserts the selected candidate and Esc closes it.  See
+	/// [`set_completion_style()`][Readline::set_completion_style] to list
+	/// them like bash instead.
+	pub fn set_completer(&mut self, completer: impl Completer + 'static) {
+		lock(&self.screen).line.completer = Some(CompletionSource::Sync(Box::new(completer)));
+	}
+
+	/// Set what Tab does when the candidates share nothing more than what is
+	/// already typed.  Defaults to [`CompletionStyle::Menu`].
+	pub fn set_completion_style(&mut self, style: CompletionStyle) {
+		lock(&self.screen).line.completion_style = style;
+	}
+
+	/// Suggest the rest of the newest history entry that starts with the
+	/// input, like fish does
+	///
+	/// The suggestion is shown dimmed after the cursor, Right or End accepts
+	/// it and Alt-Right its next word.  Off by default.  The keys are set with
+	/// [`set_accept_hint_keys()`][Readline::set_accept_hint_keys].
+	pub fn set_autosuggestions(&mut self, enabled: bool) {
+		lock(&self.screen).line.autosuggest = enabled;
+	}
+
+	/// Show the hint from `hinter` dimmed after the input, e.g. the arguments
+	/// the command being typed expects
+	///
+	/// Hints are looked up again after every key press.  A history
+	/// suggestion from [`set_autosuggestions()`][Readline::set_autosuggestions]
+	/// takes precedence.
+	pub fn set_hinter(&mut self, hinter: impl Hinter + 'static) {
+		lock(&self.screen).line.hinter = Some(Box::new(hinter));
+	}
+
+	/// Bind `key` to `action`, replacing what it did before
+	///
+	/// Modifiers must match exactly, but keys with modifiers other than just
+	/// Ctrl that aren't bound themselves do what the key without modifiers
+	/// does, e.g. Shift-Left what Left does.  Characters without a binding
+	/// are inserted.
+	pub fn bind(&mut self, key: KeyEvent, action: EditAction) {
+		let line = &mut lock(&self.screen).line;
+		line.unbind_custom(&key);
+		line.keymap.bind(vec![key], action);
+	}
+
+	/// Remove the binding of `key`, returning the action it was bound to
+	pub fn unbind(&mut self, key: KeyEvent) -> Option<EditAction> {
+		let line = &mut lock(&self.screen).line;
+		line.unbind_custom(&key);
+		line.keymap.unbind(&[key])
+	}
+
+	/// Make `key` return [`ReadlineEvent::Custom`] with `name` from
+	/// [`readline()`][Readline::readline], e.g. F1 for help
+	///
+	/// The input is left as it is.  The binding takes precedence over the
+	/// [`EditAction`] the key is bound to, until it is replaced by
+	/// [`bind()`][Readline::bind] or removed by [`unbind()`][Readline::unbind].
+	pub fn bind_event(&mut self, key: KeyEvent, name: impl Into<String>) {
+		let name = name.into();
+		self.bind_fn(key, move |_| Some(ReadlineEvent::Custom(name.clone())));
+	}
+
+	/// Call `handler` with the input when `key` is pressed, returning the
+	/// event it returns from [`readline()`][Readline::readline]
+	///
+	/// With `None` the input is edited further.  See
+	/// [`bind_event()`][Readline::bind_event] for how the binding relates to
+	/// others.
+	pub fn bind_fn(
+		&mut self,
+		key: KeyEvent,
+		handler: impl FnMut(&str) -> Option<ReadlineEvent> + Send + 'static,
+	) {
+		let line = &mut lock(&self.screen).line;
+		line.unbind_custom(&key);
+		line.custom_keys.push((key, Box::new(handler)));
+	}
+
+	/// Bind the sequence of `keys` typed one after another to `action`,
+	/// e.g. Ctrl-X Ctrl-U, replacing what it did before
+	///
+	/// After the first keys of a sequence the next one is waited for, even
+	/// if the keys typed so far are bound themselves.  Esc, or a key typed
+	/// more than two seconds later, cancels the sequence.
+	pub fn bind_sequence(&mut self, keys: impl IntoIterator<Item = KeyEvent>, action: EditAction) {
+		let keys = keys.into_iter().collect();
+		lock(&self.screen).line.keymap.bind(keys, action);
+	}
+
+	/// Remove the binding of the sequence of `keys`, returning the action it
+	/// was bound to
+	pub fn unbind_sequence(&mut self, keys: &[KeyEvent]) -> Option<EditAction> {
+		lock(&self.screen).line.keymap.unbind(keys)
+	}
+
+	/// Switch between emacs-like keys and vi keys
+	///
+	/// Vi mode starts in insert mode, and every new input does too.  See
+	/// [`EditMode::Vi`] for the commands of normal mode.
+	pub fn set_edit_mode(&mut self, mode: EditMode) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.clear(raw_term)?;
+		line.set_edit_mode(mode)?;
+		line.render(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Apply the key bindings and settings of the user's readline init file,
+	/// `$INPUTRC` or else `~/.inputrc`
+	///
+	/// A missing file is skipped.  See [`apply_inputrc()`][Self::apply_inputrc]
+	/// for what is understood of it.
+	#[cfg(feature = "inputrc")]
+	pub fn load_inputrc(&mut self) -> Result<(), ReadlineError> {
+		let Some(path) = inputrc::path() else {
+			return Ok(());
+		};
+		match std::fs::read_to_string(path) {
+			Ok(text) => self.apply_inputrc(&text),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	/// Apply the key bindings and settings of `text` in the format of a
+	/// readline init file
+	///
+	/// Bindings of keys and key sequences to the readline functions that
+	/// have an [`EditAction`] are added, and the variables `editing-mode`,
+	/// `bell-style` and `completion-ignore-case` are applied.  Macros,
+	/// `$include`, other variables and bindings in the vi command keymap are
+	/// skipped.  `$if` can test `mode=`, against the editing mode in use until
+	/// `editing-mode` is set, and `term=`, against `$TERM`.
+	#[cfg(feature = "inputrc")]
+	pub fn apply_inputrc(&mut self, text: &str) -> Result<(), ReadlineError> {
+		let term = std::env::var("TERM").ok();
+		let mode = lock(&self.screen).line.edit_mode();
+		let inputrc = inputrc::Inputrc::parse(text, mode, term.as_deref());
+		if let Some(mode) = inputrc.edit_mode {
+			self.set_edit_mode(mode)?;
+		}
+		let line = &mut lock(&self.screen).line;
+		if let Some(style) = inputrc.bell_style {
+			line.bell_style = style;
+		}
+		if let Some(ignore_case) = inputrc.completion_ignore_case {
+			line.completion_ignore_case = ignore_case;
+		}
+		for (keys, action) in inputrc.bindings {
+			line.keymap.bind(keys, action);
+		}
+		Ok(())
+	}
+
+	/// Show `prompt`, e.g. `"... "`, at the start of the rows after newlines
+	/// in the input and the rows it wraps onto, like Python's secondary prompt
+	///
+	/// Nothing is shown by default.  The prompt may contain escape sequences.
+	pub fn set_continuation_prompt(
+		&mut self,
+		prompt: impl Into<String>,
+	) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.set_continuation_prompt(prompt.into(), raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Show `insert` or `normal` before the prompt for the current vi mode
+	///
+	/// Nothing is shown by default.  The indicators may contain escape
+	/// sequences, and aren't shown in [`EditMode::Emacs`].
+	pub fn set_vi_mode_indicators(
+		&mut self,
+		insert: impl Into<String>,
+		normal: impl Into<String>,
+	) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.set_vi_indicators((insert.into(), normal.into()), raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Set the keys that insert the history suggestion or hint after the
+	/// input, all of it or its next word, replacing the defaults
+	///
+	/// The keys only do this while the cursor is at the end of the input and
+	/// something is shown after it, and do what they normally do otherwise.
+	/// The defaults are Right and End for [`AcceptHint::All`], and Alt-Right
+	/// for [`AcceptHint::Word`].  Modifiers must match exactly.
+	pub fn set_accept_hint_keys(&mut self, keys: impl IntoIterator<Item = (KeyEvent, AcceptHint)>) {
+		lock(&self.screen).line.accept_hint_keys = keys.into_iter().collect();
+	}
+
+	/// Style the input with `highlighter` whenever it is drawn, e.g. to color
+	/// keywords as they are typed
+	///
+	/// Every edit redraws the whole input while a highlighter is set, since
+	/// typing can change the style of what came before.
+	pub fn set_highlighter(&mut self, highlighter: impl Highlighter + 'static) {
+		lock(&self.screen).line.highlighter = Some(Box::new(highlighter));
+	}
+
+	/// Color the input by whether `validator` finds it valid, invalid or
+	/// incomplete, in the styles of the [`Theme`]
+	///
+	/// The input is checked again on every edit, e.g. to show an unknown
+	/// command in red as soon as it is typed.  A highlighter set with
+	/// [`set_highlighter()`][Readline::set_highlighter] takes precedence.
+	pub fn set_validator(&mut self, validator: impl Validator + 'static) {
+		lock(&self.screen).line.validator = Some(Box::new(validator));
+	}
+
+	/// Restyle the prompt with `highlighter` whenever it is drawn, e.g. in red
+	/// while the last command failed
+	///
+	/// Unlike [`update_prompt()`][Readline::update_prompt], the style can
+	/// depend on state shared with other tasks, and follows it without any
+	/// calls from them.  It takes effect the next time the prompt is drawn,
+	/// e.g. after output or a key press, or right away with
+	/// [`flush_and_redraw()`][Readline::flush_and_redraw].
+	pub fn set_prompt_highlighter(&mut self, highlighter: impl Highlighter + 'static) {
+		lock(&self.screen).line.prompt_highlighter = Some(Box::new(highlighter));
+	}
+
+	/// Ask `provider` for the prompt whenever it is drawn, e.g. to show the
+	/// current directory
+	///
+	/// Unlike [`update_prompt()`][Readline::update_prompt], the prompt can
+	/// depend on state shared with other tasks, and follows it without any
+	/// calls from them, which could race with drawing.  Like
+	/// [`set_prompt_highlighter()`][Readline::set_prompt_highlighter], it
+	/// takes effect the next time the prompt is drawn.
+	pub fn set_prompt_provider(&mut self, provider: impl PromptProvider + 'static) {
+		lock(&self.screen).line.prompt_provider = Some(Box::new(provider));
+	}
+
+	/// Set the colors and text attributes of the prompt, hints, completion
+	/// menu and indicators, and draw the prompt again
+	pub fn set_theme(&mut self, theme: Theme) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.theme = theme;
+		line.clear_and_render(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Set how much styling the terminal shows, and draw the prompt again
+	///
+	/// Colors in the prompt, the input, hints and the [`Theme`] are converted
+	/// to the nearest supported ones, or left out with
+	/// [`ColorSupport::Monochrome`] along with all other text attributes.
+	/// Detected from the environment by default, see
+	/// [`ColorSupport::detect()`].
+	pub fn set_color_support(&mut self, support: ColorSupport) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.color_support = support;
+		line.clear_and_render(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Set how the candidates from the completer are matched against the text
+	/// being completed.  Defaults to [`MatchStrategy::Prefix`].
+	///
+	/// Candidates that don't match are dropped, and in the completion menu,
+	/// the matched characters are shown in bold.
+	pub fn set_match_strategy(&mut self, strategy: MatchStrategy) {
+		lock(&self.screen).line.match_strategy = strategy;
+	}
+
+	/// Whether candidates match the text being completed regardless of case,
+	/// off by default
+	///
+	/// [`MatchStrategy::Fuzzy`] ignores case anyway while the text is all
+	/// lowercase.
+	pub fn set_completion_ignore_case(&mut self, ignore_case: bool) {
+		lock(&self.screen).line.completion_ignore_case = ignore_case;
+	}
+
+	/// Like [`set_completer()`][Readline::set_completer], but for completions
+	/// that take a while to look up
+	///
+	/// [`readline()`][Readline::readline] keeps printing output and handling
+	/// keys while the future runs.  If the input is edited before it
+	/// completes, its candidates are dropped.  Replaces a completer set with
+	/// `set_completer()`, and the other way around.
+	pub fn set_async_completer(&mut self, completer: impl AsyncCompleter + 'static) {
+		lock(&self.screen).line.completer = Some(CompletionSource::Async(Box::new(completer)));
+	}
+
+	/// Set what Ctrl-L and [`clear()`][Readline::clear] do.  Defaults to
+	/// [`ClearBehavior::ClearAll`].
+	pub fn set_clear_behavior(&mut self, behavior: ClearBehavior) {
+		lock(&self.screen).line.clear_behavior = behavior;
+	}
+
+	/// Stop printing output from [`SharedWriter`]s, e.g. while the user is
+	/// answering a sensitive question, until
+	/// [`resume_output()`][Readline::resume_output] is called
+	///
+	/// Output is still received and held back, up to the limit set with
+	/// [`set_output_limit()`][Readline::set_output_limit].  Editing
+	/// continues normally in the meantime.
+	pub fn pause_output(&mut self) {
+		lock(&self.screen).output_paused = true;
+	}
+
+	/// Print the output held back since
+	/// [`pause_output()`][Readline::pause_output] in one go, and continue
+	/// printing output as it arrives
+	///
+	/// Returns the number of lines that were dropped because of
+	/// [`OutputOverflow::Drop`].
+	pub fn resume_output(&mut self) -> Result<usize, ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		screen.output_paused = false;
+		let dropped = std::mem::take(&mut screen.held.dropped);
+		if !screen.paused {
+			screen.print_held()?;
+			screen.raw_term.flush()?;
+		}
+		Ok(dropped)
+	}
+
+	/// Set how many lines are held back while output is paused, and what
+	/// happens to output beyond that.  Defaults to 1000 lines and
+	/// [`OutputOverflow::Backpressure`].
+	pub fn set_output_limit(&mut self, limit: usize, overflow: OutputOverflow) {
+		let held = &mut lock(&self.screen).held;
+		held.limit = limit;
+		held.overflow = overflow;
+	}
+
+	/// Limit how often the prompt is drawn again after output is printed
+	///
+	/// Under sustained output, redrawing the prompt after every batch makes
+	/// the input line shimmer.  With a minimum interval, output is still
+	/// printed right away, but the prompt is drawn at most once per interval,
+	/// and always once output stops.  Redraws caused by key presses are never
+	/// delayed.  Defaults to zero, i.e. no throttling.
+	pub fn set_min_redraw_interval(&mut self, interval: Duration) {
+		lock(&self.screen).line.min_redraw_interval = interval;
+	}
+
+	/// Redraw the whole input line after every edit
+	///
+	/// By default, typing or erasing at the end of the input only sends the
+	/// changed characters to the terminal, which matters on slow connections.
+	/// Pass `false` to always clear and redraw the line instead, in case a
+	/// terminal gets confused by this.
+	pub fn set_incremental_render(&mut self, enabled: bool) {
+		lock(&self.screen).line.incremental_render = enabled;
+	}
+
+	/// Wrap each redraw in synchronized output markers
+	///
+	/// On terminals that support it (e.g. kitty, WezTerm, iTerm2), the prompt
+	/// is then never seen half drawn while output is printed or keys are
+	/// pressed.  Other terminals ignore the markers.  Off by default.
+	pub fn set_synchronized_output(&mut self, enabled: bool) {
+		lock(&self.screen).line.synchronized_output = enabled;
+	}
+
+	/// Set the size of the terminal in columns and rows
+	///
+	/// If the terminal doesn't answer size queries, 80x24 is assumed, or the
+	/// size in the `COLUMNS` and `LINES` environment variables if set.  This
+	/// corrects it, e.g. with a size received over the network for a remote
+	/// terminal.  Resize events from the terminal still take precedence.
+	pub fn set_term_size(&mut self, columns: u16, rows: u16) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.resize((columns, rows), raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Check the terminal size periodically instead of relying on resize
+	/// events alone
+	///
+	/// Some terminals, mostly older ones on Windows, never report being
+	/// resized, leaving the prompt laid out for the old size.  When enabled,
+	/// [`readline()`][Readline::readline] checks the size twice a second and
+	/// lays the prompt out again if it changed.  Enabled by default on
+	/// Windows.
+	pub fn set_size_polling(&mut self, enabled: bool) {
+		self.size_poll_deadline = enabled.then(|| Instant::now() + SIZE_POLL_INTERVAL);
+	}
+
+	/// Let the user freeze output with Ctrl-S and release it with Ctrl-Q,
+	/// like terminal flow control
+	///
+	/// The terminal's own flow control is off in raw mode, so by default
+	/// Ctrl-S reaches the prompt and searches the history forward.  With
+	/// flow control enabled it freezes output instead, except during a
+	/// search started with Ctrl-R.
+	///
+	/// While frozen, the prompt stays usable and output is held back, with
+	/// the number of pending lines shown below the input.  Output is released
+	/// automatically once the limit set with
+	/// [`set_output_limit()`][Readline::set_output_limit] is reached, so
+	/// writers are never stuck.  Disabled by default.
+	pub fn enable_flow_control(&mut self, enabled: bool) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		screen.line.flow_control = enabled;
+		if !enabled && screen.line.is_frozen() {
+			screen.line.set_frozen(None, &mut screen.raw_term)?;
+			if !screen.output_paused {
+				screen.print_held()?;
+			}
+			screen.raw_term.flush()?;
+		}
+		Ok(())
+	}
+
+	/// Show status lines below the input line, e.g. connection state or
+	/// unread counts
+	///
+	/// The lines may contain ANSI escape sequences and are cut off at the
+	/// width of the terminal.  They always stay at the bottom, with output
+	/// from [`SharedWriter`]s appearing above the prompt.  Pass an empty
+	/// `Vec` to remove them.
+	///
+	/// Use [`status_handle()`][Readline::status_handle] to update the status
+	/// from another task.
+	pub fn set_status_lines(&mut self, lines: Vec<String>) -> Result<(), ReadlineError> {
+		lock(&self.screen).set_status_lines(lines)
+	}
+
+	/// Briefly show a message below the input line, e.g. "copied to
+	/// clipboard"
+	///
+	/// The message is removed after `duration` by
+	/// [`readline()`][Readline::readline], or by its next call if it isn't
+	/// running at the time.  Showing another toast replaces this one.
+	pub fn show_toast(&mut self, msg: String, duration: Duration) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.set_toast(Some(msg), raw_term)?;
+		raw_term.flush()?;
+		self.toast_deadline = Some(Instant::now() + duration);
+		Ok(())
+	}
+
+	/// Returns a handle for updating the status lines from other tasks
+	pub fn status_handle(&self) -> StatusHandle<W> {
+		StatusHandle {
+			screen: self.screen.clone(),
+		}
+	}
+
+	/// Change how output and the prompt are drawn
+	///
+	/// [`RenderMode::ScrollRegion`] keeps the prompt at the bottom of the
+	/// terminal and avoids flicker under heavy output.  Switching back to
+	/// [`RenderMode::Redraw`] at any time restores the default behavior, e.g.
+	/// on terminals where scroll regions misbehave.
+	pub fn set_render_mode(&mut self, mode: RenderMode) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.set_render_mode(mode, raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Switch to the terminal's alternate screen
+	///
+	/// The prompt and output are drawn on a separate screen without
+	/// scrollback, starting from the top.  Once output reaches the bottom,
+	/// earlier lines scroll off and the prompt stays on the last line.
+	/// Leaving the alternate screen, including when the `Readline` is dropped
+	/// or closed, or when the panic hook or signal handlers restore the
+	/// terminal, brings back the user's previous screen and scrollback
+	/// untouched.
+	pub fn enter_alternate_screen(&mut self) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		if std::mem::replace(&mut screen.alternate_screen, true) {
+			return Ok(());
+		}
+		#[cfg(all(unix, feature = "signals"))]
+		if self.raw_mode {
+			signals::set_alternate_screen(true);
+		}
+		let size = self.current_size(&screen.line);
+		let Screen { raw_term, line, .. } = screen;
+		line.erase(raw_term)?;
+		raw_term
+			.queue(terminal::EnterAlternateScreen)?
+			.queue(Clear(terminal::ClearType::All))?
+			.queue(cursor::MoveTo(0, 0))?;
+		line.resume(size, raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Switch back from the alternate screen to the main screen, redrawing
+	/// the prompt where it was before
+	/// [`enter_alternate_screen()`][Readline::enter_alternate_screen]
+	pub fn leave_alternate_screen(&mut self) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		if !std::mem::take(&mut screen.alternate_screen) {
+			return Ok(());
+		}
+		#[cfg(all(unix, feature = "signals"))]
+		if self.raw_mode {
+			signals::set_alternate_screen(false);
+		}
+		let size = self.current_size(&screen.line);
+		let Screen { raw_term, line, .. } = screen;
+		line.erase(raw_term)?;
+		// The terminal restores the cursor position of the main screen
+		raw_term.queue(terminal::LeaveAlternateScreen)?;
+		line.resume(size, raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Create a new [`SharedWriter`] associated with this `Readline`.
+	///
+	/// This works even after all previous writers have been dropped, so parts
+	/// of a program that start late can get a writer on demand.  `readline()`
+	/// only returns [`ReadlineError::Closed`] while no writer is alive, so
+	/// creating a new writer before that happens prevents it.
+	pub fn new_writer(&self) -> SharedWriter {
+		SharedWriter::new(self.line_sender.clone(), self.writers.clone())
+	}
+
+	/// Returns the number of [`SharedWriter`]s associated with this
+	/// `Readline` that are still alive.
+	pub fn writer_count(&self) -> usize {
+		self.writers.writers()
+	}
+
+	/// Returns `true` if all associated [`SharedWriter`]s have been dropped.
+	///
+	/// In that state, `readline()` returns [`ReadlineError::Closed`] as soon
+	/// as any output still pending has been printed.  Call
+	/// [`new_writer()`][Readline::new_writer] to leave it.
+	pub fn is_closed(&self) -> bool {
+		self.writer_count() == 0
+	}
+
+	/// Set whether `readline()` should keep reading input after all
+	/// [`SharedWriter`]s have been dropped, instead of returning
+	/// [`ReadlineError::Closed`].
+	///
+	/// This suits programs that only write output during part of their run.
+	/// Whether writers are left can still be checked with
+	/// [`is_closed()`][Readline::is_closed].  The default is `false`.
+	pub fn set_ignore_writer_close(&mut self, ignore: bool) {
+		self.ignore_writer_close = ignore;
+	}
+
+	/// Returns the write counters of the associated [`SharedWriter`]s.  See
+	/// [`SharedWriter::stats()`].
+	pub fn writer_stats(&self) -> WriterStats {
+		self.writers.snapshot()
+	}
+
+	/// Append new history entries to the history file once no entry was added
+	/// for `debounce`, or as soon as `entries` of them are waiting
+	///
+	/// Entries are written while [`readline()`][Readline::readline] runs, and
+	/// an error writing them is returned from it.  This needs a file set with
+	/// [`ReadlineBuilder::history_file()`], which is still rewritten in full
+	/// when the `Readline` is dropped.  Off by default.
+	pub fn set_history_autosave(&mut self, debounce: Duration, entries: usize) {
+		self.autosave = Some((debounce, entries.max(1)));
+	}
+
+	/// Set which duplicate entries the history keeps, by default all but a
+	/// repeat of the newest entry
+	///
+	/// Only affects entries added afterwards.
+	pub fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) {
+		self.history.duplicates = duplicates;
+	}
+
+	/// Set when `!!` and other history designators in the input are replaced
+	/// by history entries, see [`HistoryExpansion`]
+	pub fn set_history_expansion(&mut self, expansion: HistoryExpansion) {
+		lock(&self.screen).line.history_expansion = expansion;
+	}
+
+	/// Set whether lines starting with a space are left out of the history,
+	/// like with bash's `ignorespace`
+	///
+	/// Off by default.
+	pub fn set_history_ignore_space(&mut self, ignore: bool) {
+		self.history.ignore_space = ignore;
+	}
+
+	/// Leave lines for which `ignore` returns true out of the history, e.g.
+	/// commands with passwords in them
+	///
+	/// Lines matching any of the rules added are left out.  A pattern can be
+	/// used through its match function, e.g. `move |line| regex.is_match(line)`.
+	pub fn add_history_ignore(&mut self, ignore: impl Fn(&str) -> bool + Send + 'static) {
+		self.history.ignore_rules.push(Box::new(ignore));
+	}
+
+	/// Replace lines with what `redact` returns for them before they are added
+	/// to the history, e.g. to mask tokens and passwords
+	///
+	/// `redact` returns `None` to keep a line as it is.  The redacted line is
+	/// what is recalled, saved and added to the [`HistoryStore`], and the
+	/// other history rules apply to it.  Lines can be left out entirely with
+	/// [`add_history_ignore()`][Readline::add_history_ignore].
+	pub fn set_history_redaction(
+		&mut self,
+		redact: impl Fn(&str) -> Option<String> + Send + 'static,
+	) {
+		self.history.redact = Some(Box::new(redact));
+	}
+
+	/// Leave lines longer than `max` bytes out of the history, e.g. large
+	/// pasted blobs, or keep lines of any length with `None`
+	///
+	/// Lines of any length are kept by default.
+	pub fn set_max_history_entry_len(&mut self, max: Option<usize>) {
+		self.history.max_entry_len = max;
+	}
+
+	/// Set maximum history length.  The default length is 1000.
+	pub fn set_max_history(&mut self, max_size: usize) {
+		self.history.set_max_size(max_size);
+	}
+
+	/// Set whether the input line should remain on the screen after
+	/// events.
+	///
+	/// If `enter` is true, then when the user presses "Enter", the prompt
+	/// and the text they entered will remain on the screen, and the cursor
+	/// will move to the next line.  If `enter` is false, the prompt &
+	/// input will be erased instead.
+	///
+	/// `control_c` similarly controls the behavior for when the user
+	/// presses Ctrl-C.
+	///
+	/// The default value for both settings is `true`.
+	pub fn should_print_line_on(&mut self, enter: bool, control_c: bool) {
+		let line = &mut lock(&self.screen).line;
+		line.should_print_line_on_enter = enter;
+		line.should_print_line_on_control_c = control_c;
+	}
+
+	/// Print `prompt`, e.g. `"❯ "`, instead of the full prompt before lines
+	/// left on the screen by
+	/// [`should_print_line_on()`][Readline::should_print_line_on], to keep
+	/// the scrollback compact
+	///
+	/// `None`, the default, prints the prompt as it was shown.
+	pub fn set_transient_prompt(&mut self, prompt: Option<String>) {
+		lock(&self.screen).line.transient_prompt = prompt;
+	}
+
+	/// Set whether ANSI escape sequences (colors, hyperlinks, etc.) should be
+	/// removed from data written to the [`SharedWriter`] before it is output.
+	///
+	/// This is useful for honoring `NO_COLOR` or dumb terminals without
+	/// changing every place that writes colored output.  The prompt is not
+	/// affected.  The default is `false`.
+	pub fn set_strip_ansi(&mut self, strip: bool) {
+		let line = &mut lock(&self.screen).line;
+		match (strip, &line.ansi_stripper) {
+			(true, None) => line.ansi_stripper = Some(AnsiStripper::default()),
+			(false, Some(_)) => line.ansi_stripper = None,
+			_ => {}
+		}
+	}
+
+	/// Flush all writers to terminal and erase the prompt string
+	///
+	/// This is intended for final teardown, so that whatever is printed next
+	/// (e.g. the shell prompt) lands on a clean line.  The prompt is drawn
+	/// again by the next call to [`readline()`][Readline::readline].  To keep
+	/// the prompt on screen, use
+	/// [`flush_and_redraw()`][Readline::flush_and_redraw] instead.
+	///
+	/// This is best-effort: only lines that writers have already sent are
+	/// printed.  Partial lines still buffered in a [`SharedWriter`], or lines
+	/// from writers waiting for room in the channel, may be missed.  Use
+	/// [`flush_async()`][Readline::flush_async] to output everything.
+	pub fn flush(&mut self) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		screen.print_held()?;
+		print_pending(&self.line_receiver, |data| screen.write_output(data))?;
+		let Screen { raw_term, line, .. } = screen;
+		line.clear(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Flush all writers to terminal and redraw the prompt and current input
+	///
+	/// Like [`flush()`][Readline::flush], but leaves the prompt on screen,
+	/// making it suitable for calling at the bottom of a select loop.
+	pub fn flush_and_redraw(&mut self) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		print_pending(&self.line_receiver, |data| screen.write_output(data))?;
+		let Screen { raw_term, line, .. } = screen;
+		line.clear_and_render(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Erase the prompt and current input from the screen
+	///
+	/// The prompt is drawn again by the next call to
+	/// [`readline()`][Readline::readline] that handles output or a key press.
+	pub fn erase_prompt(&mut self) -> Result<(), ReadlineError> {
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		line.clear(raw_term)?;
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Flush all writers to terminal and erase the prompt string, guaranteeing
+	/// that everything written to the associated [`SharedWriter`]s before
+	/// this call has been output.
+	///
+	/// Unlike [`flush()`][Readline::flush], this also prints partial lines
+	/// still buffered inside writers, and keeps receiving until no more
+	/// output arrives, yielding in between so that writers waiting for room in
+	/// the channel can deliver.  Entries still being added to the
+	/// [`HistoryStore`] are waited for first.
+	pub async fn flush_async(&mut self) -> Result<(), ReadlineError> {
+		self.finish_history_write().await?;
+		self.print_all().await?;
+		{
+			let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+			line.clear(raw_term)?;
+			raw_term.flush()?;
+		}
+		if let Some(output_pump) = &mut self.output_pump {
+			output_pump.flush().await?;
+		}
+		Ok(())
+	}
+
+	// Prints output until writers have nothing left, including partial lines
+	async fn print_all(&mut self) -> Result<(), ReadlineError> {
+		loop {
+			// Take partial lines first: everything in the channel is older
+			let partial = self.writers.take_buffers();
+			let mut printed = !partial.is_empty();
+			{
+				let screen = &mut *lock(&self.screen);
+				screen.print_held()?;
+				printed |= print_pending(&self.line_receiver, |data| screen.write_output(data))?;
+				for buf in &partial {
+					screen.write_output(buf)?;
+				}
+				screen.raw_term.flush()?;
+			}
+			if !printed {
+				break;
+			}
+			yield_now().await;
+		}
+		if let Some(output_pump) = &mut self.output_pump {
+			output_pump.flush().await?;
+		}
+		Ok(())
+	}
+
+	/// Print all pending output and restore the terminal
+	///
+	/// The prompt is erased, or left in place together with unfinished input
+	/// if [`should_print_line_on`][Readline::should_print_line_on] enabled
+	/// printing on Ctrl-C.  The cursor is then moved to the start of a fresh
+	/// line, made visible again, and raw mode is disabled.
+	///
+	/// Dropping a `Readline` does the same on a best-effort basis, but can
+	/// neither wait for partial lines still buffered in writers or entries
+	/// being added to the [`HistoryStore`], nor report errors.
+	pub async fn close(mut self) -> Result<(), ReadlineError> {
+		self.finish_history_write().await?;
+		self.print_all().await?;
+		self.restore_terminal()?;
+		if let Some(output_pump) = &mut self.output_pump {
+			output_pump.flush().await?;
+		}
+		Ok(())
+	}
+
+	// Leaves the prompt area and undoes the terminal changes made in new()
+	fn restore_terminal(&mut self) -> Result<(), ReadlineError> {
+		lock(&self.screen).restore(self.raw_mode)
+	}
+
+	/// Returns a future that prints everything written to the associated
+	/// [`SharedWriter`]s as soon as it arrives, even while
+	/// [`readline()`][Readline::readline] isn't being polled.
+	///
+	/// Normally, output is only printed while `readline()` is running, so
+	/// output written while the program is busy elsewhere queues up (and
+	/// writers may fail once the channel is full).  Spawn the returned future
+	/// on any executor to take over printing, e.g.
+	/// `async_std::task::spawn(rl.flusher())`.  It stops when the `Readline`
+	/// is dropped or `flusher()` is called again.
+	pub fn flusher(&mut self) -> impl Future<Output = ()> + Send + 'static
+	where
+		W: Send + 'static,
+	{
+		let receiver = self.line_receiver.clone();
+		let screen = self.screen.clone();
+		let (future, handle) = abortable(async move {
+			let _ = print_output(&receiver, &screen).await;
+		});
+		if let Some(previous) = self.flusher.replace(handle) {
+			previous.abort();
+		}
+		future.map(|_| ())
+	}
+
+	/// Spawn [`flusher()`][Readline::flusher] as a tokio task.  Must be
+	/// called from within a tokio runtime.
+	#[cfg(feature = "tokio")]
+	pub fn spawn_flusher(&mut self)
+	where
+		W: Send + 'static,
+	{
+		tokio::spawn(self.flusher());
+	}
+
+	/// Polling function for readline, manages all input and output.
+	/// Returns either an Readline Event or an Error
+	///
+	/// # Cancel safety
+	///
+	/// This method is cancel safe, so it can be used in `select!` along with
+	/// other futures.  Key presses and output are handled completely each
+	/// time the future is polled, and an event is returned from the same poll
+	/// that produced it, so dropping the future never loses an event or
+	/// output, and input typed so far is kept for the next call.
+	///
+	/// If the prompt was erased in the meantime, e.g. with
+	/// [`flush()`][Readline::flush] before writing to the terminal directly,
+	/// it is drawn again as soon as `readline()` is polled.
+	pub async fn readline(&mut self) -> Result<ReadlineEvent, ReadlineError> {
+		future::poll_fn(|cx| self.poll_readline(cx)).await
+	}
+
+	/// Poll for the next event, like [`readline()`][Readline::readline]
+	///
+	/// This handles input and prints output that is ready, then returns
+	/// `Poll::Pending` and arranges for `cx` to be woken once there is more
+	/// to do.  Input typed so far is kept between calls, so this can be
+	/// mixed freely with `readline()`, e.g. in a hand-written event loop.
+	pub fn poll_readline(
+		&mut self,
+		cx: &mut Context<'_>,
+	) -> Poll<Result<ReadlineEvent, ReadlineError>> {
+		loop {
+			// `line_sender` keeps the channel open, so closing is detected by
+			// counting writers instead
+			if !self.ignore_writer_close && self.is_closed() && self.line_receiver.is_empty() {
+				return Poll::Ready(Err(ReadlineError::Closed));
+			}
+			if let Poll::Ready(received) = self.input.poll_next(cx) {
+				match self.handle_input(received) {
+					Ok(None) => continue,
+					Ok(Some(event)) => return Poll::Ready(Ok(event)),
+					Err(e) => return Poll::Ready(Err(e)),
+				}
+			}
+			let hold = self.flusher.is_some()
+				|| self.halves.is_some()
+				|| lock(&self.screen).holding_full();
+			if !hold {
+				if let Poll::Ready(buf) = self.line_receiver.poll_recv_ref(cx) {
+					let Some(buf) = buf else {
+						return Poll::Ready(Err(ReadlineError::Closed));
+					};
+					// Print everything that's available with a single redraw
+					let mut batch = buf.to_vec();
+					drop(buf);
+					drain_batch(&self.line_receiver, &mut batch);
+					// Empty buffers are sent when the last writer is dropped
+					if !batch.is_empty() {
+						lock(&self.screen).print(&batch)?;
+					}
+					continue;
+				}
+			}
+			if let Some(output_pump) = &mut self.output_pump {
+				if let Poll::Ready(e) = output_pump.poll_run(cx) {
+					return Poll::Ready(Err(e.into()));
+				}
+			}
+			if self.poll_completion(cx)?.is_ready() {
+				continue;
+			}
+			self.poll_history_write(cx)?;
+			if self.poll_timer(cx).is_ready() {
+				self.handle_timers()?;
+				continue;
+			}
+			return Poll::Pending;
+		}
+	}
+
+	/// Like [`readline()`][Readline::readline], but gives up after `timeout`
+	/// and returns `Ok(None)`
+	///
+	/// Output is printed while waiting as usual, and the input typed so far
+	/// stays on screen for the next call.  The timeout either runs from the
+	/// start of the call ([`ReadlineTimeout::After`]) or from the last key
+	/// press ([`ReadlineTimeout::Idle`]).  This is cancel safe, like
+	/// `readline()`.
+	pub async fn readline_with_timeout(
+		&mut self,
+		timeout: ReadlineTimeout,
+	) -> Result<Option<ReadlineEvent>, ReadlineError> {
+		let start = Instant::now();
+		let mut timer: Option<(Instant, Delay)> = None;
+		future::poll_fn(|cx| loop {
+			if let Poll::Ready(result) = self.poll_readline(cx) {
+				return Poll::Ready(result.map(Some));
+			}
+			let deadline = match timeout {
+				ReadlineTimeout::After(duration) => start + duration,
+				ReadlineTimeout::Idle(duration) => start.max(self.last_input) + duration,
+			};
+			let now = Instant::now();
+			if deadline <= now {
+				return Poll::Ready(Ok(None));
+			}
+			match &mut timer {
+				Some((timer_deadline, _)) if *timer_deadline == deadline => {}
+				timer => *timer = Some((deadline, Delay::new(deadline - now))),
+			}
+			let (_, delay) = timer.as_mut().unwrap();
+			ready!(delay.poll_unpin(cx));
+		})
+		.await
+	}
+
+	/// Handle input and print output that is ready without waiting, returning
+	/// an event if one completed
+	///
+	/// See [`poll_readline()`][Readline::poll_readline].
+	pub fn try_readline(&mut self) -> Option<Result<ReadlineEvent, ReadlineError>> {
+		match self.poll_readline(&mut Context::from_waker(
+			futures_util::task::noop_waker_ref(),
+		)) {
+			Poll::Ready(result) => Some(result),
+			Poll::Pending => None,
+		}
+	}
+
+	// Handles a key press or line of input, returning an event if one completed
+	fn handle_input(&mut self, received: Received) -> Result<Option<ReadlineEvent>, ReadlineError> {
+		self.last_input = Instant::now();
+		let event = match received {
+			Received::Line(line) => {
+				return match line? {
+					Some(line) => Ok(Some(ReadlineEvent::Line(line))),
+					None => Ok(Some(ReadlineEvent::Eof)),
+				};
+			}
+			Received::Event(event) => event?,
+		};
+		#[cfg(all(unix, feature = "signals"))]
+		if self.raw_mode && self.suspend_on_ctrl_z && signals::is_suspend_key(&event) {
+			self.suspend()?;
+			return Ok(None);
+		}
+		let screen = &mut *lock(&self.screen);
+		let was_frozen = screen.line.is_frozen();
+		let result = screen
+			.line
+			.handle_event(event, &mut screen.raw_term, &mut self.history);
+		if was_frozen && !screen.line.is_frozen() && !screen.output_paused {
+			// Released with Ctrl-Q
+			screen.print_held()?;
+		}
+		if screen.line.is_flashing() && self.flash_deadline.is_none() {
+			self.flash_deadline = Some(Instant::now() + FLASH_DURATION);
+		}
+		let event = result?;
+		screen.raw_term.flush()?;
+		Ok(event)
+	}
+
+	// Applies completions from an async completer once they arrive
+	fn poll_completion(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ReadlineError>> {
+		let screen = &mut *lock(&self.screen);
+		ready!(screen.line.poll_completion(cx, &mut screen.raw_term))?;
+		if screen.line.is_flashing() && self.flash_deadline.is_none() {
+			self.flash_deadline = Some(Instant::now() + FLASH_DURATION);
+		}
+		screen.raw_term.flush()?;
+		Poll::Ready(Ok(()))
+	}
+
+	// Completes when the earliest of the timers is due
+	fn poll_timer(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+		let redraw_deadline = lock(&self.screen).redraw_deadline();
+		let Some(deadline) = [
+			self.toast_deadline,
+			self.flash_deadline,
+			self.size_poll_deadline,
+			self.autosave_deadline,
+			redraw_deadline,
+		]
+		.into_iter()
+		.flatten()
+		.min() else {
+			self.timer = None;
+			return Poll::Pending;
+		};
+		let now = Instant::now();
+		if deadline <= now {
+			self.timer = None;
+			return Poll::Ready(());
+		}
+		match &mut self.timer {
+			Some((timer_deadline, _)) if *timer_deadline == deadline => {}
+			timer => *timer = Some((deadline, Delay::new(deadline - now))),
+		}
+		let (_, delay) = self.timer.as_mut().unwrap();
+		ready!(delay.poll_unpin(cx));
+		self.timer = None;
+		Poll::Ready(())
+	}
+
+	// Removes toasts and flashes that have expired, draws a throttled redraw,
+	// checks the terminal size if it is polled and autosaves the history
+	fn handle_timers(&mut self) -> Result<(), ReadlineError> {
+		let now = Instant::now();
+		if self
+			.autosave_deadline
+			.is_some_and(|deadline| deadline <= now)
+		{
+			self.autosave_deadline = None;
+			if let Some(path) = &self.history_file {
+				self.history.append_unsaved(path)?;
+			}
+		}
+		let Screen { raw_term, line, .. } = &mut *lock(&self.screen);
+		if self.toast_deadline.is_some_and(|deadline| deadline <= now) {
+			self.toast_deadline = None;
+			line.set_toast(None, raw_term)?;
+		}
+		if self.flash_deadline.is_some_and(|deadline| deadline <= now) {
+			self.flash_deadline = None;
+			line.stop_flashing(raw_term)?;
+		}
+		if line
+			.redraw_deadline()
+			.is_some_and(|deadline| deadline <= now)
+		{
+			line.redraw(raw_term)?;
+		}
+		if self
+			.size_poll_deadline
+			.is_some_and(|deadline| deadline <= now)
+		{
+			self.size_poll_deadline = Some(now + SIZE_POLL_INTERVAL);
+			let size = self.current_size(line);
+			if size != line.term_size() {
+				line.resize(size, raw_term)?;
+			}
+		}
+		raw_term.flush()?;
+		Ok(())
+	}
+
+	/// Turn this `Readline` into a stream of the events returned by
+	/// [`readline()`][Readline::readline]
+	///
+	/// Output from [`SharedWriter`]s keeps being printed while the stream is
+	/// polled, just like while `readline()` runs.  The stream ends after the
+	/// first error, e.g. [`ReadlineError::Closed`] or a failure to read the
+	/// terminal.
+	pub fn into_stream(
+		self,
+	) -> impl Stream<Item = Result<ReadlineEvent, ReadlineError>> + Unpin + 'static
+	where
+		W: 'static,
+	{
+		Box::pin(futures_util::stream::unfold(
+			Some(self),
+			|readline| async move {
+				let mut readline = readline?;
+				let event = readline.readline().await;
+				let failed = event.is_err();
+				Some((event, (!failed).then_some(readline)))
+			},
+		))
+	}
+
+	/// Add a line to the input history, entered now
+	pub fn add_history_entry(&mut self, entry: String) -> Option<()> {
+		let added = self.history.add_entry(HistoryEntry {
+			time: Some(SystemTime::now()),
+			..HistoryEntry::new(entry)
+		});
+		if let Some(store) = self.history_store.as_mut().filter(|_| added) {
+			// The line as kept, possibly redacted
+			let entry = self.history.get_entries().back().unwrap().line.clone();
+			let write = store.add(entry);
+			self.history_write = Some(match self.history_write.take() {
+				Some(previous) => async move {
+					previous.await?;
+					write.await
+				}
+				.boxed(),
+				None => write,
+			});
+		}
+		if let (Some((debounce, entries)), Some(_)) = (self.autosave, &self.history_file) {
+			let unsaved = self.history.unsaved();
+			if unsaved >= entries {
+				self.autosave_deadline = Some(Instant::now());
+			} else if unsaved > 0 {
+				self.autosave_deadline = Some(Instant::now() + debounce);
+			}
+		}
+		// Return value to keep compatibility with previous API.
+		Some(())
+	}
+
+	/// Read-only access to the history, e.g. to list or search it for a
+	/// `history` command
+	pub fn history(&self) -> HistoryView<'_> {
+		self.history.view()
+	}
+
+	/// Returns the entries of the history in the order they were added in.
+	pub fn get_history_entries(&self) -> &VecDeque<HistoryEntry> {
+		self.history.get_entries()
+	}
+
+	/// Returns the newest history entry, e.g. to record the exit status of
+	/// the command once it has run
+	pub fn last_history_entry_mut(&mut self) -> Option<&mut HistoryEntry> {
+		self.history.last_entry_mut()
+	}
+
+	/// Replaces the current history.
+	pub fn set_history_entries(
+		&mut self,
+		entries: impl IntoIterator<Item = impl Into<HistoryEntry>>,
+	) {
+		self.history.set_entries(entries);
+	}
+
+	/// Add the entries of a history file of another shell, e.g.
+	/// `~/.bash_history`, as the newest entries
+	///
+	/// Entries go through the same rules as ones added with
+	/// [`add_history_entry()`][Readline::add_history_entry], but keep the
+	/// times from the file.
+	pub fn import_history(
+		&mut self,
+		path: impl AsRef<Path>,
+		format: HistoryFormat,
+	) -> Result<(), ReadlineError> {
+		let data = std::fs::read(path)?;
+		for entry in format.parse(&data) {
+			self.history.add_entry(entry);
+		}
+		Ok(())
+	}
+
+	/// Write the history to the file at `path` in the format of another
+	/// shell, e.g. to read it with standard tools
+	pub fn export_history(
+		&self,
+		path: impl AsRef<Path>,
+		format: HistoryFormat,
+	) -> Result<(), ReadlineError> {
+		std::fs::write(path, format.format(self.history.get_entries()))?;
+		Ok(())
+	}
+
+	/// Keep the history in `store`, replacing the history in memory with its
+	/// newest entries
+	///
+	/// Entries passed to [`add_history_entry()`][Readline::add_history_entry]
+	/// are added to the store while [`readline()`][Readline::readline] runs,
+	/// and an error adding them is returned from it.
+	/// [`flush_async()`][Readline::flush_async] and
+	/// [`close()`][Readline::close] wait for them.
+	pub async fn set_history_store(
+		&mut self,
+		mut store: impl HistoryStore + 'static,
+	) -> Result<(), ReadlineError> {
+		self.finish_history_write().await?;
+		let entries = store.load(self.history.max_size()).await?;
+		self.history.set_entries(entries);
+		self.history_store = Some(Box::new(store));
+		Ok(())
+	}
+
+	/// Up to `limit` history entries that contain `text`, newest first
+	///
+	/// The [`HistoryStore`] is searched if one is set, otherwise the history
+	/// in memory.
+	pub async fn search_history(
+		&mut self,
+		text: &str,
+		limit: usize,
+	) -> Result<Vec<String>, ReadlineError> {
+		self.finish_history_write().await?;
+		if let Some(store) = &mut self.history_store {
+			return Ok(store.search(text, limit).await?);
+		}
+		Ok(self
+			.history
+			.get_entries()
+			.iter()
+			.rev()
+			.filter(|entry| entry.line.contains(text))
+			.take(limit)
+			.map(|entry| entry.line.clone())
+			.collect())
+	}
+
+	// Waits for the entries being added to the history store
+	async fn finish_history_write(&mut self) -> Result<(), ReadlineError> {
+		if let Some(write) = self.history_write.take() {
+			write.await?;
+		}
+		Ok(())
+	}
+
+	fn poll_history_write(&mut self, cx: &mut Context<'_>) -> Result<(), ReadlineError> {
+		if let Some(write) = &mut self.history_write {
+			if let Poll::Ready(result) = write.poll_unpin(cx) {
+				self.history_write = None;
+				result?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Clears the current history.
+	pub fn clear_history(&mut self) {
+		self.set_history_entries(Vec::<HistoryEntry>::new());
+	}
+
+	/// Replaces the current history with the entries in the file at `path`,
+	/// see [`save_history()`][Readline::save_history] for the format
+	pub fn load_history(&mut self, path: impl AsRef<Path>) -> Result<(), ReadlineError> {
+		self.history.load(path.as_ref())?;
+		Ok(())
+	}
+
+	/// Writes the history to the file at `path`
+	///
+	/// The file is UTF-8 text with one entry per line, oldest first.  Within
+	/// entries, backslashes are written as `\\`, newlines as `\n`, carriage
+	/// returns as `\r` and a `#` at the start as `\#`.  Any other backslash is
+	/// read back as it is.
+	///
+	/// The time, exit status and tag of a [`HistoryEntry`] are written on a
+	/// line before it, starting with `#` and followed by the space separated
+	/// fields that are set: `time=` and the Unix time in seconds, `status=`
+	/// and the exit status, and last `tag=` and the tag up to the end of the
+	/// line, escaped like entries.  Unknown fields are ignored when loading.
+	pub fn save_history(&mut self, path: impl AsRef<Path>) -> Result<(), ReadlineError> {
+		self.history.save(path.as_ref())?;
+		Ok(())
+	}
+}
+
+impl<W: Write> Drop for Readline<W> {
+	fn drop(&mut self) {
+		if let Some(path) = &self.history_file {
+			let _ = self.history.save(path);
+		}
+		{
+			// Wait for the flusher to finish printing before stopping it
+			let mut screen = lock(&self.screen);
+			if let Some(flusher) = self.flusher.take() {
+				flusher.abort();
+			}
+			// Print whatever is left so final messages aren't lost
+			let _ = screen.print_held();
+			let _ = print_pending(&self.line_receiver, |data| screen.write_output(data));
+		}
+		if let Some(halves) = self.halves.take() {
+			if Arc::into_inner(halves).is_none() {
+				// The printer is still in use
+				return;
+			}
+		}
+		let _ = self.restore_terminal();
+	}
+}
+
+#[cfg(all(test, feature = "tokio"))]
+#[tokio::test]
+async fn test_tokio_copy() {
+	use crossterm::event::KeyCode;
+	use virtual_terminal::SharedTerminal;
+
+	// Enter is pressed a while after everything is copied
+	let (copied, copied_rx) = tokio::sync::oneshot::channel();
+	let enter = futures_util::stream::once(async {
+		copied_rx.await.unwrap();
+		tokio::time::sleep(Duration::from_millis(20)).await;
+		key(KeyCode::Enter)
+	});
+	let events = futures_util::stream::iter([key(KeyCode::Char('x'))]).chain(enter);
+	let term = SharedTerminal::new(20, 5);
+	let (mut rl, mut writer) = scripted(Box::pin(events), term.clone());
+	// Only borrows the writer, dropping the last one would close the Readline
+	let copy = async {
+		let mut input = &b"foo\nbar\n"[..];
+		tokio::io::copy(&mut input, &mut writer).await.unwrap();
+		copied.send(()).unwrap();
+	};
+	let (event, ()) = tokio::join!(rl.readline(), copy);
+	assert_eq!(event.unwrap(), ReadlineEvent::Line("x".into()));
+	assert_eq!(term.screen_contents()[..3], ["foo", "bar", "> x"]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_writer_stats() {
+	let (sender, receiver) = thingbuf::mpsc::channel(1);
+	let mut writer = SharedWriter::new(sender, Default::default());
+	let mut clone = writer.clone();
+	writeln!(writer, "foo\nbar").unwrap();
+	assert_eq!(
+		writeln!(clone, "baz").unwrap_err().kind(),
+		io::ErrorKind::WouldBlock
+	);
+	drop(receiver);
+	assert!(writeln!(clone, "qux").is_err());
+	assert_eq!(
+		writer.stats(),
+		WriterStats {
+			lines_sent: 2,
+			bytes_sent: 8,
+			writes_failed_full: 1,
+			writes_failed_closed: 1,
+		}
+	);
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_new_writer_after_drop() {
+	use crossterm::event::KeyCode;
+
+	// Keys only arrive once they are sent, after the writers are gone
+	let (keys, events) = async_std::channel::unbounded();
+	let output = TestOutput::default();
+	let (mut rl, writer) = scripted(events, output.clone());
+	let clone = writer.clone();
+	assert_eq!(rl.writer_count(), 2);
+	drop(writer);
+	drop(clone);
+	assert!(rl.is_closed());
+	assert!(matches!(rl.readline().await, Err(ReadlineError::Closed)));
+
+	let mut writer = rl.new_writer();
+	assert!(!rl.is_closed());
+	writeln!(writer, "still here").unwrap();
+	rl.flush_and_redraw().unwrap();
+	keys.send(key(KeyCode::Char('a'))).await.unwrap();
+	keys.send(key(KeyCode::Enter)).await.unwrap();
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("a".into())
+	);
+	let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	let (_, prompt) = output.split_once("still here").unwrap();
+	assert!(prompt.contains("> a"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_take_partial_buffers() {
+	let (sender, receiver) = thingbuf::mpsc::channel(500);
+	let writers = Arc::new(Writers::default());
+	let mut writer = SharedWriter::new(sender, writers.clone());
+	write!(writer, "foo\nbar").unwrap();
+	assert_eq!(writers.take_buffers(), [b"foo\nbar".to_vec()]);
+	assert!(writers.take_buffers().is_empty());
+	writeln!(writer, "baz").unwrap();
+	assert_eq!(&*receiver.try_recv_ref().unwrap(), b"baz\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_print_pending_on_exit() {
+	let (sender, receiver) = thingbuf::mpsc::channel(500);
+	let mut writer = SharedWriter::new(sender, Default::default());
+	writeln!(writer, "goodbye").unwrap();
+	drop(writer);
+	let mut line = LineState::new("> ".into(), (80, 24));
+	let mut term = Vec::new();
+	let mut print = |data: &[u8]| line.print_data(data, &mut term);
+	assert!(print_pending(&receiver, &mut print).unwrap());
+	assert!(!print_pending(&receiver, &mut print).unwrap());
+	assert!(String::from_utf8(term).unwrap().contains("goodbye"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_drain_batch() {
+	let (sender, receiver) = thingbuf::mpsc::channel(500);
+	let mut writer = SharedWriter::new(sender, Default::default());
+	for i in 0..3 {
+		writeln!(writer, "line {i}").unwrap();
+	}
+	let mut batch = Vec::new();
+	drain_batch(&receiver, &mut batch);
+	assert_eq!(batch, b"line 0\nline 1\nline 2\n");
+
+	// Batches are bounded
+	let long = "x".repeat(MAX_BATCH_BYTES / 2);
+	for _ in 0..3 {
+		writeln!(writer, "{long}").unwrap();
+	}
+	let mut batch = Vec::new();
+	drain_batch(&receiver, &mut batch);
+	assert_eq!(batch.len(), (long.len() + 1) * 2);
+	assert!(receiver.try_recv_ref().is_ok());
+}
+
+#[cfg(test)]
+#[test]
+fn test_default_size() {
+	let env = |columns: &'static str, lines: &'static str| {
+		move |name: &str| match name {
+			"COLUMNS" => Some(columns.to_string()),
+			"LINES" => Some(lines.to_string()),
+			_ => None,
+		}
+	};
+	assert_eq!(default_size(env("100", "0")), (100, 24));
+	assert_eq!(default_size(env("wide", "50")), (80, 50));
+	assert_eq!(default_size(|_| None), (80, 24));
+}
+
+// Output that can still be inspected after being moved into a `Readline`
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct TestOutput(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for TestOutput {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+// A key press without modifiers, as an event source yields it
+#[cfg(test)]
+fn key(code: crossterm::event::KeyCode) -> io::Result<Event> {
+	Ok(Event::Key(KeyEvent::from(code)))
+}
+
+// A `Readline` reading key presses from `events` and drawing to `output`
+#[cfg(test)]
+fn scripted<W: Write + Send + 'static>(
+	events: impl Stream<Item = io::Result<Event>> + Unpin + Send + 'static,
+	output: W,
+) -> (Readline<W>, SharedWriter) {
+	ReadlineBuilder::new("> ".into())
+		.event_source(events)
+		.build_with_output(output)
+		.unwrap()
+}
+
+#[cfg(test)]
+#[test]
+fn test_plain_output() {
+	let output = TestOutput::default();
+	let (mut rl, mut writer) = ReadlineBuilder::new("> ".into())
+		.force_interactive(false)
+		.build_with_output(output.clone())
+		.unwrap();
+	assert!(!rl.is_interactive());
+	writeln!(writer, "\x1b[1mhello\x1b[0m").unwrap();
+	rl.flush().unwrap();
+	drop(rl);
+	// Output goes through untouched, without the prompt or cursor movement
+	assert_eq!(*output.0.lock().unwrap(), b"\x1b[1mhello\x1b[0m\n");
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_streams() {
+	let output = TestOutput::default();
+	let (mut rl, _writer) = Readline::with_streams(
+		"> ".into(),
+		futures_util::io::Cursor::new(b"hi\x1b[Do\r".to_vec()),
+		futures_util::io::AllowStdIo::new(output.clone()),
+		(20, 5),
+	)
+	.unwrap();
+	assert!(matches!(rl.readline().await.unwrap(), ReadlineEvent::Line(line) if line == "hoi"));
+	assert!(matches!(rl.readline().await.unwrap(), ReadlineEvent::Eof));
+	rl.close().await.unwrap();
+	let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	assert!(output.starts_with("> "));
+	assert!(output.contains("hi"));
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_streams_alternate_screen() {
+	let session = || {
+		let output = TestOutput::default();
+		let (rl, writer) = Readline::with_streams(
+			"> ".into(),
+			futures_util::io::Cursor::new(Vec::new()),
+			futures_util::io::AllowStdIo::new(output.clone()),
+			(20, 5),
+		)
+		.unwrap();
+		(rl, writer, output)
+	};
+	let contents =
+		|output: &TestOutput| String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	let (mut first, _first_writer, first_output) = session();
+	let (mut second, _second_writer, second_output) = session();
+
+	// Each session switches its own peer's screen, the process's is untouched
+	first.enter_alternate_screen().unwrap();
+	second.enter_alternate_screen().unwrap();
+	#[cfg(all(unix, feature = "signals"))]
+	assert!(!signals::in_alternate_screen());
+	first.leave_alternate_screen().unwrap();
+	first.flush_async().await.unwrap();
+	second.flush_async().await.unwrap();
+	assert!(contents(&first_output).contains("\x1b[?1049l"));
+	assert!(contents(&second_output).contains("\x1b[?1049h"));
+	assert!(!contents(&second_output).contains("\x1b[?1049l"));
+
+	// The peer's size isn't replaced by the size of the process's terminal
+	second.size_poll_deadline = Some(Instant::now());
+	second.handle_timers().unwrap();
+	assert_eq!(lock(&second.screen).line.term_size(), (20, 5));
+	second.close().await.unwrap();
+	assert!(contents(&second_output).contains("\x1b[?1049l"));
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_event_source() {
+	use crossterm::event::{KeyCode, KeyModifiers};
+
+	let mut events: Vec<_> = "hello".chars().map(|c| key(KeyCode::Char(c))).collect();
+	events.push(key(KeyCode::Enter));
+	events.push(Ok(Event::Key(KeyEvent::new(
+		KeyCode::Char('c'),
+		KeyModifiers::CONTROL,
+	))));
+	let output = TestOutput::default();
+	let (mut rl, _writer) = scripted(futures_util::stream::iter(events), output.clone());
+	assert!(rl.is_interactive());
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("hello".into())
+	);
+	assert_eq!(rl.readline().await.unwrap(), ReadlineEvent::Interrupted);
+	assert_eq!(rl.readline().await.unwrap(), ReadlineEvent::Eof);
+	drop(rl);
+	let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	assert!(output.starts_with("> "));
+	assert!(output.contains("hello"));
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_event_source_plain() {
+	use crossterm::event::KeyCode;
+
+	let mut events: Vec<_> = "hi".chars().map(|c| key(KeyCode::Char(c))).collect();
+	events.push(key(KeyCode::Enter));
+	let output = TestOutput::default();
+	let (mut rl, mut writer) = ReadlineBuilder::new("> ".into())
+		.event_source(futures_util::stream::iter(events))
+		.force_interactive(false)
+		.build_with_output(output.clone())
+		.unwrap();
+	assert!(!rl.is_interactive());
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("hi".into())
+	);
+	writeln!(writer, "done").unwrap();
+	rl.flush_async().await.unwrap();
+	drop(rl);
+	let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	assert_eq!(output, "done\n");
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_into_stream() {
+	use crossterm::event::KeyCode;
+
+	let events = vec![key(KeyCode::Char('a')), key(KeyCode::Enter)];
+	let events = futures_util::stream::iter(events).chain(futures_util::stream::pending());
+	let (rl, writer) = scripted(events, TestOutput::default());
+	let mut stream = rl.into_stream();
+	assert_eq!(
+		stream.next().await.unwrap().unwrap(),
+		ReadlineEvent::Line("a".into())
+	);
+	// Closing the last writer ends the stream
+	drop(writer);
+	assert!(matches!(
+		stream.next().await,
+		Some(Err(ReadlineError::Closed))
+	));
+	assert!(stream.next().await.is_none());
+
+	// So does an error reading events
+	let events = [Err(io::Error::other("unplugged"))];
+	let events = futures_util::stream::iter(events).chain(futures_util::stream::pending());
+	let (rl, _writer) = scripted(events, TestOutput::default());
+	let mut stream = rl.into_stream();
+	assert!(matches!(
+		stream.next().await,
+		Some(Err(ReadlineError::IO(_)))
+	));
+	assert!(stream.next().await.is_none());
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_try_readline() {
+	use crossterm::event::KeyCode;
+
+	let mut events = VecDeque::from([
+		key(KeyCode::Char('a')),
+		key(KeyCode::Char('b')),
+		key(KeyCode::Enter),
+	]);
+	// Each event only becomes ready on the poll after the one that found it
+	// missing
+	let mut ready = false;
+	let events = futures_util::stream::poll_fn(move |cx| {
+		ready = !ready;
+		if ready {
+			if let Some(event) = events.pop_front() {
+				return Poll::Ready(Some(event));
+			}
+		}
+		cx.waker().wake_by_ref();
+		Poll::Pending
+	});
+	let (mut rl, _writer) = scripted(events, TestOutput::default());
+	assert!(rl.try_readline().is_none());
+	assert!(rl.try_readline().is_none());
+	// The input typed so far is kept for `readline()`
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("ab".into())
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_cancel_safety() {
+	use crossterm::event::KeyCode;
+
+	let mut events = VecDeque::new();
+	for i in 0..50 {
+		events.extend(i.to_string().chars().map(|c| key(KeyCode::Char(c))));
+		events.push_back(key(KeyCode::Enter));
+	}
+	// Hand out a single event per poll
+	let mut ready = false;
+	let events = futures_util::stream::poll_fn(move |_| {
+		ready = !ready;
+		match ready {
+			true => events
+				.pop_front()
+				.map_or(Poll::Pending, |event| Poll::Ready(Some(event))),
+			false => Poll::Pending,
+		}
+	});
+	let output = TestOutput::default();
+	let (mut rl, mut writer) = scripted(events, output.clone());
+	let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+	let mut lines = Vec::new();
+	for i in 0..400 {
+		if i % 3 == 0 {
+			writeln!(writer, "output {i}").unwrap();
+		}
+		// Poll a new future once and drop it, as when another branch of a
+		// `select!` completes first
+		let future = rl.readline();
+		pin_mut!(future);
+		if let Poll::Ready(event) = future.poll(&mut cx) {
+			lines.push(event.unwrap());
+		}
+	}
+	let expected: Vec<_> = (0..50)
+		.map(|i| ReadlineEvent::Line(i.to_string()))
+		.collect();
+	assert_eq!(lines, expected);
+	rl.flush().unwrap();
+	let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	for i in (0..400).step_by(3) {
+		assert_eq!(output.matches(&format!("output {i}\n")).count(), 1);
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_draft_survives_cancellation() {
+	use crossterm::event::KeyCode;
+	use virtual_terminal::SharedTerminal;
+
+	let events = Arc::new(Mutex::new(VecDeque::new()));
+	let source = events.clone();
+	let source = futures_util::stream::poll_fn(move |_| match source.lock().unwrap().pop_front() {
+		Some(event) => Poll::Ready(Some(event)),
+		None => Poll::Pending,
+	});
+	let term = SharedTerminal::new(20, 6);
+	let (mut rl, mut writer) = scripted(source, term.clone());
+	rl.set_term_size(20, 6).unwrap();
+	let type_text = |text: &str| {
+		let mut events = events.lock().unwrap();
+		events.extend(text.chars().map(|c| key(KeyCode::Char(c))));
+	};
+	let mut cx = Context::from_waker(futures_util::task::noop_waker_ref());
+	let mut poll_once = |rl: &mut Readline<SharedTerminal>| {
+		let future = rl.readline();
+		pin_mut!(future);
+		assert!(future.poll(&mut cx).is_pending());
+	};
+
+	type_text("dr");
+	poll_once(&mut rl);
+	writeln!(writer, "output").unwrap();
+	poll_once(&mut rl);
+	assert_eq!(term.screen_contents()[..2], ["output", "> dr"]);
+
+	// The prompt is erased and something else writes to the terminal
+	rl.flush().unwrap();
+	write!(term.clone(), "direct\r\n").unwrap();
+	// The next call draws the prompt again without waiting for a key press
+	poll_once(&mut rl);
+	assert_eq!(term.screen_contents()[..3], ["output", "direct", "> dr"]);
+	type_text("aft");
+	poll_once(&mut rl);
+	assert_eq!(term.screen_contents()[..3], ["output", "direct", "> draft"]);
+	assert_eq!(
+		term.screen_contents()
+			.iter()
+			.filter(|row| row.contains("dr"))
+			.count(),
+		1
+	);
+
+	events.lock().unwrap().push_back(key(KeyCode::Enter));
+	assert_eq!(
+		rl.try_readline().unwrap().unwrap(),
+		ReadlineEvent::Line("draft".into())
+	);
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_readline_with_timeout() {
+	use crossterm::event::KeyCode;
+
+	// Keys are pressed 40ms apart, then Enter after a pause
+	let keys = "abcd".chars().map(|c| (40, KeyCode::Char(c)));
+	let keys = keys.chain([(300, KeyCode::Enter)]);
+	let events = futures_util::stream::iter(keys).then(|(delay, code)| async move {
+		Delay::new(Duration::from_millis(delay)).await;
+		key(code)
+	});
+	let events = Box::pin(events.chain(futures_util::stream::pending()));
+	let (mut rl, _writer) = scripted(events, TestOutput::default());
+	let timeout = Duration::from_millis(100);
+	assert_eq!(
+		rl.readline_with_timeout(ReadlineTimeout::After(timeout))
+			.await
+			.unwrap(),
+		None
+	);
+	// Keeps waiting while keys are pressed
+	let start = Instant::now();
+	assert_eq!(
+		rl.readline_with_timeout(ReadlineTimeout::Idle(timeout))
+			.await
+			.unwrap(),
+		None
+	);
+	assert!(start.elapsed() >= Duration::from_millis(150));
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("abcd".into())
+	);
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_async_completer() {
+	use crossterm::event::KeyCode;
+	use virtual_terminal::SharedTerminal;
+
+	let keys = [
+		(0, KeyCode::Char('h')),
+		(0, KeyCode::Tab),
+		(100, KeyCode::Enter),
+		// Edited before the completion arrives
+		(0, KeyCode::Char('h')),
+		(0, KeyCode::Tab),
+		(0, KeyCode::Char('x')),
+		(100, KeyCode::Enter),
+	];
+	let events = futures_util::stream::iter(keys).then(|(delay, code)| async move {
+		Delay::new(Duration::from_millis(delay)).await;
+		key(code)
+	});
+	let events = Box::pin(events.chain(futures_util::stream::pending()));
+	let term = SharedTerminal::new(20, 4);
+	let (mut rl, mut writer) = scripted(events, term.clone());
+	rl.set_async_completer(|_: &str, _| async {
+		Delay::new(Duration::from_millis(50)).await;
+		(0, vec!["help".into()])
+	});
+
+	// Output is printed while the completion is looked up
+	writeln!(writer, "output").unwrap();
+	let timeout = ReadlineTimeout::After(Duration::from_millis(20));
+	assert_eq!(rl.readline_with_timeout(timeout).await.unwrap(), None);
+	assert_eq!(term.screen_contents()[..2], ["output", "> h"]);
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("help".into())
+	);
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("hx".into())
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_prompt_highlighter() {
+	use std::sync::atomic::{AtomicBool, Ordering};
+
+	let output = TestOutput::default();
+	let (mut rl, _writer) = ReadlineBuilder::new("> ".into())
+		.event_source(futures_util::stream::pending())
+		.color_support(ColorSupport::TrueColor)
+		.build_with_output(output.clone())
+		.unwrap();
+	let failed = Arc::new(AtomicBool::new(false));
+	let state = failed.clone();
+	rl.set_prompt_highlighter(move |prompt: &str| {
+		if state.load(Ordering::Relaxed) {
+			prompt.red().to_string()
+		} else {
+			prompt.to_string()
+		}
+	});
+	failed.store(true, Ordering::Relaxed);
+	rl.flush_and_redraw().unwrap();
+	let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	assert!(output.contains(&"> ".red().to_string()));
+}
+
+#[cfg(test)]
+#[test]
+fn test_theme() {
+	use crossterm::style::ContentStyle;
+
+	let output = TestOutput::default();
+	let theme = Theme {
+		prompt: ContentStyle::new().green(),
+		..Theme::default()
+	};
+	let (mut rl, _writer) = ReadlineBuilder::new("> ".into())
+		.event_source(futures_util::stream::pending())
+		.theme(theme)
+		.color_support(ColorSupport::TrueColor)
+		.build_with_output(output.clone())
+		.unwrap();
+	let drawn = |output: &TestOutput| String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+	assert!(drawn(&output).contains(&"> ".green().to_string()));
+
+	output.0.lock().unwrap().clear();
+	rl.set_theme(Theme {
+		prompt: ContentStyle::new().blue(),
+		..theme
+	})
+	.unwrap();
+	assert!(drawn(&output).contains(&"> ".blue().to_string()));
+
+	// Colors are left out for terminals without them
+	output.0.lock().unwrap().clear();
+	rl.set_color_support(ColorSupport::Basic).unwrap();
+	assert!(drawn(&output).contains("\x1b[94m> \x1b[39m"));
+	output.0.lock().unwrap().clear();
+	rl.set_color_support(ColorSupport::Monochrome).unwrap();
+	assert!(!drawn(&output).contains("m> "));
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_file() {
+	let path = std::env::temp_dir().join(format!("rustyline-async-builder-{}", std::process::id()));
+	let build = || {
+		ReadlineBuilder::new("> ".into())
+			.event_source(futures_util::stream::pending())
+			.history_file(&path)
+			.build_with_output(TestOutput::default())
+			.unwrap()
+	};
+	// A missing file starts an empty history
+	let (mut rl, _writer) = build();
+	assert!(rl.get_history_entries().is_empty());
+	rl.add_history_entry("first".into());
+	rl.add_history_entry("multi\nline".into());
+	drop(rl);
+
+	let (rl, _writer) = build();
+	assert_eq!(rl.get_history_entries(), &["first", "multi\nline"]);
+	drop(rl);
+	std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_history_autosave() {
+	use crossterm::event::KeyCode;
+
+	// Enter after a while, so that the history is saved in the meantime
+	let delayed_enter = || {
+		futures_util::stream::once(Box::pin(async {
+			async_std::task::sleep(Duration::from_millis(100)).await;
+			key(KeyCode::Enter)
+		}))
+	};
+	let path =
+		std::env::temp_dir().join(format!("rustyline-async-autosave-{}", std::process::id()));
+	let (mut rl, _writer) = ReadlineBuilder::new("> ".into())
+		.event_source(delayed_enter().chain(delayed_enter()))
+		.history_file(&path)
+		.build_with_output(TestOutput::default())
+		.unwrap();
+	rl.set_history_autosave(Duration::from_millis(20), 2);
+	// The entries without their times
+	let saved = || {
+		let text = std::fs::read_to_string(&path).unwrap();
+		let lines = text.lines().filter(|line| !line.starts_with('#'));
+		lines.map(String::from).collect::<Vec<_>>()
+	};
+
+	// Saved once enough entries are waiting
+	rl.add_history_entry("one".into());
+	rl.add_history_entry("two".into());
+	rl.readline().await.unwrap();
+	assert_eq!(saved(), ["one", "two"]);
+
+	// Or after the debounce interval
+	rl.add_history_entry("three".into());
+	rl.readline().await.unwrap();
+	assert_eq!(saved(), ["one", "two", "three"]);
+	drop(rl);
+	std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_history_store() {
+	// Entries shared like in a database
+	#[derive(Clone, Default)]
+	struct SharedStore(Arc<Mutex<Vec<String>>>);
+	impl HistoryStore for SharedStore {
+		fn load(&mut self, limit: usize) -> BoxFuture<'static, io::Result<Vec<String>>> {
+			let entries = self.0.lock().unwrap();
+			let skip = entries.len().saturating_sub(limit);
+			future::ready(Ok(entries[skip..].to_vec())).boxed()
+		}
+		fn add(&mut self, entry: String) -> BoxFuture<'static, io::Result<()>> {
+			let entries = self.0.clone();
+			async move {
+				entries.lock().unwrap().push(entry);
+				Ok(())
+			}
+			.boxed()
+		}
+	}
+
+	let store = SharedStore::default();
+	store
+		.0
+		.lock()
+		.unwrap()
+		.extend(["cargo build".into(), "ls".into()]);
+	let (mut rl, _writer) = scripted(futures_util::stream::pending(), TestOutput::default());
+	rl.set_max_history(1);
+	rl.set_history_store(store.clone()).await.unwrap();
+	assert_eq!(rl.get_history_entries(), &["ls"]);
+
+	// Added in the background, and waited for before searching
+	rl.add_history_entry("cargo test".into());
+	rl.add_history_entry("cargo test".into());
+	assert_eq!(store.0.lock().unwrap().len(), 2);
+	assert_eq!(
+		rl.search_history("cargo", 5).await.unwrap(),
+		["cargo test", "cargo build"]
+	);
+	assert_eq!(store.0.lock().unwrap().len(), 3);
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_custom_key_events() {
+	use crossterm::event::{KeyCode, KeyEvent};
+
+	let mut events: Vec<_> = "ls".chars().map(|c| key(KeyCode::Char(c))).collect();
+	events.extend([key(KeyCode::F(1)), key(KeyCode::F(2)), key(KeyCode::Enter)]);
+	let (mut rl, _writer) = scripted(futures_util::stream::iter(events), TestOutput::default());
+	rl.bind_event(KeyEvent::from(KeyCode::F(1)), "help");
+	rl.bind_fn(KeyEvent::from(KeyCode::F(2)), |line| {
+		assert_eq!(line, "ls");
+		None
+	});
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Custom("help".into())
+	);
+	// The input is kept, and F2 doesn't return anything
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("ls".into())
+	);
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_rebind_control_keys() {
+	use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+	let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+	let mut events: Vec<_> = "quit".chars().map(|c| key(KeyCode::Char(c))).collect();
+	events.push(key(KeyCode::Home));
+	events.extend([ctrl('d'), ctrl('c'), ctrl('q')].map(|key| Ok(Event::Key(key))));
+	events.push(key(KeyCode::Enter));
+	let (mut rl, _writer) = scripted(futures_util::stream::iter(events), TestOutput::default());
+	rl.bind(ctrl('d'), EditAction::DeleteForward);
+	assert_eq!(rl.unbind(ctrl('c')), Some(EditAction::Interrupt));
+	rl.bind(ctrl('q'), EditAction::EndOfFile);
+	assert_eq!(rl.readline().await.unwrap(), ReadlineEvent::Eof);
+	assert_eq!(
+		rl.readline().await.unwrap(),
+		ReadlineEvent::Line("uit".into())
+	);
+}
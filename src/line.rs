@@ -1,60 +1,366 @@
-use std::io::{self, Write};
+use std::{
+	cell::{Cell, Ref, RefCell},
+	io::{self, Write},
+	ops::Range,
+	task::{Context, Poll},
+	time::{Duration, Instant},
+};
 
 use crossterm::{
 	cursor,
 	event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+	style::Stylize,
 	terminal::{Clear, ClearType::*},
 	QueueableCommand,
 };
+use futures_util::{ready, FutureExt};
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+#[cfg(test)]
+use crate::virtual_terminal::VirtualTerminal;
+use crate::{
+	ansi::{advance_column, clip_to_width, insert_at_visible},
+	color::ColorSupport,
+	completion::{
+		common_prefix, format_list, Candidate, CompletionMenu, CompletionSource, CompletionStyle,
+		PendingCompletion,
+	},
+	expansion::{expand, HistoryExpansion},
+	highlighter::Highlighter,
+	hinter::AcceptHint,
+	hinter::Hinter,
+	input::InputLine,
+	keymap::{EditAction, EditMode, Keymap, Lookup},
+	kill_ring::KillRing,
+	matching::MatchStrategy,
+	prompt::{Prompt, PromptProvider},
+	search::HistorySearch,
+	sync_update::SyncUpdate,
+	theme::Theme,
+	undo::{Snapshot, UndoStack},
+	validator::{Validator, Validity},
+	vi::{ViMode, ViOutcome, ViState},
+	AnsiStripper, History, ReadlineError, ReadlineEvent,
+};
+
+/// Called for a key bound with [`Readline::bind_fn()`][crate::Readline::bind_fn]
+type KeyHandler = Box<dyn FnMut(&str) -> Option<ReadlineEvent> + Send>;
+
+/// Most times a key is repeated by a numeric argument
+const MAX_ARGUMENT: usize = 1000;
+
+/// Longest wait for the next key of a sequence like Ctrl-X Ctrl-U
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What Ctrl-L and [`Readline::clear()`][crate::Readline::clear] do
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ClearBehavior {
+	/// Only redraw the prompt and input, e.g. to fix a corrupted prompt
+	RedrawPrompt,
+	/// Clear the visible screen, leaving the prompt where it is
+	ClearVisible,
+	/// Clear the visible screen and move the prompt to the top
+	#[default]
+	ClearAll,
+	/// Like `ClearAll`, but also purge the terminal's scrollback
+	ClearWithScrollback,
+}
 
-use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
+/// Feedback given when a key can't do anything, e.g. Backspace on an empty
+/// line or Up at the oldest history entry
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BellStyle {
+	/// No feedback
+	#[default]
+	None,
+	/// Ring the terminal bell
+	Audible,
+	/// Briefly show the prompt in reverse video
+	Visible,
+}
 
-use crate::{History, ReadlineError, ReadlineEvent};
+/// How output is drawn above the prompt
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderMode {
+	/// Erase the prompt, print the output where it was and draw the prompt
+	/// again below.  Works on every terminal.
+	#[default]
+	Redraw,
+	/// Pin the prompt to the bottom rows of the terminal and print output
+	/// into a scroll region (DECSTBM) above it, so the prompt never has to be
+	/// erased.  Avoids flicker under heavy output, but not every terminal
+	/// supports scroll regions well.
+	ScrollRegion,
+}
 
 #[derive(Default)]
 pub struct LineState {
 	// Unicode Line
-	line: String,
+	line: InputLine,
 	// Index of grapheme in line
 	line_cursor_grapheme: usize,
 	// Column of grapheme in line
 	current_column: u16,
 
-	cluster_buffer: String, // buffer for holding partial grapheme clusters as they come in
-
-	prompt: String,
+	prompt: Prompt,
+	// End of the prompt as last measured, for the vi mode shown and the
+	// terminal width then
+	measured_prompt_end: Cell<Option<MeasuredPrompt>>,
+	// Drawn at the start of the rows after newlines in the input
+	continuation_prompt: String,
+	// Width of the continuation prompt as last measured, for the terminal
+	// width then
+	measured_continuation: Cell<Option<(u16, usize)>>,
+	// Where the rows of the input start, for the input last measured
+	layout: RefCell<Layout>,
 	pub should_print_line_on_enter: bool, // After pressing enter, should we print the line just submitted?
 	pub should_print_line_on_control_c: bool, // After pressing control_c should we print the line just cancelled?
+	pub transient_prompt: Option<String>,     // Printed instead of the prompt before those lines
+	pub clear_behavior: ClearBehavior,
+	pub bell_style: BellStyle,
+	pub cursor_style: Option<cursor::SetCursorStyle>, // Applied on every render
+	pub cursor_hidden: bool,
+	pub flow_control: bool, // Whether Ctrl-S and Ctrl-Q freeze and release output
+	pub min_redraw_interval: Duration, // Minimum time between redraws of the prompt after output
+	pub synchronized_output: bool, // Whether redraws are wrapped in synchronized update markers
+	pub incremental_render: bool, // Whether simple edits are drawn without redrawing the whole line
+	// Whether the prompt is on screen, it may be missing while a redraw is deferred
+	prompt_drawn: bool,
+	drawn_line: Option<String>, // Input as currently shown on screen, if known
+	last_render: Option<Instant>,
+	// Set by Ctrl-S, with the number of lines held back since
+	frozen: Option<usize>,
+	// Whether the prompt is shown in reverse video for a visible bell
+	flashing: bool,
 
-	last_line_length: usize,
+	last_line_column: u16, // Where the last, unfinished line of output ends
 	last_line_completed: bool,
+	last_line_escapes: AnsiStripper, // Skips escape sequences when measuring output
 
 	term_size: (u16, u16),
 
-	pub history: History,
+	pub ansi_stripper: Option<AnsiStripper>, // Strips escape sequences from printed data when set
+
+	toast: Option<String>,     // Short-lived message drawn below the input line
+	status_lines: Vec<String>, // Drawn below the input line and toast
+
+	render_mode: RenderMode,
+	// Rows reserved for the prompt below the scroll region, 0 while no scroll region is set up
+	region_rows: u16,
+
+	pub completer: Option<CompletionSource>, // Queried when Tab is pressed
+	pub match_strategy: MatchStrategy,       // Filters what the completer returns
+	pub completion_ignore_case: bool,
+	pending_completion: Option<PendingCompletion>,
+	menu: Option<CompletionMenu>, // Drawn right below the input line
+	pub completion_style: CompletionStyle,
+	// Tab was just pressed without anything to insert, pressing it again lists the candidates
+	tab_listing: bool,
+	pub autosuggest: bool, // Whether history entries are suggested while typing
+	// History entry the input is the start of, the rest is shown after the cursor
+	suggestion: Option<String>,
+	pub hinter: Option<Box<dyn Hinter>>,
+	hint: Option<String>, // Shown after the input when there is no suggestion
+	pub accept_hint_keys: Vec<(KeyEvent, AcceptHint)>,
+	drawn_hint: Option<String>, // Part of the suggestion or hint currently shown
+	pub highlighter: Option<Box<dyn Highlighter>>, // Styles the input when drawn
+	pub prompt_highlighter: Option<Box<dyn Highlighter>>, // Styles the prompt when drawn
+	pub prompt_provider: Option<Box<dyn PromptProvider>>, // Asked for the prompt when drawn
+	pub validator: Option<Box<dyn Validator>>, // Colors the input by whether it is valid
+	pub theme: Theme,
+	pub color_support: ColorSupport, // Styling is downgraded to what the terminal shows
+	pub history_expansion: HistoryExpansion,
+	// Set while searching the history with Ctrl-R or Ctrl-S
+	search: Option<HistorySearch>,
+	pub keymap: Keymap,
+	pub custom_keys: Vec<(KeyEvent, KeyHandler)>, // Take precedence over the keymap
+	kill_ring: KillRing,
+	// Typed with Alt and digits, the next key is repeated this many times
+	argument: Option<usize>,
+	// Start of a sequence of keys bound to an action, and when it was typed
+	pending_keys: Vec<KeyEvent>,
+	pending_since: Option<Instant>,
+	undo: UndoStack,
+	// Typed characters replace the one under the cursor, toggled by Insert
+	overwrite: bool,
+	// Set in vi mode
+	vi: Option<ViState>,
+	// Shown before the prompt in vi insert and normal mode
+	vi_indicators: (String, String),
 }
 
 impl LineState {
-	pub fn new(prompt: String, term_size: (u16, u16)) -> Self {
-		let current_column = prompt.len() as u16;
-		Self {
+	pub fn new(prompt: Prompt, term_size: (u16, u16)) -> Self {
+		let mut state = Self {
 			prompt,
 			last_line_completed: true,
 			term_size,
-			current_column,
 			should_print_line_on_enter: true,
 			should_print_line_on_control_c: true,
+			incremental_render: true,
+			accept_hint_keys: vec![
+				(KeyEvent::from(KeyCode::Right), AcceptHint::All),
+				(KeyEvent::from(KeyCode::End), AcceptHint::All),
+				(
+					KeyEvent::new(KeyCode::Right, KeyModifiers::ALT),
+					AcceptHint::Word,
+				),
+			],
 
 			..Default::default()
+		};
+		state.current_column = state.prompt_end().0 as u16;
+		state
+	}
+	/// Position right after the prompt, which may contain escape sequences
+	/// and newlines, counted like [`position()`][Self::position], and the
+	/// position its last row starts at
+	fn prompt_end(&self) -> (usize, usize) {
+		let mode = self
+			.vi
+			.as_ref()
+			.filter(|_| self.search.is_none())
+			.map(|vi| vi.mode);
+		match self.measured_prompt_end.get() {
+			Some((measured, width, end)) if measured == mode && width == self.term_size.0 => end,
+			_ => {
+				let prompt = format!("{}{}", self.mode_indicator(), self.prompt.text());
+				let end = self.end_of(&prompt);
+				self.measured_prompt_end
+					.set(Some((mode, self.term_size.0, end)));
+				end
+			}
+		}
+	}
+	/// Change the prompt, it's measured again when next needed
+	fn set_prompt(&mut self, prompt: Prompt) {
+		self.prompt = prompt;
+		self.measured_prompt_end.set(None);
+	}
+	/// Position right after `prompt` and the position its last row starts
+	/// at, like [`prompt_end()`][Self::prompt_end] for other prompts
+	fn end_of(&self, prompt: &str) -> (usize, usize) {
+		let mut text = Vec::new();
+		let mut stripper = AnsiStripper::default();
+		stripper.strip(prompt.as_bytes(), &mut text);
+		let width = self.term_size.0 as usize;
+		let text = String::from_utf8_lossy(&text);
+		let mut rows = text.split('\n');
+		let mut pos = rows.next().unwrap_or_default().width();
+		let mut row_start = 0;
+		for row in rows {
+			pos = next_row(pos, row_start, width);
+			row_start = pos;
+			pos += row.width();
+		}
+		(pos, row_start)
+	}
+	/// Columns taken up by the continuation prompt
+	fn continuation_width(&self) -> usize {
+		match self.measured_continuation.get() {
+			Some((width, measured)) if width == self.term_size.0 => measured,
+			_ => {
+				let mut stripper = AnsiStripper::default();
+				let width = self.term_size.0 as usize;
+				let measured =
+					advance_column(&mut stripper, self.continuation_prompt.as_bytes(), 0, width);
+				self.measured_continuation
+					.set(Some((self.term_size.0, measured)));
+				measured
+			}
+		}
+	}
+	/// Position of the grapheme at `index`, counting columns from the start
+	/// of the prompt through the rows the input wraps onto
+	///
+	/// A newline in the input moves on to the start of the next row, after
+	/// the continuation prompt, so the rest of the layout works the same for
+	/// entries spanning several lines.  While a continuation prompt is set,
+	/// graphemes that don't fit on the rest of a row wrap onto the next one
+	/// after it too.
+	///
+	/// Within a row the position follows from the widths the input keeps, so
+	/// only editing the input, the prompt or the terminal width lays the rows
+	/// out again.
+	fn position(&self, index: usize) -> usize {
+		let index = index.min(self.line.count());
+		self.layout().position(&self.line, index)
+	}
+	/// Where the rows of the input start, laid out again if the input, the
+	/// prompt or the terminal width changed since last time
+	fn layout(&self) -> Ref<'_, Layout> {
+		let start = self.prompt_end();
+		let key = (self.line.generation(), start, self.term_size.0);
+		if self.layout.borrow().key != Some(key) {
+			let mut layout = self.lay_out(start);
+			layout.key = Some(key);
+			*self.layout.borrow_mut() = layout;
+		}
+		self.layout.borrow()
+	}
+	/// Rows of the input for input starting at `start`, a position and the
+	/// start of its row
+	fn lay_out(&self, start: (usize, usize)) -> Layout {
+		let width = self.term_size.0 as usize;
+		let continuation = self.continuation_width();
+		let wrapping = !self.continuation_prompt.is_empty();
+		let (mut pos, mut row_start) = start;
+		let mut layout = Layout {
+			rows: vec![(0, pos)],
+			..Default::default()
+		};
+		let count = self.line.count();
+		let mut index = 0;
+		loop {
+			let end = self.line.next_newline(index);
+			// Fill rows up to the newline, a row at a time
+			if wrapping {
+				loop {
+					let room = (row_start + width).saturating_sub(pos);
+					let mut fit = self.line.fit(index, end, room);
+					// Each row keeps at least one grapheme after the
+					// continuation prompt
+					if fit == index && fit < end && pos <= row_start + continuation {
+						fit += 1;
+					}
+					pos += self.line.width_to(fit) - self.line.width_to(index);
+					index = fit;
+					if index == end {
+						// The cursor after the input takes up a column too
+						let cursor_wraps = end == count && pos >= row_start + width;
+						if !cursor_wraps || pos <= row_start + continuation {
+							break;
+						}
+					} else if pos <= row_start + continuation {
+						continue;
+					}
+					row_start += width;
+					pos = row_start + continuation;
+					layout.wraps.push(self.line.offset(index));
+					layout.rows.push((index, pos));
+				}
+			}
+			pos += self.line.width_to(end) - self.line.width_to(index);
+			if end == count {
+				break;
+			}
+			pos = next_row(pos, row_start, width);
+			row_start = pos;
+			pos += continuation;
+			index = end + 1;
+			layout.rows.push((index, pos));
 		}
+		layout
 	}
 	fn line_height(&self, pos: u16) -> u16 {
-		pos / self.term_size.0 // Gets the number of lines wrapped
+		// A position right after the last column is at the start of the next
+		// row, see `render()`
+		pos / self.term_size.0
 	}
 	/// Move from a position on the line to the start
 	fn move_to_beginning(&self, term: &mut impl Write, from: u16) -> io::Result<()> {
-		let move_up = self.line_height(from.saturating_sub(1));
+		let move_up = self.line_height(from);
 		term.queue(cursor::MoveToColumn(0))?;
 		if move_up != 0 {
 			term.queue(cursor::MoveUp(move_up))?;
@@ -63,47 +369,70 @@ impl LineState {
 	}
 	/// Move from the start of the line to some position
 	fn move_from_beginning(&self, term: &mut impl Write, to: u16) -> io::Result<()> {
-		let line_height = self.line_height(to.saturating_sub(1));
+		let line_height = self.line_height(to);
 		let line_remaining_len = to % self.term_size.0; // Get the remaining length
 		if line_height != 0 {
 			term.queue(cursor::MoveDown(line_height))?;
 		}
-		term.queue(cursor::MoveRight(line_remaining_len))?;
+		if line_remaining_len != 0 {
+			term.queue(cursor::MoveRight(line_remaining_len))?;
+		}
 
 		Ok(())
 	}
 	/// Move cursor by one unicode grapheme either left (negative) or right (positive)
 	fn move_cursor(&mut self, change: isize) -> io::Result<()> {
 		if change > 0 {
-			let count = self.line.graphemes(true).count();
+			let count = self.line.count();
 			self.line_cursor_grapheme =
 				usize::min(self.line_cursor_grapheme + change as usize, count);
 		} else {
 			self.line_cursor_grapheme =
 				self.line_cursor_grapheme.saturating_sub((-change) as usize);
 		}
-		let (pos, str) = self.current_grapheme().unwrap_or((0, ""));
-		let pos = pos + str.len();
-		self.current_column =
-			(self.prompt.len() + UnicodeWidthStr::width(&self.line[0..pos])) as u16;
+		self.current_column = self.position(self.line_cursor_grapheme) as u16;
 
 		Ok(())
 	}
 	fn current_grapheme(&self) -> Option<(usize, &str)> {
 		self.line
-			.grapheme_indices(true)
-			.take(self.line_cursor_grapheme)
-			.last()
+			.grapheme(self.line_cursor_grapheme.checked_sub(1)?)
 	}
 	fn next_grapheme(&self) -> Option<(usize, &str)> {
-		let total = self.line.grapheme_indices(true).count();
-		if self.line_cursor_grapheme == total {
-			return None;
-		}
+		self.line.grapheme(self.line_cursor_grapheme)
+	}
+	/// Whether the grapheme at `index` is between words: a space, or with
+	/// `alphanumeric` anything but letters and digits
+	fn is_space(&self, index: usize, alphanumeric: bool) -> bool {
 		self.line
-			.grapheme_indices(true)
-			.take(self.line_cursor_grapheme + 1)
-			.last()
+			.grapheme(index)
+			.is_some_and(|(_, str)| match alphanumeric {
+				true => !str.chars().next().is_some_and(char::is_alphanumeric),
+				false => str == " ",
+			})
+	}
+	/// Index of the grapheme starting the word before `from`
+	fn word_start(&self, from: usize, alphanumeric: bool) -> usize {
+		let mut start = from;
+		while start > 0 && self.is_space(start - 1, alphanumeric) {
+			start -= 1;
+		}
+		while start > 0 && !self.is_space(start - 1, alphanumeric) {
+			start -= 1;
+		}
+		start
+	}
+	/// Index of the grapheme after the word following `from`
+	fn word_end(&self, from: usize, alphanumeric: bool) -> usize {
+		let count = self.line.count();
+		let mut end = from;
+		while end < count && self.is_space(end, alphanumeric) {
+			end += 1;
+		}
+		while end < count && !self.is_space(end, alphanumeric) {
+			end += 1;
+		}
+		end
 	}
 	fn reset_cursor(&self, term: &mut impl Write) -> io::Result<()> {
 		self.move_to_beginning(term, self.current_column)
@@ -112,33 +441,634 @@ impl LineState {
 		self.move_from_beginning(term, self.current_column)
 	}
 	/// Clear current line
-	pub fn clear(&self, term: &mut impl Write) -> io::Result<()> {
-		self.move_to_beginning(term, self.current_column)?;
+	pub fn clear(&mut self, term: &mut impl Write) -> io::Result<()> {
+		self.clear_from(self.current_column, term)
+	}
+	/// Clear current line, with the cursor at column `from`
+	fn clear_from(&mut self, from: u16, term: &mut impl Write) -> io::Result<()> {
+		if self.region_rows != 0 {
+			term.queue(cursor::MoveTo(0, self.prompt_row()))?;
+		} else if self.prompt_drawn {
+			self.move_to_beginning(term, from)?;
+		}
 		term.queue(Clear(FromCursorDown))?;
+		// Drawn again by `redraw()` if nothing else does
+		self.prompt_drawn = false;
+		self.drawn_line = None;
 		Ok(())
 	}
+	/// Show an edit of the input, with the cursor at column `from` before the edit
+	fn update_line(&mut self, from: u16, term: &mut impl Write) -> io::Result<()> {
+		if self.incremental_render && self.render_incremental(from, term)? {
+			return Ok(());
+		}
+		self.clear_from(from, term)?;
+		self.render(term)
+	}
+	/// Draw typing or erasing at the end of a single-row input with as few
+	/// bytes as possible, returns whether that was possible
+	fn render_incremental(&mut self, from: u16, term: &mut impl Write) -> io::Result<bool> {
+		let Some(drawn) = &self.drawn_line else {
+			return Ok(false);
+		};
+		if drawn.contains('\n') || self.line.as_str().contains('\n') {
+			return Ok(false);
+		}
+		let width = self.term_size.0 as usize;
+		let line_len = self.position(self.line.count());
+		let drawn_len = match self.line.as_str().strip_prefix(drawn.as_str()) {
+			Some(added) => line_len - added.width(),
+			None => match drawn.strip_prefix(self.line.as_str()) {
+				Some(removed) => line_len + removed.width(),
+				None => return Ok(false),
+			},
+		};
+		if !self.prompt_drawn
+			|| self.flashing
+			|| self.drawn_hint.is_some()
+			// The prompt may change with every render
+			|| self.prompt_provider.is_some()
+			// Typing can change the style of what is already drawn
+			|| self.highlighter.is_some()
+			|| self.validator.is_some()
+			|| from as usize != drawn_len
+			|| self.current_column as usize != line_len
+			// Stay clear of the last column, where the cursor would wrap
+			|| drawn_len >= width
+			|| line_len >= width
+		{
+			return Ok(false);
+		}
+		if let Some(added) = self.line.as_str().strip_prefix(drawn.as_str()) {
+			term.write_all(added.as_bytes())?;
+		} else {
+			let removed = (drawn_len - line_len) as u16;
+			if removed != 0 {
+				term.queue(cursor::MoveLeft(removed))?;
+			}
+			term.queue(Clear(UntilNewLine))?;
+		}
+		self.drawn_line = Some(self.line.as_str().to_owned());
+		self.last_render = Some(Instant::now());
+		Ok(true)
+	}
+	// Rows taken up by the prompt, input and status lines
+	fn rows(&self) -> u16 {
+		let line_len = self.position(self.line.count());
+		self.line_height(line_len as u16)
+			+ 1 + self
+			.menu
+			.as_ref()
+			.map_or(0, |menu| menu.visible().len() as u16)
+			+ self.frozen.is_some() as u16
+			+ self.toast.is_some() as u16
+			+ self.status_lines.len() as u16
+	}
 	/// Render line
-	pub fn render(&self, term: &mut impl Write) -> io::Result<()> {
-		write!(term, "{}{}", self.prompt, self.line)?;
-		let line_len = self.prompt.len() + UnicodeWidthStr::width(&self.line[..]);
+	pub fn render(&mut self, term: &mut impl Write) -> io::Result<()> {
+		if let Some(provider) = &mut self.prompt_provider {
+			// The search prompt isn't the application's
+			if self.search.is_none() {
+				let prompt = provider.prompt();
+				if prompt != self.prompt {
+					self.set_prompt(prompt);
+				}
+				self.move_cursor(0)?;
+			}
+		}
+		self.prompt_drawn = true;
+		self.drawn_line = Some(self.line.as_str().to_owned());
+		self.last_render = Some(Instant::now());
+		let line_len = self.position(self.line.count());
+		if self.region_rows != 0 {
+			// Input may have wrapped onto more or fewer rows
+			let rows = self.rows();
+			if rows != self.region_rows {
+				self.set_region_rows(rows, term)?;
+			}
+			term.queue(cursor::MoveTo(0, self.prompt_row()))?;
+		}
+		let prompt = self.styled_prompt();
+		let wraps = self.layout().wraps.clone();
+		let line = self.styled_line(&wraps);
+		// Newlines don't return the cursor to the first column in raw mode
+		let prompt = prompt.replace('\n', "\r\n");
+		let line = line.replace('\n', "\r\n");
+		let mut text = match self.flashing {
+			true => format!("{}{line}", prompt.reverse()),
+			false => format!("{prompt}{line}"),
+		};
+		let width = self.term_size.0 as usize;
+		self.drawn_hint = self.visible_hint();
+		if let Some(hint) = &self.drawn_hint {
+			text += &self.theme.hint.apply(hint).to_string();
+		}
+		let colors = self.color_support;
+		write!(term, "{}", colors.downgrade(&text))?;
+		// Whether the last row is empty after a newline
+		let ends_row = match self.line.is_empty() {
+			true => {
+				let (end, row_start) = self.prompt_end();
+				end == row_start
+			}
+			false => self.line.as_str().ends_with('\n') && self.continuation_prompt.is_empty(),
+		};
+		if line_len != 0 && line_len.is_multiple_of(width) && !ends_row {
+			// The cursor stays in the last column until the next character
+			// is printed, move it to the next row so it can be placed there
+			writeln!(term)?;
+		}
+		let mut below = 0;
+		if let Some(menu) = &self.menu {
+			for index in menu.visible() {
+				writeln!(term)?;
+				term.queue(cursor::MoveToColumn(0))?;
+				let row = menu.row(index, width, &self.theme);
+				write!(term, "{}", colors.downgrade(&row))?;
+				below += 1;
+			}
+		}
+		if let Some(pending) = self.frozen {
+			let indicator =
+				format!("-- output paused, {pending} lines pending (Ctrl-Q to resume) --");
+			writeln!(term)?;
+			term.queue(cursor::MoveToColumn(0))?;
+			let indicator = self.theme.indicator.apply(clip_to_width(&indicator, width));
+			write!(term, "{}", colors.downgrade(&indicator.to_string()))?;
+			below += 1;
+		}
+		if let Some(toast) = &self.toast {
+			writeln!(term)?;
+			term.queue(cursor::MoveToColumn(0))?;
+			let toast = self.theme.toast.apply(clip_to_width(toast, width));
+			write!(term, "{}", colors.downgrade(&toast.to_string()))?;
+			below += 1;
+		}
+		for status in &self.status_lines {
+			writeln!(term)?;
+			term.queue(cursor::MoveToColumn(0))?;
+			write!(term, "{}", colors.downgrade(&clip_to_width(status, width)))?;
+			below += 1;
+		}
+		if below != 0 {
+			// Back to the last row of the input
+			term.queue(cursor::MoveUp(below))?;
+		}
 		self.move_to_beginning(term, line_len as u16)?;
 		self.move_from_beginning(term, self.current_column)?;
+		if let Some(style) = self.shown_cursor_style() {
+			term.queue(style)?;
+		}
+		if self.cursor_hidden {
+			term.queue(cursor::Hide)?;
+		}
+		Ok(())
+	}
+	/// Shape of the cursor to set when drawing, if any
+	fn shown_cursor_style(&self) -> Option<cursor::SetCursorStyle> {
+		match self.overwrite {
+			true => Some(cursor::SetCursorStyle::SteadyUnderScore),
+			false => self.cursor_style,
+		}
+	}
+	/// Shown before the prompt for the vi mode, not while searching
+	fn mode_indicator(&self) -> &str {
+		match &self.vi {
+			Some(vi) if self.search.is_none() => match vi.mode {
+				ViMode::Insert => &self.vi_indicators.0,
+				ViMode::Normal => &self.vi_indicators.1,
+			},
+			_ => "",
+		}
+	}
+	/// The prompt as the prompt highlighter styles it, after the vi mode
+	/// indicator
+	fn styled_prompt(&mut self) -> String {
+		let prompt = match &mut self.prompt_highlighter {
+			// The search prompt isn't the application's
+			Some(highlighter) if self.search.is_none() => {
+				highlighter.highlight(&self.prompt.to_string())
+			}
+			_ => self.prompt.render(self.theme.prompt, self.color_support),
+		};
+		format!("{}{prompt}", self.mode_indicator())
+	}
+	/// Remove what `key` was bound to by
+	/// [`Readline::bind_fn()`][crate::Readline::bind_fn]
+	pub fn unbind_custom(&mut self, key: &KeyEvent) {
+		self.custom_keys
+			.retain(|(bound, _)| bound.code != key.code || bound.modifiers != key.modifiers);
+	}
+	/// Switch between emacs and vi keys, starting vi mode in insert mode
+	///
+	/// The prompt may change, clear the input before.
+	pub fn set_edit_mode(&mut self, mode: EditMode) -> io::Result<()> {
+		self.vi = (mode == EditMode::Vi).then(ViState::default);
+		self.move_cursor(0)
+	}
+	#[cfg(feature = "inputrc")]
+	pub fn edit_mode(&self) -> EditMode {
+		match self.vi {
+			Some(_) => EditMode::Vi,
+			None => EditMode::Emacs,
+		}
+	}
+	pub fn set_continuation_prompt(
+		&mut self,
+		prompt: String,
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		self.clear(term)?;
+		self.continuation_prompt = prompt;
+		self.measured_continuation.set(None);
+		*self.layout.get_mut() = Layout::default();
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	pub fn set_vi_indicators(
+		&mut self,
+		indicators: (String, String),
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		self.clear(term)?;
+		self.vi_indicators = indicators;
+		self.measured_prompt_end.set(None);
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	/// The input as the highlighter styles it, or else in the style of the
+	/// theme for its validity, with the continuation prompt after newlines
+	/// and at the byte offsets in `wraps`
+	fn styled_line(&mut self, wraps: &[usize]) -> String {
+		let line = match &mut self.highlighter {
+			Some(highlighter) => {
+				insert_at_visible(&highlighter.highlight(self.line.as_str()), wraps, "\n")
+			}
+			None => {
+				let style = match self
+					.validator
+					.as_mut()
+					.map(|v| v.validate(self.line.as_str()))
+				{
+					None | Some(Validity::Valid) => self.theme.valid,
+					Some(Validity::Invalid) => self.theme.invalid,
+					Some(Validity::Incomplete) => self.theme.incomplete,
+				};
+				// Styled row by row so the continuation prompt keeps its own
+				insert_at_visible(self.line.as_str(), wraps, "\n")
+					.split('\n')
+					.map(|row| style.apply(row).to_string())
+					.collect::<Vec<_>>()
+					.join("\n")
+			}
+		};
+		if self.continuation_prompt.is_empty() {
+			return line;
+		}
+		let continuation = self.theme.prompt.apply(&self.continuation_prompt);
+		line.replace('\n', &format!("\n{continuation}"))
+	}
+	/// The rest of the suggested history entry, while the cursor is at the end
+	/// of the input it continues
+	fn suggestion(&self) -> Option<&str> {
+		let suggestion = self.suggestion.as_deref()?;
+		if self.line.is_empty() || self.line_cursor_grapheme != self.line.count() {
+			return None;
+		}
+		suggestion
+			.strip_prefix(self.line.as_str())
+			.filter(|rest| !rest.is_empty())
+	}
+	/// The part of the suggestion, or else the hint, that fits on the row
+	/// after the input, so that it never wraps
+	fn visible_hint(&self) -> Option<String> {
+		let hint = self.suggestion().or(self.hint.as_deref())?;
+		// Only the rest of the row is used
+		let hint = hint.split('\n').next().unwrap_or_default();
+		let width = self.term_size.0 as usize;
+		let line_len = self.position(self.line.count());
+		let hint = clip_to_width(hint, (width - 1).saturating_sub(line_len % width));
+		(!hint.is_empty()).then_some(hint)
+	}
+	/// Look up the suggestion and hint for the input after an edit or cursor
+	/// movement, and draw them again if that changes what is shown
+	fn update_hints(&mut self, term: &mut impl Write, history: &History) -> io::Result<()> {
+		if self.autosuggest {
+			self.suggestion = history.suggest(self.line.as_str()).map(str::to_owned);
+		}
+		if let Some(hinter) = &mut self.hinter {
+			let pos = self.line.offset(self.line_cursor_grapheme);
+			self.hint = hinter.hint(self.line.as_str(), pos);
+		}
+		if self.prompt_drawn && self.visible_hint() != self.drawn_hint {
+			self.clear_and_render(term)?;
+		}
+		Ok(())
+	}
+	/// Show the cursor again after drawing, unless it was hidden on purpose
+	fn show_cursor(&self, term: &mut impl Write) -> io::Result<()> {
+		if !self.cursor_hidden {
+			term.queue(cursor::Show)?;
+		}
 		Ok(())
 	}
 	/// Clear line and render
-	pub fn clear_and_render(&self, term: &mut impl Write) -> io::Result<()> {
+	pub fn clear_and_render(&mut self, term: &mut impl Write) -> io::Result<()> {
 		self.clear(term)?;
 		self.render(term)?;
 		Ok(())
 	}
+	/// Leave the prompt area so that following output starts on a fresh line
+	///
+	/// Unfinished input is left on screen if `should_print_line_on_control_c`
+	/// is set, otherwise the prompt is erased.
+	pub fn finish(&mut self, term: &mut impl Write) -> io::Result<()> {
+		self.redraw(term)?;
+		if self.region_rows != 0 {
+			self.leave_scroll_region(term)?;
+			self.render(term)?;
+		}
+		if self.line.is_empty() || !self.should_print_line_on_control_c {
+			self.clear(term)?;
+		} else {
+			let line_len = self.position(self.line.count());
+			self.move_to_beginning(term, self.current_column)?;
+			self.move_from_beginning(term, line_len as u16)?;
+			// Erase the status lines
+			term.queue(Clear(FromCursorDown))?;
+			if !line_len.is_multiple_of(self.term_size.0 as usize) {
+				writeln!(term)?;
+			}
+		}
+		term.queue(cursor::MoveToColumn(0))?;
+		Ok(())
+	}
+	pub fn term_size(&self) -> (u16, u16) {
+		self.term_size
+	}
+	/// Lay the prompt out again for a new terminal size
+	pub fn resize(&mut self, term_size: (u16, u16), term: &mut impl Write) -> io::Result<()> {
+		// Rows of the input above the cursor in the old layout
+		let above = self.line_height(self.current_column);
+		self.term_size = (term_size.0.max(1), term_size.1.max(1));
+		// Rows of multi-line input are laid out again for the new width
+		self.move_cursor(0)?;
+		if self.region_rows != 0 {
+			// Move the prompt to the new bottom of the terminal
+			let rows = self.region_rows;
+			self.set_region_rows(rows, term)?;
+			return self.clear_and_render(term);
+		}
+		if self.prompt_drawn {
+			// Some terminals reflow wrapped lines when resized and some don't,
+			// so go up far enough for either layout
+			let above = above.max(self.line_height(self.current_column));
+			term.queue(cursor::MoveToColumn(0))?;
+			if above != 0 {
+				term.queue(cursor::MoveUp(above))?;
+			}
+			term.queue(Clear(FromCursorDown))?;
+			self.drawn_line = None;
+		}
+		self.render(term)
+	}
+	/// Draw the prompt again after another program (or the shell, while
+	/// suspended) used the terminal
+	pub fn resume(&mut self, term_size: (u16, u16), term: &mut impl Write) -> io::Result<()> {
+		self.term_size = term_size;
+		self.move_cursor(0)?;
+		// The cursor position is unknown, so start over on the current line
+		self.last_line_completed = true;
+		self.last_line_column = 0;
+		self.region_rows = 0;
+		term.queue(cursor::MoveToColumn(0))?;
+		if self.render_mode == RenderMode::ScrollRegion {
+			self.enter_scroll_region(term)?;
+		} else {
+			self.render(term)?;
+		}
+		Ok(())
+	}
+	/// Replace the status lines drawn below the input line
+	pub fn set_status_lines(
+		&mut self,
+		lines: Vec<String>,
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		self.clear(term)?;
+		self.status_lines = lines;
+		self.render(term)
+	}
+	/// Clear the screen according to `clear_behavior` and redraw the prompt
+	pub fn clear_screen(&mut self, term: &mut impl Write) -> io::Result<()> {
+		match self.clear_behavior {
+			ClearBehavior::RedrawPrompt => {}
+			ClearBehavior::ClearVisible => {
+				self.clear(term)?;
+				term.queue(Clear(All))?;
+			}
+			ClearBehavior::ClearAll => {
+				term.queue(Clear(All))?.queue(cursor::MoveTo(0, 0))?;
+			}
+			ClearBehavior::ClearWithScrollback => {
+				term.queue(Clear(Purge))?
+					.queue(Clear(All))?
+					.queue(cursor::MoveTo(0, 0))?;
+			}
+		}
+		if self.region_rows != 0 && self.clear_behavior != ClearBehavior::RedrawPrompt {
+			// Output starts over at the top of the cleared scroll region
+			term.queue(cursor::MoveTo(0, 0))?
+				.queue(cursor::SavePosition)?;
+			self.last_line_completed = true;
+		}
+		self.clear_and_render(term)
+	}
+	/// Give the feedback chosen by `bell_style`
+	fn bell(&mut self, term: &mut impl Write) -> io::Result<()> {
+		match self.bell_style {
+			BellStyle::None => {}
+			BellStyle::Audible => term.write_all(b"\x07")?,
+			BellStyle::Visible => {
+				self.flashing = true;
+				self.clear_and_render(term)?;
+			}
+		}
+		Ok(())
+	}
+	/// Whether the prompt is flashing for a visible bell
+	pub fn is_flashing(&self) -> bool {
+		self.flashing
+	}
+	/// End the flash of a visible bell
+	pub fn stop_flashing(&mut self, term: &mut impl Write) -> io::Result<()> {
+		if self.flashing {
+			self.flashing = false;
+			self.clear_and_render(term)?;
+		}
+		Ok(())
+	}
+	/// When the prompt has to be drawn again after a redraw was deferred
+	/// because of `min_redraw_interval`, if it is missing
+	pub fn redraw_deadline(&self) -> Option<Instant> {
+		if self.prompt_drawn {
+			return None;
+		}
+		Some(match self.last_render {
+			Some(last_render) => last_render + self.min_redraw_interval,
+			None => Instant::now(),
+		})
+	}
+	/// Draw the prompt if a redraw was deferred
+	pub fn redraw(&mut self, term: &mut impl Write) -> io::Result<()> {
+		if !self.prompt_drawn {
+			self.render(term)?;
+		}
+		Ok(())
+	}
+	/// Whether output is frozen by Ctrl-S
+	pub fn is_frozen(&self) -> bool {
+		self.frozen.is_some()
+	}
+	/// Freeze output with the given number of lines pending, or release it
+	pub fn set_frozen(&mut self, frozen: Option<usize>, term: &mut impl Write) -> io::Result<()> {
+		self.clear(term)?;
+		self.frozen = frozen;
+		self.render(term)
+	}
+	/// Show a message below the input line, or remove it
+	pub fn set_toast(&mut self, toast: Option<String>, term: &mut impl Write) -> io::Result<()> {
+		self.clear(term)?;
+		self.toast = toast;
+		self.render(term)
+	}
+	/// Erase the prompt and remove the scroll region, if any, before handing
+	/// the terminal over to something else
+	pub fn erase(&mut self, term: &mut impl Write) -> io::Result<()> {
+		if self.region_rows != 0 {
+			self.leave_scroll_region(term)
+		} else {
+			self.clear(term)
+		}
+	}
+	/// Switch to a different way of drawing output and the prompt
+	pub fn set_render_mode(&mut self, mode: RenderMode, term: &mut impl Write) -> io::Result<()> {
+		if mode == self.render_mode {
+			return Ok(());
+		}
+		self.render_mode = mode;
+		match mode {
+			RenderMode::ScrollRegion => {
+				self.clear(term)?;
+				self.enter_scroll_region(term)
+			}
+			RenderMode::Redraw => {
+				self.leave_scroll_region(term)?;
+				self.render(term)
+			}
+		}
+	}
+	// Row of the terminal the prompt starts at while a scroll region is set up
+	fn prompt_row(&self) -> u16 {
+		self.term_size.1.saturating_sub(self.region_rows)
+	}
+	/// Reserve rows at the bottom of the terminal for the prompt and set up a
+	/// scroll region above them.  The cursor must be at the start of the
+	/// erased prompt, which becomes the position output is printed at.
+	fn enter_scroll_region(&mut self, term: &mut impl Write) -> io::Result<()> {
+		let rows = self.rows().min(self.term_size.1.saturating_sub(1)).max(1);
+		// Scroll everything up to make room for the prompt, keeping the output
+		// position (in the saved cursor) on the same line of text
+		term.queue(cursor::SavePosition)?
+			.queue(cursor::MoveTo(0, self.term_size.1.saturating_sub(1)))?;
+		for _ in 0..rows {
+			term.write_all(b"\n")?;
+		}
+		term.queue(cursor::RestorePosition)?
+			.queue(cursor::MoveUp(rows))?
+			.queue(cursor::SavePosition)?;
+		self.set_region_rows(rows, term)?;
+		self.render(term)
+	}
+	/// Remove the scroll region, leaving the cursor at the start of a line
+	/// below the output
+	fn leave_scroll_region(&mut self, term: &mut impl Write) -> io::Result<()> {
+		if self.region_rows == 0 {
+			return Ok(());
+		}
+		self.clear(term)?;
+		self.region_rows = 0;
+		write!(term, "\x1b[r")?;
+		term.queue(cursor::RestorePosition)?;
+		if !self.last_line_completed {
+			writeln!(term)?;
+		}
+		self.last_line_completed = true;
+		self.last_line_column = 0;
+		term.queue(cursor::MoveToColumn(0))?
+			.queue(Clear(FromCursorDown))?;
+		Ok(())
+	}
+	// Change the number of rows reserved for the prompt
+	fn set_region_rows(&mut self, rows: u16, term: &mut impl Write) -> io::Result<()> {
+		let rows = rows.min(self.term_size.1.saturating_sub(1)).max(1);
+		if self.region_rows != 0 && rows > self.region_rows {
+			// Scroll the output up, out of the way of the growing prompt
+			let grow = rows - self.region_rows;
+			term.queue(cursor::MoveTo(0, self.prompt_row().saturating_sub(1)))?;
+			for _ in 0..grow {
+				term.write_all(b"\n")?;
+			}
+			term.queue(cursor::RestorePosition)?
+				.queue(cursor::MoveUp(grow))?
+				.queue(cursor::SavePosition)?;
+		}
+		self.region_rows = rows;
+		// Setting the margins moves the cursor, so callers position it afterwards
+		write!(term, "\x1b[1;{}r", self.prompt_row())?;
+		Ok(())
+	}
 	pub fn print_data(&mut self, data: &[u8], term: &mut impl Write) -> Result<(), ReadlineError> {
+		let mut term = SyncUpdate::begin(term, self.synchronized_output)?;
+		self.write_data(data, &mut *term)
+	}
+	fn write_data(&mut self, data: &[u8], term: &mut impl Write) -> Result<(), ReadlineError> {
+		let mut stripped = Vec::new();
+		let data = match &mut self.ansi_stripper {
+			Some(stripper) => {
+				stripper.strip(data, &mut stripped);
+				&stripped[..]
+			}
+			None => data,
+		};
+
+		// Keep the cursor from flashing around while output is printed
+		term.queue(cursor::Hide)?;
+
+		if self.region_rows != 0 {
+			// Output goes into the scroll region at the saved position, the
+			// prompt stays where it is
+			term.queue(cursor::RestorePosition)?;
+			for line in data.split_inclusive(|b| *b == b'\n') {
+				term.write_all(line)?;
+				if line.ends_with(b"\n") {
+					term.queue(cursor::MoveToColumn(0))?;
+				}
+			}
+			term.queue(cursor::SavePosition)?;
+			self.last_line_completed = data.ends_with(b"\n");
+			term.queue(cursor::MoveTo(0, self.prompt_row()))?;
+			self.set_cursor(term)?;
+			self.show_cursor(term)?;
+			return Ok(());
+		}
+
 		self.clear(term)?;
 
 		// If last written data was not newline, restore the cursor
 		if !self.last_line_completed {
 			term.queue(cursor::MoveUp(1))?
-				.queue(cursor::MoveToColumn(0))?
-				.queue(cursor::MoveRight(self.last_line_length as u16))?;
+				.queue(cursor::MoveToColumn(self.last_line_column))?;
 		}
 
 		// Write data in a way that newlines also act as carriage returns
@@ -152,285 +1082,2512 @@ impl LineState {
 		// If data does not end with newline, save the cursor and write newline for prompt
 		// Usually data does end in newline due to the buffering of SharedWriter, but sometimes it may not (i.e. if .flush() is called)
 		if !self.last_line_completed {
-			self.last_line_length += data.len();
-			// Make sure that last_line_length wraps around when doing multiple writes
-			if self.last_line_length >= self.term_size.0 as usize {
-				self.last_line_length %= self.term_size.0 as usize;
+			let (column, tail) = match data.iter().rposition(|b| *b == b'\n') {
+				Some(newline) => (0, &data[newline + 1..]),
+				None => (self.last_line_column as usize, data),
+			};
+			let width = self.term_size.0 as usize;
+			let column = advance_column(&mut self.last_line_escapes, tail, column, width);
+			if column >= width {
+				// The row is full and the next character would start a new
+				// one, which the cursor can't be put back into, so start it now
+				self.last_line_column = 0;
 				writeln!(term)?;
+			} else {
+				self.last_line_column = column as u16;
 			}
 			writeln!(term)?; // Move to beginning of line and make new line
 		} else {
-			self.last_line_length = 0;
+			self.last_line_column = 0;
 		}
 
 		term.queue(cursor::MoveToColumn(0))?;
 
-		self.render(term)?;
+		// Under heavy output, the prompt is drawn again by `redraw()` later
+		if self
+			.redraw_deadline()
+			.is_none_or(|deadline| deadline <= Instant::now())
+		{
+			self.render(term)?;
+		}
+		self.show_cursor(term)?;
 		Ok(())
 	}
 	pub fn print(&mut self, string: &str, term: &mut impl Write) -> Result<(), ReadlineError> {
 		self.print_data(string.as_bytes(), term)?;
 		Ok(())
 	}
-	pub fn update_prompt(
+	/// Ask the completer for candidates for the text before the cursor
+	///
+	/// An async completer's answer is applied by `poll_completion()` later.
+	fn complete(&mut self, term: &mut impl Write) -> Result<(), ReadlineError> {
+		let pos = self.line.offset(self.line_cursor_grapheme);
+		match &mut self.completer {
+			None => Ok(()),
+			Some(CompletionSource::Sync(completer)) => {
+				let (start, candidates) = completer.complete(self.line.as_str(), pos);
+				self.apply_completion(start, &candidates, term)
+			}
+			Some(CompletionSource::Async(completer)) => {
+				self.pending_completion = Some(PendingCompletion {
+					line: self.line.as_str().to_string(),
+					cursor: self.line_cursor_grapheme,
+					future: completer.complete(self.line.as_str(), pos),
+				});
+				Ok(())
+			}
+		}
+	}
+	/// Apply the candidates from an async completer once they are ready
+	///
+	/// They are dropped if the input was edited while they were looked up.
+	pub fn poll_completion(
 		&mut self,
-		prompt: &str,
+		cx: &mut Context<'_>,
 		term: &mut impl Write,
-	) -> Result<(), ReadlineError> {
-		self.clear(term)?;
-		self.prompt.clear();
-		self.prompt.push_str(prompt);
-		// recalculates column
-		self.move_cursor(0)?;
-		self.render(term)?;
-		term.flush()?;
-		Ok(())
+	) -> Poll<Result<(), ReadlineError>> {
+		let Some(pending) = &mut self.pending_completion else {
+			return Poll::Pending;
+		};
+		let (start, candidates) = ready!(pending.future.poll_unpin(cx));
+		let pending = self.pending_completion.take().unwrap();
+		if pending.line != self.line.as_str() || pending.cursor != self.line_cursor_grapheme {
+			return Poll::Ready(Ok(()));
+		}
+		let mut term = SyncUpdate::begin(term, self.synchronized_output)?;
+		Poll::Ready(self.apply_completion(start, &candidates, &mut *term))
 	}
-	pub fn handle_event(
+	/// Replace the text from byte offset `start` up to the cursor with the
+	/// candidate if there is only one, or else with the prefix they share
+	///
+	/// When they share nothing more than what is already typed, shows a menu
+	/// of them below the input, or lists them above the prompt on the second
+	/// try, depending on `completion_style`.
+	pub fn apply_completion(
 		&mut self,
-		event: Event,
+		start: usize,
+		candidates: &[Candidate],
 		term: &mut impl Write,
-	) -> Result<Option<ReadlineEvent>, ReadlineError> {
-		match event {
-			// Control Keys
-			Event::Key(KeyEvent {
-				code,
-				modifiers: KeyModifiers::CONTROL,
-				kind: KeyEventKind::Press,
-				..
-			}) => match code {
-				// End of transmission (CTRL-D)
-				KeyCode::Char('d') => {
-					writeln!(term)?;
+	) -> Result<(), ReadlineError> {
+		let pos = self.line.offset(self.line_cursor_grapheme);
+		let start = start.min(pos);
+		if candidates.is_empty() || !self.line.as_str().is_char_boundary(start) {
+			self.bell(term)?;
+			return Ok(());
+		}
+		let typed = &self.line.as_str()[start..pos];
+		let matches = self
+			.match_strategy
+			.filter(typed, candidates, self.completion_ignore_case);
+		if matches.is_empty() {
+			self.bell(term)?;
+			return Ok(());
+		}
+		let completion = common_prefix(&matches);
+		if matches.len() > 1 && completion.len() <= typed.len() {
+			match self.completion_style {
+				CompletionStyle::Menu => {
 					self.clear(term)?;
-					return Ok(Some(ReadlineEvent::Eof));
-				}
-				// End of text (CTRL-C)
-				KeyCode::Char('c') => {
-					if self.should_print_line_on_control_c {
-						self.print(&format!("{}{}", self.prompt, self.line), term)?;
-					}
-
-					self.line.clear();
-					self.move_cursor(-10000)?;
-					self.clear_and_render(term)?;
-					return Ok(Some(ReadlineEvent::Interrupted));
-				}
-				// Clear all
-				KeyCode::Char('l') => {
-					term.queue(Clear(All))?.queue(cursor::MoveTo(0, 0))?;
-					self.clear_and_render(term)?;
-				}
-				// Clear to start
-				KeyCode::Char('u') => {
-					if let Some((pos, str)) = self.current_grapheme() {
-						let pos = pos + str.len();
-						self.line.drain(0..pos);
-						self.move_cursor(-100000)?;
-						self.clear_and_render(term)?;
-					}
-				}
-				// Clear last word
-				KeyCode::Char('w') => {
-					let count = self.line.graphemes(true).count();
-					let skip_count = count - self.line_cursor_grapheme;
-					let start = self
-						.line
-						.grapheme_indices(true)
-						.rev()
-						.skip(skip_count)
-						.skip_while(|(_, str)| *str == " ")
-						.find_map(|(pos, str)| if str == " " { Some(pos + 1) } else { None })
-						.unwrap_or(0);
-					let end = self
-						.line
-						.grapheme_indices(true)
-						.nth(self.line_cursor_grapheme)
-						.map(|(end, _)| end);
-					let change = start as isize - self.line_cursor_grapheme as isize;
-					self.move_cursor(change)?;
-					if let Some(end) = end {
-						self.line.drain(start..end);
-					} else {
-						self.line.drain(start..);
-					}
-					self.clear_and_render(term)?;
-				}
-				// Move to beginning
-				#[cfg(feature = "emacs")]
-				KeyCode::Char('a') => {
-					self.reset_cursor(term)?;
-					self.move_cursor(-100000)?;
-					self.set_cursor(term)?;
-				}
-				// Move to end
-				#[cfg(feature = "emacs")]
-				KeyCode::Char('e') => {
-					self.reset_cursor(term)?;
-					self.move_cursor(100000)?;
-					self.set_cursor(term)?;
-				}
-				// Move cursor left to previous word
-				KeyCode::Left => {
-					self.reset_cursor(term)?;
-					let count = self.line.graphemes(true).count();
-					let skip_count = count - self.line_cursor_grapheme;
-					if let Some((pos, _)) = self
-						.line
-						.grapheme_indices(true)
-						.rev()
-						.skip(skip_count)
-						.skip_while(|(_, str)| *str == " ")
-						.find(|(_, str)| *str == " ")
-					{
-						let change = pos as isize - self.line_cursor_grapheme as isize;
-						self.move_cursor(change + 1)?;
-					} else {
-						self.move_cursor(-100000)?
-					}
-					self.set_cursor(term)?;
-				}
-				// Move cursor right to next word
-				KeyCode::Right => {
-					self.reset_cursor(term)?;
-					if let Some((pos, _)) = self
-						.line
-						.grapheme_indices(true)
-						.skip(self.line_cursor_grapheme)
-						.skip_while(|(_, c)| *c == " ")
-						.find(|(_, c)| *c == " ")
-					{
-						let change = pos as isize - self.line_cursor_grapheme as isize;
-						self.move_cursor(change)?;
-					} else {
-						self.move_cursor(10000)?;
-					};
-					self.set_cursor(term)?;
-				}
-				_ => {}
-			},
-			// Other Modifiers (None, Shift, Control+Alt)
-			// All other modifiers must be considered because the match expression cannot match
-			// combined KeyModifiers. Control+Alt is used to reach certain special symbols on a lot
-			// of international keyboard layouts.
-			Event::Key(KeyEvent {
-				code,
-				modifiers: _,
-				kind: KeyEventKind::Press,
-				..
-			}) => match code {
-				KeyCode::Enter => {
-					// Print line so you can see what commands you've typed
-					if self.should_print_line_on_enter {
-						self.print(&format!("{}{}\n", self.prompt, self.line), term)?;
-					}
-
-					// Take line
-					let line = std::mem::take(&mut self.line);
-
-					// Render new line from beginning
-					self.move_cursor(-100000)?;
-					self.clear_and_render(term)?;
-					self.history.reset_position();
-
-					// Return line
-					return Ok(Some(ReadlineEvent::Line(line)));
-				}
-				// Delete character from line
-				KeyCode::Backspace => {
-					if let Some((pos, str)) = self.current_grapheme() {
-						self.clear(term)?;
-
-						let len = pos + str.len();
-						self.line.replace_range(pos..len, "");
-						self.move_cursor(-1)?;
-
-						self.render(term)?;
-					}
-				}
-				KeyCode::Delete => {
-					if let Some((pos, str)) = self.next_grapheme() {
-						self.clear(term)?;
-
-						let len = pos + str.len();
-						self.line.replace_range(pos..len, "");
-
-						self.render(term)?;
-					}
+					self.menu = Some(CompletionMenu::new(start, matches));
+					self.render(term)?;
 				}
-				KeyCode::Left => {
-					self.reset_cursor(term)?;
-					self.move_cursor(-1)?;
-					self.set_cursor(term)?;
+				CompletionStyle::List if self.tab_listing => {
+					let list = format_list(&matches, self.term_size.0 as usize);
+					self.print(&list, term)?;
 				}
-				KeyCode::Right => {
-					self.reset_cursor(term)?;
-					self.move_cursor(1)?;
-					self.set_cursor(term)?;
+				CompletionStyle::List => {
+					self.tab_listing = true;
+					self.bell(term)?;
 				}
-				KeyCode::Home => {
-					self.reset_cursor(term)?;
-					self.move_cursor(-100000)?;
-					self.set_cursor(term)?;
+			}
+			return Ok(());
+		}
+		let completion = completion.to_string();
+		self.insert_completion(start, &completion, term)?;
+		Ok(())
+	}
+	/// Replace the text from byte offset `start` up to the cursor
+	fn insert_completion(
+		&mut self,
+		start: usize,
+		text: &str,
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		let pos = self.line.offset(self.line_cursor_grapheme);
+		let from = self.current_column;
+		let after = self.line.count() - self.line_cursor_grapheme;
+		self.line.replace(start..pos, text);
+		// Keep the cursor in front of the text that was after it
+		self.line_cursor_grapheme = self.line.count().saturating_sub(after);
+		self.move_cursor(0)?;
+		self.update_line(from, term)
+	}
+	/// Handle a key while the completion menu is open, returns whether the
+	/// key was used up
+	///
+	/// Tab and the arrow keys move through the candidates, Enter inserts the
+	/// selected one and Esc closes the menu.  Other keys close it and then do
+	/// what they normally do.
+	fn handle_menu_key(&mut self, key: &KeyEvent, term: &mut impl Write) -> io::Result<bool> {
+		let Some(menu) = &mut self.menu else {
+			return Ok(false);
+		};
+		if key.kind != KeyEventKind::Press {
+			return Ok(false);
+		}
+		match key.code {
+			KeyCode::Tab | KeyCode::Down => menu.select_next(),
+			KeyCode::BackTab | KeyCode::Up => menu.select_previous(),
+			KeyCode::Enter if menu.selected.is_some() => {
+				let menu = self.menu.take().unwrap();
+				let candidate = &menu.candidates[menu.selected.unwrap()]
+					.candidate
+					.replacement;
+				self.clear(term)?;
+				self.insert_completion(menu.start, candidate, term)?;
+				return Ok(true);
+			}
+			KeyCode::Esc => {
+				self.clear(term)?;
+				self.menu = None;
+				self.render(term)?;
+				return Ok(true);
+			}
+			_ => {
+				self.clear(term)?;
+				self.menu = None;
+				self.render(term)?;
+				return Ok(false);
+			}
+		}
+		self.clear_and_render(term)?;
+		Ok(true)
+	}
+	/// Insert the suggestion or hint after the input if `key` is bound to
+	/// accepting it, and return whether the key was used up
+	fn handle_accept_hint_key(
+		&mut self,
+		key: &KeyEvent,
+		term: &mut impl Write,
+	) -> io::Result<bool> {
+		if key.kind != KeyEventKind::Press || self.line_cursor_grapheme != self.line.count() {
+			return Ok(false);
+		}
+		let Some(accept) = self
+			.accept_hint_keys
+			.iter()
+			.find(|(bound, _)| bound.code == key.code && bound.modifiers == key.modifiers)
+			.map(|&(_, accept)| accept)
+		else {
+			return Ok(false);
+		};
+		let Some(hint) = self.suggestion().or(self.hint.as_deref()) else {
+			return Ok(false);
+		};
+		let text = accept.part(hint).to_owned();
+		if text.is_empty() {
+			return Ok(false);
+		}
+		self.clear(term)?;
+		let end = self.line.as_str().len();
+		self.line.replace(end..end, &text);
+		self.move_cursor(100000)?;
+		self.render(term)?;
+		Ok(true)
+	}
+	/// Handle a key while searching the history, returns whether the key was
+	/// used up
+	///
+	/// Typing extends the search text, the keys bound to
+	/// [`EditAction::ReverseSearch`] and [`EditAction::ForwardSearch`] find
+	/// the next older or newer match, and Esc or Ctrl-G put the input back.  Other keys keep
+	/// the entry found and then do what they normally do.
+	fn handle_search_key(
+		&mut self,
+		key: &KeyEvent,
+		term: &mut impl Write,
+		history: &mut History,
+	) -> io::Result<bool> {
+		if key.kind != KeyEventKind::Press {
+			return Ok(false);
+		}
+		let action = self.keymap.action(key);
+		let Some(search) = &mut self.search else {
+			return Ok(false);
+		};
+		match key.code {
+			_ if action == Some(EditAction::ReverseSearch) => {
+				search.forward = false;
+				self.search_history(true, history, term)?;
+			}
+			// Also with flow control enabled
+			_ if action == Some(EditAction::ForwardSearch) => {
+				search.forward = true;
+				self.search_history(true, history, term)?;
+			}
+			KeyCode::Char('g') if key.modifiers == KeyModifiers::CONTROL => {
+				self.end_search(false, history, term)?
+			}
+			KeyCode::Esc => self.end_search(false, history, term)?,
+			KeyCode::Char(c)
+				if !key
+					.modifiers
+					.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+			{
+				search.query.push(c);
+				self.search_history(false, history, term)?;
+			}
+			KeyCode::Backspace => {
+				// Search for the shorter text from the start again
+				search.query.pop();
+				search.found = search.start;
+				self.search_history(false, history, term)?;
+			}
+			_ => {
+				self.end_search(true, history, term)?;
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+	/// Start searching the history, from the entry shown if any
+	fn start_search(
+		&mut self,
+		forward: bool,
+		term: &mut impl Write,
+		history: &History,
+	) -> io::Result<()> {
+		let line = self.line.as_str().to_owned();
+		let prompt = self.prompt.clone();
+		self.search = Some(HistorySearch::new(
+			forward,
+			history.position(),
+			line,
+			prompt,
+		));
+		self.show_search(term)
+	}
+	/// Show the nearest history entry containing the search text, starting
+	/// after the one shown if `step` is set
+	fn search_history(
+		&mut self,
+		step: bool,
+		history: &History,
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		let Some(search) = &mut self.search else {
+			return Ok(());
+		};
+		if search.query.is_empty() && !step {
+			search.found = search.start;
+			search.failing = false;
+			self.line.set(search.line.clone());
+			self.line_cursor_grapheme = self.line.count();
+			return self.show_search(term);
+		}
+		let from = match search.found {
+			Some(found) if !step => Some(found),
+			Some(found) if search.forward => Some(found + 1),
+			Some(found) => found.checked_sub(1),
+			// The input is newer than any entry
+			None if search.forward => None,
+			None => history.get_entries().len().checked_sub(1),
+		};
+		match from.and_then(|from| history.find(&search.query, from, search.forward)) {
+			Some(index) => {
+				search.found = Some(index);
+				search.failing = false;
+				let entry = history.get_entries()[index].as_str();
+				// The cursor goes to the match
+				let offset = entry.find(&search.query).unwrap_or(0);
+				self.line.set(entry.to_owned());
+				self.line_cursor_grapheme = self.line.index(offset);
+			}
+			None => {
+				let failed = std::mem::replace(&mut search.failing, true);
+				self.show_search(term)?;
+				if !failed {
+					self.bell(term)?;
 				}
-				KeyCode::End => {
-					self.reset_cursor(term)?;
-					self.move_cursor(100000)?;
-					self.set_cursor(term)?;
+				return Ok(());
+			}
+		}
+		self.show_search(term)
+	}
+	/// Draw the input with the search prompt
+	fn show_search(&mut self, term: &mut impl Write) -> io::Result<()> {
+		let Some(search) = &self.search else {
+			return Ok(());
+		};
+		let prompt = search.prompt();
+		self.clear(term)?;
+		self.set_prompt(prompt.into());
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	/// Leave the history search, keeping the entry found if `accept` is set
+	/// or else putting the input back
+	fn end_search(
+		&mut self,
+		accept: bool,
+		history: &mut History,
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		let Some(search) = self.search.take() else {
+			return Ok(());
+		};
+		self.clear(term)?;
+		self.set_prompt(search.prompt);
+		if !accept {
+			self.line.set(search.line);
+			self.line_cursor_grapheme = self.line.count();
+		} else if search.found.is_some() {
+			// Up and Down continue from the entry found
+			history.set_position(search.found);
+		}
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	/// Expand the history designators in the input while the cursor is at its
+	/// end, leaving it as it is if an entry isn't found
+	fn expand_before_space(&mut self, history: &History, term: &mut impl Write) -> io::Result<()> {
+		if self.line_cursor_grapheme != self.line.count() {
+			return Ok(());
+		}
+		match expand(self.line.as_str(), history) {
+			Some(expanded) if expanded != self.line.as_str() => {
+				self.clear(term)?;
+				self.line.set(expanded);
+				self.move_cursor(100000)?;
+				self.render(term)
+			}
+			_ => Ok(()),
+		}
+	}
+	pub fn update_prompt(
+		&mut self,
+		prompt: Prompt,
+		term: &mut impl Write,
+	) -> Result<(), ReadlineError> {
+		if let Some(search) = &mut self.search {
+			// Shown again once the search is over
+			search.prompt = prompt;
+			return Ok(());
+		}
+		self.clear(term)?;
+		self.set_prompt(prompt);
+		// recalculates column
+		self.move_cursor(0)?;
+		self.render(term)?;
+		term.flush()?;
+		Ok(())
+	}
+	pub fn handle_event(
+		&mut self,
+		event: Event,
+		term: &mut impl Write,
+		history: &mut History,
+	) -> Result<Option<ReadlineEvent>, ReadlineError> {
+		let mut term = SyncUpdate::begin(term, self.synchronized_output)?;
+		let key = match &event {
+			Event::Key(key) if key.kind == KeyEventKind::Press => Some(*key),
+			_ => None,
+		};
+		let before = (self.line.as_str().to_owned(), self.line_cursor_grapheme);
+		// Characters inserted one by one are undone together, up to a space
+		let typing = key.is_some_and(|key| match key.code {
+			KeyCode::Char(c) => {
+				!c.is_whitespace()
+					&& self.keymap.action(&key).is_none()
+					&& self.vi.as_ref().is_none_or(|vi| vi.mode == ViMode::Insert)
+			}
+			_ => false,
+		});
+		let result = self.apply_event(event, &mut *term, history);
+		if key.is_some() {
+			match &result {
+				// The next input starts afresh
+				Ok(Some(_)) => self.undo.clear(),
+				_ => {
+					let busy = self.search.is_some();
+					self.undo.record(before, self.line.as_str(), typing, busy);
 				}
-				KeyCode::Up => {
-					// search for next history item, replace line if found.
-					if let Some(line) = self.history.search_next(&self.line) {
-						self.line.clear();
-						self.line += line;
-						self.clear(term)?;
-						self.move_cursor(100000)?;
-						self.render(term)?;
+			}
+		}
+		self.update_hints(&mut *term, history)?;
+		result
+	}
+	fn apply_event(
+		&mut self,
+		event: Event,
+		term: &mut impl Write,
+		history: &mut History,
+	) -> Result<Option<ReadlineEvent>, ReadlineError> {
+		// Key presses are never throttled
+		self.redraw(term)?;
+		if let Event::Key(key) = &event {
+			if key.kind == KeyEventKind::Press && key.code != KeyCode::Tab {
+				self.tab_listing = false;
+			}
+			if self.handle_menu_key(key, term)?
+				|| self.handle_search_key(key, term, history)?
+				|| self.handle_accept_hint_key(key, term)?
+			{
+				return Ok(None);
+			}
+		}
+		match event {
+			Event::Key(key) if key.kind == KeyEventKind::Press => {
+				self.kill_ring.start_command();
+				if let Some((_, handler)) = self
+					.custom_keys
+					.iter_mut()
+					.find(|(bound, _)| bound.code == key.code && bound.modifiers == key.modifiers)
+				{
+					return Ok(handler(self.line.as_str()));
+				}
+				if self.flow_control && key.modifiers == KeyModifiers::CONTROL {
+					match key.code {
+						// Freeze output (XOFF)
+						KeyCode::Char('s') => {
+							if self.frozen.is_none() {
+								self.set_frozen(Some(0), term)?;
+							}
+							return Ok(None);
+						}
+						// Release output (XON)
+						KeyCode::Char('q') => {
+							self.set_frozen(None, term)?;
+							return Ok(None);
+						}
+						_ => {}
 					}
 				}
-				KeyCode::Down => {
-					// search for next history item, replace line if found.
-					if let Some(line) = self.history.search_previous(&self.line) {
-						self.line.clear();
-						self.line += line;
+				if let KeyCode::Char(c @ '0'..='9') = key.code {
+					let alt = key.modifiers == KeyModifiers::ALT;
+					let more = self.argument.is_some() && key.modifiers == KeyModifiers::NONE;
+					if (alt || more) && self.keymap.action(&key).is_none() {
+						let argument =
+							self.argument.unwrap_or(0) * 10 + (c as usize - '0' as usize);
+						self.argument = Some(argument.min(MAX_ARGUMENT));
+						return Ok(None);
+					}
+				}
+				// Keys typed so far of a sequence like Ctrl-X Ctrl-U
+				let mut keys = std::mem::take(&mut self.pending_keys);
+				if self
+					.pending_since
+					.is_some_and(|since| since.elapsed() > SEQUENCE_TIMEOUT)
+				{
+					keys.clear();
+				}
+				if !keys.is_empty() && key.code == KeyCode::Esc {
+					return Ok(None);
+				}
+				keys.push(key);
+				let action = match self.keymap.lookup(&keys) {
+					Lookup::Prefix => {
+						self.pending_keys = keys;
+						self.pending_since = Some(Instant::now());
+						return Ok(None);
+					}
+					Lookup::Unbound if keys.len() > 1 => {
+						self.argument = None;
+						self.bell(term)?;
+						return Ok(None);
+					}
+					Lookup::Action(action) => Some(action),
+					Lookup::Unbound => None,
+				};
+				let count = self.argument.take().unwrap_or(1);
+				if let Some(vi) = self.vi.as_mut().filter(|_| keys.len() == 1) {
+					let plain = !key
+						.modifiers
+						.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+					match key.code {
+						KeyCode::Esc if plain => {
+							vi.escape(&mut self.line_cursor_grapheme);
+							self.redraw_vi(term)?;
+							return Ok(None);
+						}
+						KeyCode::Char(c) if plain && vi.mode == ViMode::Normal => {
+							return self.handle_vi_key(c, term, history);
+						}
+						_ => {}
+					}
+				}
+				for i in 0..count {
+					if i > 0 {
+						self.kill_ring.start_command();
+					}
+					match action {
+						Some(action) => {
+							if let Some(event) = self.run_action(action, term, history)? {
+								return Ok(Some(event));
+							}
+						}
+						// Add character to line and output
+						None => match key.code {
+							KeyCode::Char(c) if key.modifiers != KeyModifiers::CONTROL => {
+								self.insert_char(c, term, history)?;
+							}
+							_ => {}
+						},
+					}
+				}
+			}
+			Event::Resize(x, y) => self.resize((x, y), term)?,
+			_ => {}
+		}
+		Ok(None)
+	}
+	/// Handle a character typed in vi normal mode
+	fn handle_vi_key(
+		&mut self,
+		c: char,
+		term: &mut impl Write,
+		history: &mut History,
+	) -> Result<Option<ReadlineEvent>, ReadlineError> {
+		let Some(vi) = &mut self.vi else {
+			return Ok(None);
+		};
+		match vi.normal_key(c, &mut self.line, &mut self.line_cursor_grapheme) {
+			ViOutcome::Pending => {}
+			ViOutcome::Changed => self.redraw_vi(term)?,
+			ViOutcome::Bell => self.bell(term)?,
+			ViOutcome::Action(action) => {
+				let event = self.run_action(action, term, history)?;
+				self.redraw_vi(term)?;
+				return Ok(event);
+			}
+		}
+		Ok(None)
+	}
+	/// Draw the input again after a vi command, which may have changed the
+	/// input, the cursor or the mode indicator
+	fn redraw_vi(&mut self, term: &mut impl Write) -> io::Result<()> {
+		if let Some(vi) = &self.vi {
+			// On the last character in normal mode, not after it
+			let count = self.line.count();
+			if vi.mode == ViMode::Normal && count > 0 && self.line_cursor_grapheme >= count {
+				self.line_cursor_grapheme = count - 1;
+			}
+		}
+		self.clear(term)?;
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	/// Do what a key bound to `action` does
+	fn run_action(
+		&mut self,
+		action: EditAction,
+		term: &mut impl Write,
+		history: &mut History,
+	) -> Result<Option<ReadlineEvent>, ReadlineError> {
+		match action {
+			EditAction::AcceptLine => {
+				if self.history_expansion.on_enter {
+					let Some(expanded) = expand(self.line.as_str(), history) else {
+						self.bell(term)?;
+						return Ok(None);
+					};
+					if expanded != self.line.as_str() {
 						self.clear(term)?;
+						self.line.set(expanded);
 						self.move_cursor(100000)?;
 						self.render(term)?;
+						if self.history_expansion.verify {
+							return Ok(None);
+						}
 					}
 				}
-				// Add character to line and output
-				KeyCode::Char(c) => {
-					self.clear(term)?;
-					let prev_len = self.cluster_buffer.graphemes(true).count();
-					self.cluster_buffer.push(c);
-					let new_len = self.cluster_buffer.graphemes(true).count();
-
-					let (g_pos, g_str) = self.current_grapheme().unwrap_or((0, ""));
-					let pos = g_pos + g_str.len();
-
-					self.line.insert(pos, c);
-
-					if prev_len != new_len {
-						self.move_cursor(1)?;
-						if prev_len > 0 {
-							if let Some((pos, str)) =
-								self.cluster_buffer.grapheme_indices(true).next()
-							{
-								let len = str.len();
-								self.cluster_buffer.replace_range(pos..len, "");
+				// Print line so you can see what commands you've typed
+				if self.should_print_line_on_enter {
+					self.echo(term)?;
+				}
+
+				// Take line
+				self.clear(term)?;
+				let line = self.line.take();
+				if let Some(vi) = &mut self.vi {
+					vi.reset();
+				}
+
+				// Render new line from beginning
+				self.move_cursor(-100000)?;
+				self.render(term)?;
+				history.reset_position();
+
+				// Return line
+				return Ok(Some(ReadlineEvent::Line(line)));
+			}
+			EditAction::Interrupt => {
+				if self.should_print_line_on_control_c {
+					self.echo(term)?;
+				}
+
+				// Erased while the cursor is still placed for the input,
+				// which may span several rows
+				self.clear(term)?;
+				self.line.clear();
+				if let Some(vi) = &mut self.vi {
+					vi.reset();
+				}
+				self.move_cursor(-10000)?;
+				self.render(term)?;
+				return Ok(Some(ReadlineEvent::Interrupted));
+			}
+			EditAction::EndOfFile => {
+				writeln!(term)?;
+				self.clear(term)?;
+				return Ok(Some(ReadlineEvent::Eof));
+			}
+			EditAction::ClearScreen => self.clear_screen(term)?,
+			EditAction::MoveLeft => {
+				if self.current_grapheme().is_none() {
+					self.bell(term)?;
+				}
+				self.reset_cursor(term)?;
+				self.move_cursor(-1)?;
+				self.set_cursor(term)?;
+			}
+			EditAction::MoveRight => {
+				if self.next_grapheme().is_none() {
+					self.bell(term)?;
+				}
+				self.reset_cursor(term)?;
+				self.move_cursor(1)?;
+				self.set_cursor(term)?;
+			}
+			EditAction::MoveWordLeft | EditAction::BackwardWord => {
+				self.reset_cursor(term)?;
+				let start = self.word_start(
+					self.line_cursor_grapheme,
+					action == EditAction::BackwardWord,
+				);
+				self.move_cursor(start as isize - self.line_cursor_grapheme as isize)?;
+				self.set_cursor(term)?;
+			}
+			EditAction::MoveWordRight | EditAction::ForwardWord => {
+				self.reset_cursor(term)?;
+				let end =
+					self.word_end(self.line_cursor_grapheme, action == EditAction::ForwardWord);
+				self.move_cursor(end as isize - self.line_cursor_grapheme as isize)?;
+				self.set_cursor(term)?;
+			}
+			EditAction::MoveHome => {
+				self.reset_cursor(term)?;
+				self.move_cursor(-100000)?;
+				self.set_cursor(term)?;
+			}
+			EditAction::MoveEnd => {
+				self.reset_cursor(term)?;
+				self.move_cursor(100000)?;
+				self.set_cursor(term)?;
+			}
+			// Delete character from line
+			EditAction::DeleteBackward => {
+				if let Some(vi) = &mut self.vi {
+					vi.record_backspace();
+				}
+				if let Some((pos, str)) = self.current_grapheme() {
+					let from = self.current_column;
+
+					let len = pos + str.len();
+					self.line.replace(pos..len, "");
+					self.move_cursor(-1)?;
+
+					self.update_line(from, term)?;
+				} else {
+					self.bell(term)?;
+				}
+			}
+			EditAction::DeleteForward => {
+				if let Some((pos, str)) = self.next_grapheme() {
+					let len = pos + str.len();
+					self.line.replace(pos..len, "");
+
+					self.update_line(self.current_column, term)?;
+				} else {
+					self.bell(term)?;
+				}
+			}
+			// Clear last word
+			EditAction::DeleteWordBackward | EditAction::KillWordBackward => {
+				let from = self.current_column;
+				let start = self.word_start(
+					self.line_cursor_grapheme,
+					action == EditAction::KillWordBackward,
+				);
+				let end = self.line_cursor_grapheme;
+				let range = self.line.offset(start)..self.line.offset(end);
+				self.kill_ring
+					.kill(&self.line.as_str()[range.clone()], true);
+				self.move_cursor(start as isize - end as isize)?;
+				self.line.replace(range, "");
+				self.update_line(from, term)?;
+			}
+			// Clear to start
+			EditAction::KillToStart => {
+				let pos = self.line.offset(self.line_cursor_grapheme);
+				self.kill_ring.kill(&self.line.as_str()[..pos], true);
+				if pos > 0 {
+					self.line.replace(0..pos, "");
+					self.move_cursor(-100000)?;
+					self.clear_and_render(term)?;
+				}
+			}
+			// Clear to end
+			EditAction::KillLine => {
+				let pos = self.line.offset(self.line_cursor_grapheme);
+				let end = self.line.as_str().len();
+				self.kill_ring.kill(&self.line.as_str()[pos..], false);
+				if pos < end {
+					self.line.replace(pos..end, "");
+					self.update_line(self.current_column, term)?;
+				}
+			}
+			EditAction::KillWordForward => {
+				let pos = self.line.offset(self.line_cursor_grapheme);
+				let end = self
+					.line
+					.offset(self.word_end(self.line_cursor_grapheme, true));
+				self.kill_ring.kill(&self.line.as_str()[pos..end], false);
+				if pos < end {
+					self.line.replace(pos..end, "");
+					self.update_line(self.current_column, term)?;
+				}
+			}
+			EditAction::TransposeChars => {
+				let count = self.line.count();
+				// At the end of the input the two characters before the cursor
+				let second = match self.line_cursor_grapheme {
+					cursor if cursor == count => cursor.saturating_sub(1),
+					cursor => cursor,
+				};
+				if second == 0 || count < 2 {
+					self.bell(term)?;
+				} else {
+					let range = self.line.offset(second - 1)..self.line.offset(second + 1);
+					let (_, first) = self.line.grapheme(second - 1).unwrap();
+					let (_, last) = self.line.grapheme(second).unwrap();
+					let swapped = format!("{last}{first}");
+					self.replace_and_render(range, &swapped, term)?;
+				}
+			}
+			EditAction::TransposeWords => {
+				// The word at or after the cursor and the one before it, or
+				// the last two words at the end of the input
+				let second_end = self.word_end(self.line_cursor_grapheme, true);
+				let second_start = self.word_start(second_end, true);
+				let first_start = self.word_start(second_start, true);
+				let first_end = self.word_end(first_start, true);
+				if first_start == second_start || second_start < first_end {
+					self.bell(term)?;
+				} else {
+					let text = self.line.as_str();
+					let offset = |index| self.line.offset(index);
+					let swapped = [
+						&text[offset(second_start)..offset(second_end)],
+						&text[offset(first_end)..offset(second_start)],
+						&text[offset(first_start)..offset(first_end)],
+					]
+					.concat();
+					let range = offset(first_start)..offset(second_end);
+					self.replace_and_render(range, &swapped, term)?;
+				}
+			}
+			EditAction::UppercaseWord | EditAction::LowercaseWord | EditAction::CapitalizeWord => {
+				let start = self.line.offset(self.line_cursor_grapheme);
+				let end = self
+					.line
+					.offset(self.word_end(self.line_cursor_grapheme, true));
+				let word = &self.line.as_str()[start..end];
+				let changed = match action {
+					EditAction::UppercaseWord => word.to_uppercase(),
+					EditAction::LowercaseWord => word.to_lowercase(),
+					_ => {
+						let mut first = true;
+						let mut capitalized = String::new();
+						for c in word.chars() {
+							match first && c.is_alphanumeric() {
+								true => capitalized.extend(c.to_uppercase()),
+								false => capitalized.extend(c.to_lowercase()),
 							}
+							first &= !c.is_alphanumeric();
 						}
+						capitalized
 					}
-					self.render(term)?;
+				};
+				self.replace_and_render(start..end, &changed, term)?;
+			}
+			EditAction::Yank => {
+				let pos = self.line.offset(self.line_cursor_grapheme);
+				match self.kill_ring.yank(pos) {
+					Some(text) => {
+						let text = text.to_owned();
+						self.replace_and_render(pos..pos, &text, term)?;
+					}
+					None => self.bell(term)?,
+				}
+			}
+			EditAction::YankPop => match self.kill_ring.yank_pop() {
+				Some((range, text)) => {
+					let text = text.to_owned();
+					self.replace_and_render(range, &text, term)?;
 				}
-				_ => {}
+				None => self.bell(term)?,
 			},
-			Event::Resize(x, y) => {
-				self.term_size = (x, y);
-				self.clear_and_render(term)?;
+			EditAction::HistoryPrevious => {
+				// search for next history item, replace line if found.
+				if let Some(line) = history.search_next(self.line.as_str()) {
+					self.line.set(line.into());
+					self.clear(term)?;
+					self.move_cursor(100000)?;
+					self.render(term)?;
+				} else {
+					self.bell(term)?;
+				}
+			}
+			EditAction::HistoryNext => {
+				// search for next history item, replace line if found.
+				if let Some(line) = history.search_previous(self.line.as_str()) {
+					self.line.set(line.into());
+					self.clear(term)?;
+					self.move_cursor(100000)?;
+					self.render(term)?;
+				} else {
+					self.bell(term)?;
+				}
+			}
+			EditAction::ReverseSearch => self.start_search(false, term, history)?,
+			EditAction::ForwardSearch => self.start_search(true, term, history)?,
+			EditAction::Undo | EditAction::Redo => {
+				let current = (self.line.as_str().to_owned(), self.line_cursor_grapheme);
+				let restored = match action {
+					EditAction::Undo => self.undo.undo(current),
+					_ => self.undo.redo(current),
+				};
+				match restored {
+					Some(snapshot) => self.restore(snapshot, term)?,
+					None => self.bell(term)?,
+				}
+			}
+			EditAction::Complete => self.complete(term)?,
+			EditAction::ToggleOverwrite => {
+				self.overwrite = !self.overwrite;
+				let style = self.shown_cursor_style();
+				term.queue(style.unwrap_or(cursor::SetCursorStyle::DefaultUserShape))?;
 			}
-			_ => {}
 		}
 		Ok(None)
 	}
+	/// Print the prompt, or the transient prompt if set, and the input above
+	/// the input line, to keep it on screen once it is submitted or cancelled
+	fn echo(&mut self, term: &mut impl Write) -> Result<(), ReadlineError> {
+		let (prompt, start) = match &self.transient_prompt {
+			Some(prompt) => (
+				self.theme.prompt.apply(prompt).to_string(),
+				self.end_of(prompt),
+			),
+			None => (self.styled_prompt(), self.prompt_end()),
+		};
+		let mut wraps = self.lay_out(start).wraps;
+		// Nothing is typed after the echoed input
+		wraps.retain(|&offset| offset < self.line.as_str().len());
+		let line = self.styled_line(&wraps);
+		let echo = format!("{prompt}{line}\n");
+		self.print(&self.color_support.downgrade(&echo), term)
+	}
+	/// Put back the input and cursor from before a change
+	fn restore(&mut self, (line, cursor): Snapshot, term: &mut impl Write) -> io::Result<()> {
+		self.clear(term)?;
+		self.line.set(line);
+		self.line_cursor_grapheme = cursor.min(self.line.count());
+		if let Some(vi) = &self.vi {
+			// On the last character in normal mode
+			if vi.mode == ViMode::Normal && self.line_cursor_grapheme == self.line.count() {
+				self.line_cursor_grapheme = self.line_cursor_grapheme.saturating_sub(1);
+			}
+		}
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	/// Replace the byte `range` of the input with `text` and put the cursor
+	/// after it
+	fn replace_and_render(
+		&mut self,
+		range: Range<usize>,
+		text: &str,
+		term: &mut impl Write,
+	) -> io::Result<()> {
+		self.clear(term)?;
+		let end = range.start + text.len();
+		self.line.replace(range, text);
+		self.line_cursor_grapheme = match end < self.line.as_str().len() {
+			true => self.line.index(end),
+			false => self.line.count(),
+		};
+		self.move_cursor(0)?;
+		self.render(term)
+	}
+	/// Insert a typed character at the cursor, or put it in place of the one
+	/// under the cursor in overwrite mode
+	fn insert_char(
+		&mut self,
+		c: char,
+		term: &mut impl Write,
+		history: &History,
+	) -> Result<(), ReadlineError> {
+		if c == ' ' && self.history_expansion.on_space {
+			self.expand_before_space(history, term)?;
+		}
+		if let Some(vi) = &mut self.vi {
+			vi.record(c);
+		}
+		let from = self.current_column;
+		let count = self.line.count();
+		let pos = self.line.offset(self.line_cursor_grapheme);
+		// Combining characters join the grapheme before the cursor instead of
+		// overwriting, and the cursor stays
+		let end = match self.overwrite && count > self.line_cursor_grapheme && c.width() != Some(0)
+		{
+			true => self.line.offset(self.line_cursor_grapheme + 1),
+			false => pos,
+		};
+		self.line.replace(pos..end, c.encode_utf8(&mut [0; 4]));
+		self.move_cursor((self.line.count() + (end > pos) as usize > count) as isize)?;
+		self.update_line(from, term)?;
+		Ok(())
+	}
+}
+
+/// Vi mode shown and terminal width the prompt was measured for, and where
+/// it ends
+type MeasuredPrompt = (Option<ViMode>, u16, (usize, usize));
+
+/// Where the rows of the input start, for one version of it
+#[derive(Default)]
+struct Layout {
+	// Version of the input, start of the input and terminal width it was
+	// laid out for
+	key: Option<(u64, (usize, usize), u16)>,
+	// Index of the first grapheme on each row and its position, in order
+	rows: Vec<(usize, usize)>,
+	// Byte offsets where the input wraps onto the continuation prompt
+	wraps: Vec<usize>,
+}
+
+impl Layout {
+	/// Position of the grapheme at `index`, from the start of its row
+	fn position(&self, line: &InputLine, index: usize) -> usize {
+		let row = self.rows.partition_point(|&(start, _)| start <= index) - 1;
+		let (start, pos) = self.rows[row];
+		pos + line.width_to(index) - line.width_to(start)
+	}
+}
+
+/// Position of the start of the row after a newline at `pos`, on a row
+/// starting at `row_start`
+fn next_row(pos: usize, row_start: usize, width: usize) -> usize {
+	// A row that was just filled already left the cursor at the start of the
+	// next one
+	match pos.is_multiple_of(width) && pos != row_start {
+		true => pos,
+		false => (pos / width + 1) * width,
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_finish() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut term = Vec::new();
+	state.finish(&mut term).unwrap();
+	assert!(!term.contains(&b'\n'));
+
+	state.line.set("unfinished".into());
+	let mut term = Vec::new();
+	state.finish(&mut term).unwrap();
+	assert!(term.contains(&b'\n'));
+
+	state.should_print_line_on_control_c = false;
+	let mut term = Vec::new();
+	state.finish(&mut term).unwrap();
+	assert!(!term.contains(&b'\n'));
+}
+
+#[cfg(test)]
+#[test]
+fn test_scroll_region() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut term = Vec::new();
+	state
+		.set_render_mode(RenderMode::ScrollRegion, &mut term)
+		.unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("\x1b[1;23r"));
+
+	// Output is written at the saved position without erasing the prompt
+	let mut term = Vec::new();
+	state.print("hello\n", &mut term).unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.contains("\x1b8hello\n"));
+	assert!(!term.contains("\x1b[J"));
+
+	// Wrapping input reserves another row
+	state.line.set("x".repeat(100));
+	let mut term = Vec::new();
+	state.render(&mut term).unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("\x1b[1;22r"));
+
+	let mut term = Vec::new();
+	state
+		.set_render_mode(RenderMode::Redraw, &mut term)
+		.unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("\x1b[r"));
+	assert_eq!(state.region_rows, 0);
+}
+
+#[cfg(test)]
+#[test]
+fn test_status_lines() {
+	let mut state = LineState::new("> ".into(), (10, 24));
+	let mut term = Vec::new();
+	state
+		.set_status_lines(vec!["connected to example.com".into()], &mut term)
+		.unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.contains("connected "));
+	assert!(!term.contains("connected t"));
+	assert_eq!(state.rows(), 2);
+
+	state.set_status_lines(vec![], &mut Vec::new()).unwrap();
+	assert_eq!(state.rows(), 1);
+}
+
+#[cfg(test)]
+#[test]
+fn test_toast() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	state
+		.set_status_lines(vec!["status".into()], &mut Vec::new())
+		.unwrap();
+	let mut term = Vec::new();
+	state.set_toast(Some("copied".into()), &mut term).unwrap();
+	let term = String::from_utf8(term).unwrap();
+	// The toast goes between the input and the status lines
+	assert!(term.find("copied").unwrap() < term.find("status").unwrap());
+	assert_eq!(state.rows(), 3);
+
+	let mut term = Vec::new();
+	state.set_toast(None, &mut term).unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(!term.contains("copied"));
+	assert!(term.contains("status"));
+	assert_eq!(state.rows(), 2);
+}
+
+#[cfg(test)]
+#[test]
+fn test_clear_behavior() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut clear = |behavior| {
+		state.clear_behavior = behavior;
+		let mut term = Vec::new();
+		state.clear_screen(&mut term).unwrap();
+		String::from_utf8(term).unwrap()
+	};
+	assert!(!clear(ClearBehavior::RedrawPrompt).contains("\x1b[2J"));
+	assert!(clear(ClearBehavior::ClearAll).contains("\x1b[2J\x1b[1;1H"));
+	assert!(clear(ClearBehavior::ClearWithScrollback).contains("\x1b[3J"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_bell() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut history = History::default();
+	let backspace = Event::Key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+
+	let mut term = Vec::new();
+	state
+		.handle_event(backspace.clone(), &mut term, &mut history)
+		.unwrap();
+	assert!(!term.contains(&b'\x07'));
+
+	state.bell_style = BellStyle::Audible;
+	let mut term = Vec::new();
+	state
+		.handle_event(backspace.clone(), &mut term, &mut history)
+		.unwrap();
+	assert!(term.contains(&b'\x07'));
+
+	state.bell_style = BellStyle::Visible;
+	state
+		.handle_event(backspace, &mut Vec::new(), &mut history)
+		.unwrap();
+	assert!(state.is_flashing());
+	state.stop_flashing(&mut Vec::new()).unwrap();
+	assert!(!state.is_flashing());
+}
+
+#[cfg(test)]
+#[test]
+fn test_cursor_hidden_while_printing() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut term = Vec::new();
+	state.print("output\n", &mut term).unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.starts_with("\x1b[?25l"));
+	assert!(term.ends_with("\x1b[?25h"));
+
+	state.cursor_hidden = true;
+	let mut term = Vec::new();
+	state.print("output\n", &mut term).unwrap();
+	assert!(!String::from_utf8(term).unwrap().contains("\x1b[?25h"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_flow_control() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut history = History::default();
+	let ctrl_s = Event::Key(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+	let ctrl_q = Event::Key(KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL));
+
+	// Off by default, Ctrl-S searches the history instead
+	state
+		.handle_event(ctrl_s.clone(), &mut Vec::new(), &mut history)
+		.unwrap();
+	assert!(!state.is_frozen());
+	assert!(state.search.is_some());
+	let esc = Event::Key(KeyEvent::from(KeyCode::Esc));
+	state
+		.handle_event(esc, &mut Vec::new(), &mut history)
+		.unwrap();
+
+	state.flow_control = true;
+	state
+		.handle_event(ctrl_s, &mut Vec::new(), &mut history)
+		.unwrap();
+	assert!(state.is_frozen());
+	let mut term = Vec::new();
+	state.set_frozen(Some(3), &mut term).unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("3 lines pending"));
+	state
+		.handle_event(ctrl_q, &mut Vec::new(), &mut history)
+		.unwrap();
+	assert!(!state.is_frozen());
+}
+
+#[cfg(test)]
+#[test]
+fn test_redraw_throttling() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	state.min_redraw_interval = Duration::from_secs(60);
+	state.render(&mut Vec::new()).unwrap();
+
+	// The prompt is not drawn again right after the first render
+	let mut term = Vec::new();
+	state.print("output\n", &mut term).unwrap();
+	assert!(!String::from_utf8(term).unwrap().contains("> "));
+	assert!(state.redraw_deadline().is_some());
+
+	// Another batch doesn't try to erase the missing prompt
+	let mut term = Vec::new();
+	state.print("more\n", &mut term).unwrap();
+	assert!(!String::from_utf8(term).unwrap().contains("\x1b[1A"));
+
+	let mut term = Vec::new();
+	state.redraw(&mut term).unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("> "));
+	assert!(state.redraw_deadline().is_none());
+}
+
+#[cfg(test)]
+#[test]
+fn test_synchronized_output() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	state.synchronized_output = true;
+	let mut term = Vec::new();
+	state.print("output\n", &mut term).unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.starts_with("\x1b[?2026h"));
+	assert!(term.ends_with("\x1b[?2026l"));
+
+	// Enter prints the line and draws the prompt again in a single update
+	let mut history = History::default();
+	state.line.set("input".into());
+	let mut term = Vec::new();
+	state
+		.handle_event(
+			Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE)),
+			&mut term,
+			&mut history,
+		)
+		.unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert_eq!(term.matches("\x1b[?2026h").count(), 1);
+	assert!(term.ends_with("\x1b[?2026l"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_incremental_render() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut history = History::default();
+	let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+	state.render(&mut Vec::new()).unwrap();
+
+	// Typing at the end only writes the new character
+	let mut term = Vec::new();
+	state
+		.handle_event(key(KeyCode::Char('a')), &mut term, &mut history)
+		.unwrap();
+	state
+		.handle_event(key(KeyCode::Char('日')), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(String::from_utf8(term).unwrap(), "a日");
+
+	// Erasing at the end moves back and clears the rest of the row
+	let mut term = Vec::new();
+	state
+		.handle_event(key(KeyCode::Backspace), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term, b"\x1b[2D\x1b[K");
+
+	// Edits in the middle redraw the line
+	state
+		.handle_event(key(KeyCode::Left), &mut Vec::new(), &mut history)
+		.unwrap();
+	let mut term = Vec::new();
+	state
+		.handle_event(key(KeyCode::Char('b')), &mut term, &mut history)
+		.unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("\x1b[J> ba"));
+
+	// Turning it off always redraws
+	state
+		.handle_event(key(KeyCode::End), &mut Vec::new(), &mut history)
+		.unwrap();
+	let mut term = Vec::new();
+	state.incremental_render = false;
+	state
+		.handle_event(key(KeyCode::Char('c')), &mut term, &mut history)
+		.unwrap();
+	assert!(String::from_utf8(term).unwrap().contains("\x1b[J> bac"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_word_editing() {
+	let mut state = LineState::new("> ".into(), (80, 24));
+	let mut history = History::default();
+	let ctrl = |c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+	let ctrl_left = Event::Key(KeyEvent::new(KeyCode::Left, KeyModifiers::CONTROL));
+	state.line.set("héllo wörld".into());
+	state.move_cursor(100).unwrap();
+
+	state
+		.handle_event(ctrl_left, &mut Vec::new(), &mut history)
+		.unwrap();
+	assert_eq!(state.line_cursor_grapheme, 6);
+	state
+		.handle_event(ctrl('w'), &mut Vec::new(), &mut history)
+		.unwrap();
+	assert_eq!(state.line.as_str(), "wörld");
+	assert_eq!(state.current_column, 2);
+}
+
+#[cfg(test)]
+#[test]
+fn test_partial_line_wrapping() {
+	let mut state = LineState::new("> ".into(), (10, 24));
+
+	// A partial line spanning two rows continues on the second one
+	let mut term = Vec::new();
+	state.print("12345678901234", &mut term).unwrap();
+	assert_eq!(state.last_line_column, 4);
+	assert_eq!(term.iter().filter(|b| **b == b'\n').count(), 1);
+
+	let mut term = Vec::new();
+	state.print("\x1b[31mabc\x1b[0mdef", &mut term).unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.contains("\x1b[1A\x1b[5G\x1b[31mabc"));
+	// The row is exactly full, so the next chunk starts on a new one
+	assert_eq!(state.last_line_column, 0);
+	assert_eq!(term.matches('\n').count(), 2);
+
+	let mut term = Vec::new();
+	state.print("xyz\n", &mut term).unwrap();
+	assert!(String::from_utf8(term)
+		.unwrap()
+		.contains("\x1b[1A\x1b[1Gxyz\n"));
+	assert!(state.last_line_completed);
+}
+
+#[cfg(test)]
+#[test]
+fn test_resize() {
+	let mut state = LineState::new("> ".into(), (10, 24));
+	let mut history = History::default();
+	state.line.set("x".repeat(25));
+	state.move_cursor(100).unwrap();
+	state.render(&mut Vec::new()).unwrap();
+
+	// Narrower: the reflowed input takes up more rows than before
+	let mut term = Vec::new();
+	state
+		.handle_event(Event::Resize(5, 24), &mut term, &mut history)
+		.unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.starts_with("\x1b[1G\x1b[5A\x1b[J> "));
+	assert_eq!(term.matches("> ").count(), 1);
+
+	// Wider again: the old layout is still the taller one
+	let mut term = Vec::new();
+	state
+		.handle_event(Event::Resize(10, 24), &mut term, &mut history)
+		.unwrap();
+	let term = String::from_utf8(term).unwrap();
+	assert!(term.starts_with("\x1b[1G\x1b[5A\x1b[J> "));
+	assert_eq!(term.matches("> ").count(), 1);
+
+	// Narrower than the prompt
+	let mut term = Vec::new();
+	state
+		.handle_event(Event::Resize(1, 24), &mut term, &mut history)
+		.unwrap();
+	assert!(String::from_utf8(term)
+		.unwrap()
+		.starts_with("\x1b[1G\x1b[27A\x1b[J> "));
+}
+
+// Type `text` and return the screen afterwards
+#[cfg(test)]
+fn type_text(state: &mut LineState, term: &mut VirtualTerminal, text: &str) -> Vec<String> {
+	let mut history = History::default();
+	for c in text.chars() {
+		let key = Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+		state.handle_event(key, term, &mut history).unwrap();
+	}
+	term.screen_contents()
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_colored_prompt() {
+	let mut term = VirtualTerminal::new(10, 3);
+	let mut state = LineState::new("\x1b[32m>\x1b[0m ".into(), (10, 3));
+	state.render(&mut term).unwrap();
+	assert_eq!(term.cursor_pos(), (2, 0));
+	assert_eq!(type_text(&mut state, &mut term, "hi"), ["> hi", "", ""]);
+	assert_eq!(term.cursor_pos(), (4, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_styled_prompt() {
+	use crossterm::style::{Color, Stylize};
+
+	let mut term = VirtualTerminal::new(20, 3);
+	let prompt = Prompt::new()
+		.styled("~/src".with(Color::Rgb { r: 0, g: 0, b: 130 }).bold())
+		.plain(" > ");
+	let mut state = LineState::new(prompt, (20, 3));
+	state.color_support = ColorSupport::Basic;
+	assert_eq!(
+		state.styled_prompt(),
+		format!("{} > ", "~/src".dark_blue().bold())
+	);
+	state.render(&mut term).unwrap();
+	assert_eq!(term.cursor_pos(), (8, 0));
+	assert_eq!(
+		type_text(&mut state, &mut term, "hi"),
+		["~/src > hi", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (10, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_wrapping() {
+	let mut term = VirtualTerminal::new(10, 3);
+	let mut state = LineState::new("> ".into(), (10, 3));
+	state.render(&mut term).unwrap();
+	// Exactly filling the row puts the cursor at the start of the next one
+	assert_eq!(
+		type_text(&mut state, &mut term, "abcdefgh"),
+		["> abcdefgh", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (0, 1));
+	assert_eq!(
+		type_text(&mut state, &mut term, "ij"),
+		["> abcdefgh", "ij", ""]
+	);
+	assert_eq!(term.cursor_pos(), (2, 1));
+
+	let mut history = History::default();
+	for code in [
+		KeyCode::Backspace,
+		KeyCode::Backspace,
+		KeyCode::Backspace,
+		KeyCode::Home,
+	] {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state.handle_event(key, &mut term, &mut history).unwrap();
+	}
+	assert_eq!(term.screen_contents(), ["> abcdefg", "", ""]);
+	assert_eq!(term.cursor_pos(), (2, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_move_across_wraps() {
+	let mut term = VirtualTerminal::new(10, 3);
+	let mut state = LineState::new("> ".into(), (10, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	assert_eq!(
+		type_text(&mut state, &mut term, "abcdefghijklmnopqrstuvwxy"),
+		["> abcdefgh", "ijklmnopqr", "stuvwxy"]
+	);
+	assert_eq!(term.cursor_pos(), (7, 2));
+	let mut press = |code, times| {
+		for _ in 0..times {
+			let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+			state.handle_event(key, &mut term, &mut history).unwrap();
+		}
+		term.cursor_pos()
+	};
+	assert_eq!(press(KeyCode::Home, 1), (2, 0));
+	assert_eq!(press(KeyCode::Right, 7), (9, 0));
+	assert_eq!(press(KeyCode::Right, 1), (0, 1));
+	assert_eq!(press(KeyCode::Left, 1), (9, 0));
+	assert_eq!(press(KeyCode::Right, 12), (1, 2));
+	assert_eq!(press(KeyCode::Left, 2), (9, 1));
+	assert_eq!(press(KeyCode::End, 1), (7, 2));
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_multiline_entry() {
+	let mut term = VirtualTerminal::new(10, 6);
+	let mut state = LineState::new("> ".into(), (10, 6));
+	let mut history = History::default();
+	history.set_entries(["abcdefgh\nx", "echo a\nbcdefghijklm"]);
+	state.render(&mut term).unwrap();
+	let mut press = |code, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	press(KeyCode::Up, &mut term);
+	assert_eq!(
+		term.screen_contents(),
+		["> echo a", "bcdefghijk", "lm", "", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (2, 2));
+	press(KeyCode::Home, &mut term);
+	assert_eq!(term.cursor_pos(), (2, 0));
+
+	// A row filled right before the newline isn't followed by an empty one
+	press(KeyCode::Up, &mut term);
+	assert_eq!(term.screen_contents(), ["> abcdefgh", "x", "", "", "", ""]);
+	assert_eq!(term.cursor_pos(), (1, 1));
+	let event = press(KeyCode::Enter, &mut term);
+	assert_eq!(event, Some(ReadlineEvent::Line("abcdefgh\nx".into())));
+	assert_eq!(term.screen_contents(), ["> abcdefgh", "x", ">", "", "", ""]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_multiline_prompt() {
+	let mut term = VirtualTerminal::new(10, 8);
+	let mut state = LineState::new("\x1b[1m~/src\x1b[0m\n> ".into(), (10, 8));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	assert_eq!(
+		term.screen_contents(),
+		["~/src", ">", "", "", "", "", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (2, 1));
+	let mut press = |state: &mut LineState, code, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	for c in "echo hello".chars() {
+		press(&mut state, KeyCode::Char(c), &mut term);
+	}
+	assert_eq!(
+		term.screen_contents(),
+		["~/src", "> echo hel", "lo", "", "", "", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (2, 2));
+	press(&mut state, KeyCode::Home, &mut term);
+	assert_eq!(term.cursor_pos(), (2, 1));
+
+	// Output goes above both rows of the prompt
+	state.print_data(b"output\n", &mut term).unwrap();
+	assert_eq!(
+		term.screen_contents(),
+		["output", "~/src", "> echo hel", "lo", "", "", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (2, 2));
+	press(&mut state, KeyCode::Enter, &mut term);
+	assert_eq!(
+		term.screen_contents(),
+		["output", "~/src", "> echo hel", "lo", "~/src", ">", "", ""]
+	);
+
+	// A filled row and a newline at the end of the prompt
+	state
+		.update_prompt("0123456789\n".into(), &mut term)
+		.unwrap();
+	assert_eq!(
+		term.screen_contents(),
+		[
+			"output",
+			"~/src",
+			"> echo hel",
+			"lo",
+			"0123456789",
+			"",
+			"",
+			""
+		]
+	);
+	assert_eq!(term.cursor_pos(), (0, 5));
+}
+
+#[cfg(test)]
+#[test]
+fn test_prompt_provider() {
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	let unread = Arc::new(AtomicUsize::new(0));
+	let shared = unread.clone();
+	state.prompt_provider = Some(Box::new(move || {
+		format!("[{}] > ", shared.load(Ordering::Relaxed))
+	}));
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "reply");
+	assert_eq!(term.screen_contents(), ["[0] > reply", "", "", ""]);
+
+	// Picked up when drawn after output, with the cursor placed for it
+	unread.store(12, Ordering::Relaxed);
+	state.print_data(b"message\n", &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["message", "[12] > reply", "", ""]);
+	assert_eq!(term.cursor_pos(), (12, 1));
+}
+
+#[cfg(test)]
+#[test]
+fn test_continuation_prompt() {
+	let mut term = VirtualTerminal::new(12, 10);
+	let mut state = LineState::new(">>> ".into(), (12, 10));
+	let mut history = History::default();
+	history.set_entries(["for x in y:\n  print(x)\n"]);
+	state.render(&mut term).unwrap();
+	state
+		.set_continuation_prompt("... ".into(), &mut term)
+		.unwrap();
+	let mut press = |state: &mut LineState, code, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	press(&mut state, KeyCode::Up, &mut term);
+	assert_eq!(
+		term.screen_contents(),
+		[
+			">>> for x in",
+			"...  y:",
+			"...   print(",
+			"... x)",
+			"...",
+			"",
+			"",
+			"",
+			"",
+			""
+		]
+	);
+	assert_eq!(term.cursor_pos(), (4, 4));
+	press(&mut state, KeyCode::Backspace, &mut term);
+	press(&mut state, KeyCode::Left, &mut term);
+	assert_eq!(
+		term.screen_contents(),
+		[
+			">>> for x in",
+			"...  y:",
+			"...   print(",
+			"... x)",
+			"",
+			"",
+			"",
+			"",
+			"",
+			""
+		]
+	);
+	assert_eq!(term.cursor_pos(), (5, 3));
+
+	// Kept when the entry is echoed
+	press(&mut state, KeyCode::Enter, &mut term);
+	assert_eq!(
+		term.screen_contents(),
+		[
+			">>> for x in",
+			"...  y:",
+			"...   print(",
+			"... x)",
+			">>>",
+			"",
+			"",
+			"",
+			"",
+			""
+		]
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_continuation_prompt_wrapping() {
+	let mut term = VirtualTerminal::new(10, 8);
+	let mut state = LineState::new("> ".into(), (10, 8));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	state
+		.set_continuation_prompt(". ".into(), &mut term)
+		.unwrap();
+	let mut press = |state: &mut LineState, code, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	for c in "abcdefghijklmnop".chars() {
+		press(&mut state, KeyCode::Char(c), &mut term);
+	}
+	// The cursor after a filled row waits on the next one
+	assert_eq!(
+		term.screen_contents(),
+		["> abcdefgh", ". ijklmnop", ".", "", "", "", "", ""]
+	);
+	assert_eq!(term.cursor_pos(), (2, 2));
+	press(&mut state, KeyCode::Home, &mut term);
+	assert_eq!(term.cursor_pos(), (2, 0));
+	for _ in 0..8 {
+		press(&mut state, KeyCode::Right, &mut term);
+	}
+	assert_eq!(term.cursor_pos(), (2, 1));
+	press(&mut state, KeyCode::Left, &mut term);
+	assert_eq!(term.cursor_pos(), (9, 0));
+	press(&mut state, KeyCode::Right, &mut term);
+
+	press(&mut state, KeyCode::Enter, &mut term);
+	assert_eq!(
+		term.screen_contents(),
+		["> abcdefgh", ". ijklmnop", ">", "", "", "", "", ""]
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_transient_prompt() {
+	let mut term = VirtualTerminal::new(20, 6);
+	let mut state = LineState::new("~/src\n> ".into(), (20, 6));
+	let mut history = History::default();
+	state.transient_prompt = Some("❯ ".into());
+	state.render(&mut term).unwrap();
+	let mut press = |state: &mut LineState, code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	for c in "ls".chars() {
+		press(&mut state, KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	press(&mut state, KeyCode::Enter, KeyModifiers::NONE, &mut term);
+	assert_eq!(term.screen_contents(), ["❯ ls", "~/src", ">", "", "", ""]);
+	press(
+		&mut state,
+		KeyCode::Char('x'),
+		KeyModifiers::NONE,
+		&mut term,
+	);
+	press(
+		&mut state,
+		KeyCode::Char('c'),
+		KeyModifiers::CONTROL,
+		&mut term,
+	);
+	assert_eq!(
+		term.screen_contents(),
+		["❯ ls", "❯ x", "~/src", ">", "", ""]
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_print_data() {
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "input");
+
+	state.print_data(b"first\n", &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["first", "> input", "", ""]);
+	assert_eq!(term.cursor_pos(), (7, 1));
+
+	// Partial lines are continued by the next output
+	state.print_data(b"second", &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["first", "second", "> input", ""]);
+	state.print_data(b" line\nthird\n", &mut term).unwrap();
+	assert_eq!(
+		term.screen_contents(),
+		["first", "second line", "third", "> input"]
+	);
+	assert_eq!(term.cursor_pos(), (7, 3));
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_ctrl_c() {
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "abandoned");
+	let ctrl_c = Event::Key(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL));
+	state.handle_event(ctrl_c, &mut term, &mut history).unwrap();
+	assert_eq!(term.screen_contents(), ["> abandoned", ">", "", ""]);
+	assert_eq!(term.cursor_pos(), (2, 1));
+
+	// Output goes below the abandoned input
+	state.print_data(b"output\n", &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["> abandoned", "output", ">", ""]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_render_resize() {
+	let mut term = VirtualTerminal::new(10, 4);
+	let mut state = LineState::new("> ".into(), (10, 4));
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "abcdefghijkl");
+	assert_eq!(term.screen_contents(), ["> abcdefgh", "ijkl", "", ""]);
+
+	term.resize(6, 4);
+	state.resize((6, 4), &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["> abcd", "efghij", "kl", ""]);
+	assert_eq!(term.cursor_pos(), (2, 2));
+
+	term.resize(20, 4);
+	state.resize((20, 4), &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["> abcdefghijkl", "", "", ""]);
+	assert_eq!(term.cursor_pos(), (14, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_completion() {
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	let mut history = History::default();
+	state.completer = Some(CompletionSource::Sync(Box::new(
+		|line: &str, pos: usize| {
+			let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+			let candidates = ["help", "hello", "quit"]
+				.into_iter()
+				.filter(|c| c.starts_with(&line[start..pos]))
+				.map(Candidate::from)
+				.collect();
+			(start, candidates)
+		},
+	)));
+	let tab = Event::Key(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+	state.render(&mut term).unwrap();
+
+	// A single candidate is inserted
+	type_text(&mut state, &mut term, "q");
+	state
+		.handle_event(tab.clone(), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term.screen_contents()[0], "> quit");
+
+	// Several insert what they share, then get shown in a menu
+	type_text(&mut state, &mut term, " h");
+	state
+		.handle_event(tab.clone(), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term.screen_contents()[0], "> quit hel");
+	state
+		.handle_event(tab.clone(), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term.screen_contents(), ["> quit hel", "help", "hello", ""]);
+	assert_eq!(term.cursor_pos(), (10, 0));
+
+	// Other keys close it, text after the cursor stays after it
+	let home = Event::Key(KeyEvent::new(KeyCode::Home, KeyModifiers::NONE));
+	state.handle_event(home, &mut term, &mut history).unwrap();
+	type_text(&mut state, &mut term, "q");
+	state.handle_event(tab, &mut term, &mut history).unwrap();
+	assert_eq!(term.screen_contents(), ["> quitquit hel", "", "", ""]);
+	assert_eq!(term.cursor_pos(), (6, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_completion_menu() {
+	let mut term = VirtualTerminal::new(20, 5);
+	let mut state = LineState::new("> ".into(), (20, 5));
+	let mut history = History::default();
+	let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "x");
+	let candidates = ["one", "two", "three"].map(Candidate::from);
+	state.apply_completion(1, &candidates, &mut term).unwrap();
+	assert_eq!(term.screen_contents(), ["> x", "one", "two", "three", ""]);
+
+	// Output goes above it
+	state.print_data(b"output\n", &mut term).unwrap();
+	assert_eq!(
+		term.screen_contents(),
+		["output", "> x", "one", "two", "three"]
+	);
+
+	// Tab and the arrow keys cycle, Enter inserts the selection
+	for code in [KeyCode::Tab, KeyCode::Down, KeyCode::Up, KeyCode::Up] {
+		state
+			.handle_event(key(code), &mut term, &mut history)
+			.unwrap();
+	}
+	let event = state.handle_event(key(KeyCode::Enter), &mut term, &mut history);
+	assert_eq!(event.unwrap(), None);
+	assert_eq!(term.screen_contents(), ["output", "> xthree", "", "", ""]);
+	assert_eq!(term.cursor_pos(), (8, 1));
+
+	// Esc closes it without changing the input
+	state.apply_completion(1, &candidates, &mut term).unwrap();
+	state
+		.handle_event(key(KeyCode::Tab), &mut term, &mut history)
+		.unwrap();
+	state
+		.handle_event(key(KeyCode::Esc), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term.screen_contents(), ["output", "> xthree", "", "", ""]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_completion_descriptions() {
+	let mut term = VirtualTerminal::new(24, 4);
+	let mut state = LineState::new("> ".into(), (24, 4));
+	state.render(&mut term).unwrap();
+	let candidates = [
+		Candidate::new("run").with_description("Run a binary"),
+		Candidate::new("rustc")
+			.with_label("rustc <args>")
+			.with_description("Compile"),
+	];
+	// The shared prefix is inserted first
+	state.apply_completion(0, &candidates, &mut term).unwrap();
+	state.apply_completion(0, &candidates, &mut term).unwrap();
+	// Descriptions line up, and are clipped to the width
+	assert_eq!(
+		term.screen_contents(),
+		[
+			"> ru",
+			"run           Run a bina",
+			"rustc <args>  Compile",
+			""
+		]
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_completion_list() {
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	let mut history = History::default();
+	state.completion_style = CompletionStyle::List;
+	state.completer = Some(CompletionSource::Sync(Box::new(|_: &str, _| {
+		(0, ["cat", "cd", "cp"].map(Candidate::from).into())
+	})));
+	let key = |code| Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "c");
+
+	// Only the second Tab in a row lists the candidates
+	state
+		.handle_event(key(KeyCode::Tab), &mut term, &mut history)
+		.unwrap();
+	state
+		.handle_event(key(KeyCode::Left), &mut term, &mut history)
+		.unwrap();
+	state
+		.handle_event(key(KeyCode::Right), &mut term, &mut history)
+		.unwrap();
+	state
+		.handle_event(key(KeyCode::Tab), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term.screen_contents(), ["> c", "", "", ""]);
+	state
+		.handle_event(key(KeyCode::Tab), &mut term, &mut history)
+		.unwrap();
+	assert_eq!(term.screen_contents(), ["cat  cd   cp", "> c", "", ""]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_autosuggestions() {
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	let mut history = History::default();
+	history.set_entries(["cargo test --workspace", "cargo build"]);
+	state.autosuggest = true;
+	state.render(&mut term).unwrap();
+	let mut type_key = |state: &mut LineState, term: &mut VirtualTerminal, code| {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state.handle_event(key, term, &mut history).unwrap();
+		term.screen_contents()[0].clone()
+	};
+
+	// The newest match is shown after the cursor, clipped to the row
+	type_key(&mut state, &mut term, KeyCode::Char('c'));
+	assert_eq!(term.screen_contents()[0], "> cargo build");
+	assert_eq!(term.cursor_pos(), (3, 0));
+	for c in "argo t".chars() {
+		type_key(&mut state, &mut term, KeyCode::Char(c));
+	}
+	assert_eq!(term.screen_contents()[0], "> cargo test --work");
+	assert_eq!(term.cursor_pos(), (9, 0));
+
+	// It goes away when the input stops matching or the cursor moves
+	let row = type_key(&mut state, &mut term, KeyCode::Char('x'));
+	assert_eq!(row, "> cargo tx");
+	type_key(&mut state, &mut term, KeyCode::Backspace);
+	let row = type_key(&mut state, &mut term, KeyCode::Left);
+	assert_eq!(row, "> cargo t");
+	assert_eq!(
+		type_key(&mut state, &mut term, KeyCode::Right),
+		"> cargo test --work"
+	);
+
+	// Right or End accepts it
+	type_key(&mut state, &mut term, KeyCode::End);
+	assert_eq!(state.line.as_str(), "cargo test --workspace");
+	assert_eq!(
+		term.screen_contents()[..2],
+		["> cargo test --works", "pace"]
+	);
+	assert_eq!(term.cursor_pos(), (4, 1));
+}
+
+#[cfg(test)]
+#[test]
+fn test_hinter() {
+	let mut term = VirtualTerminal::new(20, 4);
+	let mut state = LineState::new("> ".into(), (20, 4));
+	state.hinter = Some(Box::new(|line: &str, _| {
+		(line == "cp").then(|| " <source> <destination>".to_string())
+	}));
+	state.render(&mut term).unwrap();
+	type_text(&mut state, &mut term, "cp");
+	assert_eq!(term.screen_contents()[0], "> cp <source> <dest");
+	assert_eq!(term.cursor_pos(), (4, 0));
+	// Cleared when it no longer applies
+	assert_eq!(type_text(&mut state, &mut term, " ")[0], "> cp");
+	assert_eq!(term.cursor_pos(), (5, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_accept_hint_keys() {
+	let mut term = VirtualTerminal::new(30, 4);
+	let mut state = LineState::new("> ".into(), (30, 4));
+	let mut history = History::default();
+	history.set_entries(["cargo test --workspace"]);
+	state.autosuggest = true;
+	state.render(&mut term).unwrap();
+	let mut press = |state: &mut LineState, key| {
+		state
+			.handle_event(Event::Key(key), &mut term, &mut history)
+			.unwrap();
+		state.line.as_str().to_string()
+	};
+	press(&mut state, KeyEvent::from(KeyCode::Char('c')));
+	let alt_right = KeyEvent::new(KeyCode::Right, KeyModifiers::ALT);
+	assert_eq!(press(&mut state, alt_right), "cargo");
+	assert_eq!(press(&mut state, alt_right), "cargo test");
+
+	// Rebound, Right only moves the cursor
+	state.accept_hint_keys = vec![(KeyEvent::from(KeyCode::Tab), AcceptHint::All)];
+	press(&mut state, KeyEvent::from(KeyCode::Left));
+	assert_eq!(
+		press(&mut state, KeyEvent::from(KeyCode::Right)),
+		"cargo test"
+	);
+	assert_eq!(
+		press(&mut state, KeyEvent::from(KeyCode::Tab)),
+		"cargo test --workspace"
+	);
+
+	// Hints are accepted too
+	state.autosuggest = false;
+	state.hinter = Some(Box::new(|line: &str, _| {
+		line.ends_with(" -").then(|| "-release".to_string())
+	}));
+	press(&mut state, KeyEvent::from(KeyCode::Char(' ')));
+	press(&mut state, KeyEvent::from(KeyCode::Char('-')));
+	assert_eq!(
+		press(&mut state, KeyEvent::from(KeyCode::Tab)),
+		"cargo test --workspace --release"
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_expansion() {
+	let mut term = VirtualTerminal::new(30, 4);
+	let mut state = LineState::new("> ".into(), (30, 4));
+	let mut history = History::default();
+	history.set_entries(["git status", "ls -l"]);
+	state.history_expansion = HistoryExpansion {
+		on_enter: true,
+		on_space: true,
+		verify: true,
+	};
+	state.render(&mut term).unwrap();
+	let mut press = |state: &mut LineState, code| {
+		let key = Event::Key(KeyEvent::from(code));
+		state.handle_event(key, &mut term, &mut history).unwrap()
+	};
+	for c in "sudo !! !g".chars() {
+		press(&mut state, KeyCode::Char(c));
+	}
+	assert_eq!(state.line.as_str(), "sudo ls -l !g");
+
+	// Shown for editing first, and returned on the next Enter
+	assert_eq!(press(&mut state, KeyCode::Enter), None);
+	assert_eq!(state.line.as_str(), "sudo ls -l git status");
+	assert_eq!(
+		press(&mut state, KeyCode::Enter),
+		Some(ReadlineEvent::Line("sudo ls -l git status".into()))
+	);
+
+	// Unknown entries ring the bell and keep the input
+	for c in "!cargo".chars() {
+		press(&mut state, KeyCode::Char(c));
+	}
+	assert_eq!(press(&mut state, KeyCode::Enter), None);
+	assert_eq!(state.line.as_str(), "!cargo");
+}
+
+#[cfg(test)]
+#[test]
+fn test_highlighter() {
+	let mut state = LineState::new("> ".into(), (20, 4));
+	state.highlighter = Some(Box::new(|line: &str| {
+		line.replace("SELECT", "\x1b[1mSELECT\x1b[0m")
+	}));
+	let mut term = Vec::new();
+	state.render(&mut term).unwrap();
+	for c in "SELECT 1".chars() {
+		let key = Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+		state
+			.handle_event(key, &mut term, &mut History::default())
+			.unwrap();
+	}
+	let output = String::from_utf8(term).unwrap();
+	assert!(output.ends_with("> \x1b[1mSELECT\x1b[0m 1\x1b[1G\x1b[10C"));
+
+	// The cursor is placed by the visible text
+	let mut term = VirtualTerminal::new(20, 4);
+	state.clear_and_render(&mut term).unwrap();
+	assert_eq!(term.screen_contents()[0], "> SELECT 1");
+	assert_eq!(term.cursor_pos(), (10, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_validator() {
+	let mut state = LineState::new("> ".into(), (20, 4));
+	state.validator = Some(Box::new(|line: &str| match line {
+		"ls" | "l" => Validity::Valid,
+		_ => Validity::Invalid,
+	}));
+	let mut term = Vec::new();
+	state.render(&mut term).unwrap();
+	let mut press = |code, term: &mut Vec<u8>| {
+		let key = Event::Key(KeyEvent::new(code, KeyModifiers::NONE));
+		state
+			.handle_event(key, term, &mut History::default())
+			.unwrap();
+	};
+	press(KeyCode::Char('l'), &mut term);
+	press(KeyCode::Char('x'), &mut term);
+	let output = String::from_utf8(term.clone()).unwrap();
+	assert!(output.ends_with(&format!("> {}\x1b[1G\x1b[4C", "lx".red())));
+
+	term.clear();
+	press(KeyCode::Backspace, &mut term);
+	press(KeyCode::Char('s'), &mut term);
+	let output = String::from_utf8(term).unwrap();
+	assert!(output.ends_with("> ls\x1b[1G\x1b[4C"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_history_search() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	history.set_entries(["cargo build", "git status", "cargo test"]);
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let ctrl = KeyModifiers::CONTROL;
+	for c in "draft".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	press(KeyCode::Char('r'), ctrl, &mut term);
+	for c in "carg".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	assert_eq!(
+		term.screen_contents()[0],
+		"(reverse-i-search)`carg': cargo test"
+	);
+	assert_eq!(term.cursor_pos(), (26, 0));
+	press(KeyCode::Char('r'), ctrl, &mut term);
+	assert_eq!(
+		term.screen_contents()[0],
+		"(reverse-i-search)`carg': cargo build"
+	);
+	press(KeyCode::Char('r'), ctrl, &mut term);
+	assert_eq!(
+		term.screen_contents()[0],
+		"(failing reverse-i-search)`carg': cargo build"
+	);
+	press(KeyCode::Char('s'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "(i-search)`carg': cargo test");
+	press(KeyCode::Esc, KeyModifiers::NONE, &mut term);
+	assert_eq!(term.screen_contents()[0], "> draft");
+
+	// Other keys keep the entry, and Up continues from it
+	press(KeyCode::Char('r'), ctrl, &mut term);
+	press(KeyCode::Char('t'), KeyModifiers::NONE, &mut term);
+	press(KeyCode::Char('u'), KeyModifiers::NONE, &mut term);
+	press(KeyCode::Home, KeyModifiers::NONE, &mut term);
+	assert_eq!(term.screen_contents()[0], "> git status");
+	assert_eq!(term.cursor_pos(), (2, 0));
+	press(KeyCode::Up, KeyModifiers::NONE, &mut term);
+	let event = press(KeyCode::Enter, KeyModifiers::NONE, &mut term);
+	assert_eq!(event, Some(ReadlineEvent::Line("cargo build".into())));
+}
+
+#[cfg(test)]
+#[test]
+fn test_vi_mode() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	history.set_entries(["cargo build"]);
+	state.set_edit_mode(EditMode::Vi).unwrap();
+	state
+		.set_vi_indicators(("[I]".into(), "[N]".into()), &mut term)
+		.unwrap();
+	let mut press = |code, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::from(code));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	for c in "hello world".chars() {
+		press(KeyCode::Char(c), &mut term);
+	}
+	assert_eq!(term.screen_contents()[0], "[I]> hello world");
+	press(KeyCode::Esc, &mut term);
+	assert_eq!(term.screen_contents()[0], "[N]> hello world");
+	assert_eq!(term.cursor_pos(), (15, 0));
+	for c in "bdw".chars() {
+		press(KeyCode::Char(c), &mut term);
+	}
+	assert_eq!(term.screen_contents()[0], "[N]> hello");
+	assert_eq!(term.cursor_pos(), (10, 0));
+	press(KeyCode::Char('k'), &mut term);
+	assert_eq!(term.screen_contents()[0], "[N]> cargo build");
+	assert_eq!(term.cursor_pos(), (15, 0));
+
+	// Enter works in normal mode, and the next input starts in insert mode
+	let event = press(KeyCode::Enter, &mut term);
+	assert_eq!(event, Some(ReadlineEvent::Line("cargo build".into())));
+	assert_eq!(term.screen_contents()[1], "[I]>");
+}
+
+#[cfg(test)]
+#[test]
+fn test_kill_and_yank() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let (ctrl, alt) = (KeyModifiers::CONTROL, KeyModifiers::ALT);
+	for c in "cp src dst".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	// Both words are yanked back together
+	press(KeyCode::Char('w'), ctrl, &mut term);
+	press(KeyCode::Char('w'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "> cp");
+	press(KeyCode::Home, KeyModifiers::NONE, &mut term);
+	press(KeyCode::Char('d'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], ">");
+	press(KeyCode::Char('y'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "> cp");
+	press(KeyCode::Char('y'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> src dst");
+	assert_eq!(term.cursor_pos(), (9, 0));
+	press(KeyCode::Char('y'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> cp");
+}
+
+#[cfg(test)]
+#[test]
+fn test_undo() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	history.set_entries(["cargo build"]);
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let undo = KeyCode::Char('_');
+	for c in "ls -la".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	press(KeyCode::Backspace, KeyModifiers::NONE, &mut term);
+	press(KeyCode::Up, KeyModifiers::NONE, &mut term);
+	assert_eq!(term.screen_contents()[0], "> cargo build");
+	press(undo, KeyModifiers::CONTROL, &mut term);
+	assert_eq!(term.screen_contents()[0], "> ls -l");
+	press(undo, KeyModifiers::CONTROL, &mut term);
+	assert_eq!(term.screen_contents()[0], "> ls -la");
+	press(undo, KeyModifiers::CONTROL, &mut term);
+	assert_eq!(term.screen_contents()[0], "> ls");
+	assert_eq!(term.cursor_pos(), (5, 0));
+	press(undo, KeyModifiers::ALT, &mut term);
+	assert_eq!(term.screen_contents()[0], "> ls -la");
+}
+
+#[cfg(test)]
+#[test]
+fn test_word_movement() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap();
+		term.cursor_pos().0
+	};
+	let (ctrl, alt) = (KeyModifiers::CONTROL, KeyModifiers::ALT);
+	for c in "cd ~/src/rust-lang".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	assert_eq!(press(KeyCode::Char('b'), alt, &mut term), 16);
+	assert_eq!(press(KeyCode::Char('b'), alt, &mut term), 11);
+	assert_eq!(press(KeyCode::Char('b'), alt, &mut term), 7);
+	assert_eq!(press(KeyCode::Char('f'), alt, &mut term), 10);
+	assert_eq!(press(KeyCode::Left, ctrl, &mut term), 5);
+	assert_eq!(press(KeyCode::Right, ctrl, &mut term), 20);
+}
+
+#[cfg(test)]
+#[test]
+fn test_kill_words() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let (ctrl, alt) = (KeyModifiers::CONTROL, KeyModifiers::ALT);
+	for c in "cd ~/src/rust-lang".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	press(KeyCode::Backspace, alt, &mut term);
+	press(KeyCode::Backspace, alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> cd ~/src/");
+	press(KeyCode::Home, KeyModifiers::NONE, &mut term);
+	press(KeyCode::Char('d'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], ">  ~/src/");
+	press(KeyCode::Char('d'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> /");
+	// Both kills in a row are yanked back together
+	press(KeyCode::Char('y'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "> cd ~/src/");
+	press(KeyCode::Char('y'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> rust-lang/");
+}
+
+#[cfg(test)]
+#[test]
+fn test_transpose() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let (ctrl, alt) = (KeyModifiers::CONTROL, KeyModifiers::ALT);
+	for c in "git psuh ae\u{301}".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	// With a combining accent
+	press(KeyCode::Char('t'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "> git psuh e\u{301}a");
+	press(KeyCode::Char('t'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> git e\u{301}a psuh");
+	assert_eq!(term.cursor_pos(), (13, 0));
+	for _ in 0..2 {
+		press(KeyCode::Left, KeyModifiers::NONE, &mut term);
+	}
+	press(KeyCode::Char('t'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "> git e\u{301}a push");
+	assert_eq!(term.cursor_pos(), (12, 0));
+	// No word before the first one
+	press(KeyCode::Home, KeyModifiers::NONE, &mut term);
+	press(KeyCode::Char('t'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> git e\u{301}a push");
+}
+
+#[cfg(test)]
+#[test]
+fn test_change_case() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let alt = KeyModifiers::ALT;
+	for c in "straße ÉLAN  mIXed".chars() {
+		press(KeyCode::Char(c), KeyModifiers::NONE, &mut term);
+	}
+	press(KeyCode::Home, KeyModifiers::NONE, &mut term);
+	press(KeyCode::Char('u'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> STRASSE ÉLAN  mIXed");
+	assert_eq!(term.cursor_pos(), (9, 0));
+	press(KeyCode::Char('l'), alt, &mut term);
+	press(KeyCode::Char('c'), alt, &mut term);
+	assert_eq!(term.screen_contents()[0], "> STRASSE élan  Mixed");
+	assert_eq!(term.cursor_pos(), (21, 0));
+}
+
+#[cfg(test)]
+#[test]
+fn test_numeric_argument() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let (none, alt) = (KeyModifiers::NONE, KeyModifiers::ALT);
+	press(KeyCode::Char('1'), alt, &mut term);
+	press(KeyCode::Char('2'), none, &mut term);
+	press(KeyCode::Char('-'), none, &mut term);
+	assert_eq!(term.screen_contents()[0], "> ------------");
+	press(KeyCode::Char('5'), alt, &mut term);
+	press(KeyCode::Left, none, &mut term);
+	assert_eq!(term.cursor_pos(), (9, 0));
+	press(KeyCode::Char('3'), alt, &mut term);
+	press(KeyCode::Delete, none, &mut term);
+	assert_eq!(term.screen_contents()[0], "> ---------");
+	// Digits are typed again once the argument is used
+	press(KeyCode::Char('2'), none, &mut term);
+	assert_eq!(term.screen_contents()[0], "> -------2--");
+}
+
+#[cfg(test)]
+#[test]
+fn test_key_sequences() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, modifiers, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::new(code, modifiers));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	let (none, ctrl) = (KeyModifiers::NONE, KeyModifiers::CONTROL);
+	for c in "make ".chars() {
+		press(KeyCode::Char(c), none, &mut term);
+	}
+	press(KeyCode::Char('x'), ctrl, &mut term);
+	press(KeyCode::Char('u'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], "> make");
+	// Esc cancels, and the keys after it work as usual
+	press(KeyCode::Char('x'), ctrl, &mut term);
+	press(KeyCode::Esc, none, &mut term);
+	press(KeyCode::Char('u'), ctrl, &mut term);
+	assert_eq!(term.screen_contents()[0], ">");
+	// Nothing is typed by a sequence that isn't bound
+	press(KeyCode::Char('x'), ctrl, &mut term);
+	press(KeyCode::Char('a'), none, &mut term);
+	press(KeyCode::Char('b'), none, &mut term);
+	assert_eq!(term.screen_contents()[0], "> b");
+}
+
+#[cfg(test)]
+#[test]
+fn test_overwrite() {
+	let mut term = VirtualTerminal::new(50, 3);
+	let mut state = LineState::new("> ".into(), (50, 3));
+	let mut history = History::default();
+	state.render(&mut term).unwrap();
+	let mut press = |code, term: &mut VirtualTerminal| {
+		let key = Event::Key(KeyEvent::from(code));
+		state.handle_event(key, term, &mut history).unwrap()
+	};
+	for c in "hello".chars() {
+		press(KeyCode::Char(c), &mut term);
+	}
+	press(KeyCode::Home, &mut term);
+	press(KeyCode::Insert, &mut term);
+	press(KeyCode::Char('J'), &mut term);
+	assert_eq!(term.screen_contents()[0], "> Jello");
+	assert_eq!(term.cursor_pos(), (3, 0));
+	// A combining accent is added to the character before
+	press(KeyCode::Char('\u{301}'), &mut term);
+	assert_eq!(term.cursor_pos(), (3, 0));
+	for c in "ELLO!".chars() {
+		press(KeyCode::Char(c), &mut term);
+	}
+	assert_eq!(term.screen_contents()[0], "> J\u{301}ELLO!");
+	press(KeyCode::Insert, &mut term);
+	press(KeyCode::Home, &mut term);
+	press(KeyCode::Char('x'), &mut term);
+	assert_eq!(term.screen_contents()[0], "> xJ\u{301}ELLO!");
 }
@@ -0,0 +1,111 @@
+use std::{
+	collections::VecDeque,
+	io::{self, BufRead},
+	sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+	task::{Context, Poll},
+	thread,
+};
+
+use futures_util::task::AtomicWaker;
+
+// Number of lines read ahead before the thread waits for them to be taken
+const READ_AHEAD: usize = 16;
+
+#[derive(Default)]
+struct Lines {
+	queue: VecDeque<io::Result<String>>,
+	// Set once the end of input or an error was reached
+	done: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+	lines: Mutex<Lines>,
+	// Notified when a line is taken from a full queue
+	taken: Condvar,
+	waker: AtomicWaker,
+}
+
+impl Shared {
+	fn lock(&self) -> MutexGuard<'_, Lines> {
+		self.lines.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+/// Reads lines on a background thread, for input that isn't a terminal
+///
+/// Blocking reads can't be cancelled, so the thread keeps running until the
+/// end of input even if the `LineReader` is dropped.
+pub(crate) struct LineReader {
+	shared: Arc<Shared>,
+}
+
+impl LineReader {
+	pub fn spawn(mut input: impl BufRead + Send + 'static) -> Self {
+		let shared = Arc::new(Shared::default());
+		let reader = shared.clone();
+		thread::spawn(move || loop {
+			let mut line = String::new();
+			let result = input.read_line(&mut line);
+			let mut lines = reader.lock();
+			while lines.queue.len() >= READ_AHEAD {
+				lines = reader
+					.taken
+					.wait(lines)
+					.unwrap_or_else(PoisonError::into_inner);
+			}
+			match result {
+				Ok(0) => lines.done = true,
+				Ok(_) => {
+					if line.ends_with('\n') {
+						line.pop();
+						if line.ends_with('\r') {
+							line.pop();
+						}
+					}
+					lines.queue.push_back(Ok(line));
+				}
+				Err(e) => {
+					lines.queue.push_back(Err(e));
+					lines.done = true;
+				}
+			}
+			let done = lines.done;
+			drop(lines);
+			reader.waker.wake();
+			if done {
+				break;
+			}
+		});
+		Self { shared }
+	}
+
+	/// The next line without its line ending, or `None` at the end of input
+	pub fn poll_next(&self, cx: &mut Context<'_>) -> Poll<io::Result<Option<String>>> {
+		self.shared.waker.register(cx.waker());
+		let mut lines = self.shared.lock();
+		if let Some(line) = lines.queue.pop_front() {
+			self.shared.taken.notify_one();
+			Poll::Ready(line.map(Some))
+		} else if lines.done {
+			Poll::Ready(Ok(None))
+		} else {
+			Poll::Pending
+		}
+	}
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_line_reader() {
+	let input = "foo\nbar\r\n".repeat(20) + "baz";
+	let reader = LineReader::spawn(io::Cursor::new(input));
+	let next = || std::future::poll_fn(|cx| reader.poll_next(cx));
+	for _ in 0..20 {
+		assert_eq!(next().await.unwrap().as_deref(), Some("foo"));
+		assert_eq!(next().await.unwrap().as_deref(), Some("bar"));
+	}
+	assert_eq!(next().await.unwrap().as_deref(), Some("baz"));
+	assert_eq!(next().await.unwrap(), None);
+	assert_eq!(next().await.unwrap(), None);
+}
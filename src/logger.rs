@@ -0,0 +1,122 @@
+use std::sync::Mutex;
+
+use crossterm::style::Stylize;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::{prefix::LinePrefix, SharedWriter};
+
+/// Install a [`log`] logger that prints records above the prompt through
+/// `writer`.
+///
+/// Records are formatted as `[time] LEVEL target: message`, with the level
+/// colored.  Logging never blocks, and records are never dropped because
+/// the channel is momentarily full: the logger keeps anything it couldn't
+/// send buffered until the next record, [`log::logger().flush()`][Log::flush]
+/// or [`Readline::flush()`][crate::Readline::flush].
+pub fn init_logger(writer: SharedWriter, level: LevelFilter) -> Result<(), SetLoggerError> {
+	log::set_boxed_logger(Box::new(Logger::new(writer, level)))?;
+	log::set_max_level(level);
+	Ok(())
+}
+
+struct Logger {
+	writer: SharedWriter,
+	// Taken from the writer, records are written through a shared reference
+	prefix: Mutex<LinePrefix>,
+	level: LevelFilter,
+}
+
+impl Logger {
+	fn new(writer: SharedWriter, level: LevelFilter) -> Self {
+		Self {
+			prefix: Mutex::new(writer.prefix.clone()),
+			writer,
+			level,
+		}
+	}
+}
+
+impl Log for Logger {
+	fn enabled(&self, metadata: &Metadata) -> bool {
+		metadata.level() <= self.level
+	}
+
+	fn log(&self, record: &Record) {
+		if !self.enabled(record.metadata()) {
+			return;
+		}
+		let level = match record.level() {
+			Level::Error => "ERROR".red(),
+			Level::Warn => "WARN ".yellow(),
+			Level::Info => "INFO ".green(),
+			Level::Debug => "DEBUG".blue(),
+			Level::Trace => "TRACE".magenta(),
+		};
+		let line = format!(
+			"{} {} {}: {}\n",
+			chrono::Local::now().format("[%H:%M:%S]"),
+			level.bold(),
+			record.target(),
+			record.args()
+		);
+		if let Ok(mut prefix) = self.prefix.lock() {
+			let _ = self.writer.write_buffered(&mut prefix, line.as_bytes());
+		}
+	}
+
+	fn flush(&self) {
+		let _ = self.writer.send_buffered();
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_logger_format() {
+	let (sender, receiver) = thingbuf::mpsc::channel(500);
+	let logger = Logger::new(
+		SharedWriter::new(sender, Default::default()),
+		LevelFilter::Info,
+	);
+	logger.log(
+		&Record::builder()
+			.level(Level::Info)
+			.target("net")
+			.args(format_args!("connected to {}", "localhost"))
+			.build(),
+	);
+	logger.log(
+		&Record::builder()
+			.level(Level::Debug)
+			.args(format_args!("filtered"))
+			.build(),
+	);
+	let line = String::from_utf8(receiver.try_recv_ref().unwrap().clone()).unwrap();
+	assert!(line.ends_with(" net: connected to localhost\n"));
+	assert!(receiver.try_recv_ref().is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn test_logger_full_channel() {
+	let (sender, receiver) = thingbuf::mpsc::channel(1);
+	let logger = Logger::new(
+		SharedWriter::new(sender, Default::default()),
+		LevelFilter::Info,
+	);
+	let record = |message| {
+		logger.log(
+			&Record::builder()
+				.level(Level::Info)
+				.args(format_args!("{message}"))
+				.build(),
+		)
+	};
+	let start = std::time::Instant::now();
+	record("first");
+	record("second");
+	// Kept buffered without waiting for room
+	assert!(start.elapsed() < std::time::Duration::from_millis(50));
+	assert!(receiver.try_recv_ref().unwrap().ends_with(b"first\n"));
+	logger.flush();
+	assert!(receiver.try_recv_ref().unwrap().ends_with(b"second\n"));
+}
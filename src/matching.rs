@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+use crate::completion::Candidate;
+
+/// How completion candidates are matched against the text being completed,
+/// see [`Readline::set_match_strategy()`][crate::Readline::set_match_strategy]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MatchStrategy {
+	/// Candidates that contain the text
+	Exact,
+	/// Candidates that start with the text
+	#[default]
+	Prefix,
+	/// Candidates that contain the characters of the text in order, best
+	/// matches first.  Case is ignored unless the text contains uppercase
+	/// letters.
+	Fuzzy,
+}
+
+/// A candidate that matched, with the byte offsets of the matched characters
+/// in the text shown for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Match {
+	pub candidate: Candidate,
+	pub positions: Vec<usize>,
+}
+
+// Fuzzy scores for each matched character
+const MATCH_SCORE: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 8;
+const BOUNDARY_BONUS: i64 = 8;
+const GAP_PENALTY: i64 = 1;
+
+impl MatchStrategy {
+	/// Keep the candidates whose replacement matches `text`, best first for
+	/// fuzzy matching, with or without regard to case
+	pub(crate) fn filter(
+		self,
+		text: &str,
+		candidates: &[Candidate],
+		ignore_case: bool,
+	) -> Vec<Match> {
+		let mut scored: Vec<(i64, Match)> = candidates
+			.iter()
+			.filter_map(|candidate| {
+				let (score, mut positions) =
+					self.score(text, &candidate.replacement, ignore_case)?;
+				if candidate.label.is_some() {
+					// Highlight the label where it matches too
+					positions = self
+						.score(text, candidate.display(), ignore_case)
+						.map(|(_, positions)| positions)
+						.unwrap_or_default();
+				}
+				let candidate = candidate.clone();
+				Some((
+					score,
+					Match {
+						candidate,
+						positions,
+					},
+				))
+			})
+			.collect();
+		// Stable, so equally good candidates keep the completer's order
+		scored.sort_by_key(|(score, _)| -score);
+		scored.into_iter().map(|(_, matched)| matched).collect()
+	}
+
+	/// How well `candidate` matches `text`, and the byte offsets of the
+	/// matched characters, or `None` if it doesn't
+	fn score(self, text: &str, candidate: &str, ignore_case: bool) -> Option<(i64, Vec<usize>)> {
+		let positions = |range: Range<usize>| {
+			candidate[range.clone()]
+				.char_indices()
+				.map(|(i, _)| range.start + i)
+				.collect()
+		};
+		match self {
+			Self::Exact => candidate.char_indices().find_map(|(start, _)| {
+				let len = matched_len(text, &candidate[start..], ignore_case)?;
+				Some((0, positions(start..start + len)))
+			}),
+			Self::Prefix => {
+				let len = matched_len(text, candidate, ignore_case)?;
+				Some((0, positions(0..len)))
+			}
+			Self::Fuzzy => fuzzy_score(text, candidate, ignore_case),
+		}
+	}
+}
+
+fn same_char(a: char, b: char, ignore_case: bool) -> bool {
+	a == b || ignore_case && a.to_lowercase().eq(b.to_lowercase())
+}
+
+// Bytes at the start of `candidate` that match `text`, if it starts with it
+fn matched_len(text: &str, candidate: &str, ignore_case: bool) -> Option<usize> {
+	let mut chars = candidate.char_indices();
+	for wanted in text.chars() {
+		let (_, c) = chars.next()?;
+		if !same_char(wanted, c, ignore_case) {
+			return None;
+		}
+	}
+	Some(chars.next().map_or(candidate.len(), |(end, _)| end))
+}
+
+// Matches the characters of `text` to the earliest ones in `candidate`,
+// preferring runs of characters and the starts of words.  Case is ignored
+// anyway if `text` is all lowercase.
+fn fuzzy_score(text: &str, candidate: &str, ignore_case: bool) -> Option<(i64, Vec<usize>)> {
+	let ignore_case = ignore_case || !text.chars().any(char::is_uppercase);
+	let same = |a: char, b: char| same_char(a, b, ignore_case);
+	let mut score = 0;
+	let mut positions = Vec::new();
+	let mut chars = candidate.char_indices();
+	let mut previous: Option<char> = None;
+	let mut gap = 0;
+	for wanted in text.chars() {
+		loop {
+			let (offset, c) = chars.next()?;
+			let at_boundary = match previous {
+				None => true,
+				Some(previous) => {
+					!previous.is_alphanumeric() && c.is_alphanumeric()
+						|| previous.is_lowercase() && c.is_uppercase()
+				}
+			};
+			previous = Some(c);
+			if !same(wanted, c) {
+				gap += 1;
+				continue;
+			}
+			score += MATCH_SCORE;
+			if at_boundary {
+				score += BOUNDARY_BONUS;
+			}
+			if !positions.is_empty() && gap == 0 {
+				score += CONSECUTIVE_BONUS;
+			}
+			score -= gap * GAP_PENALTY;
+			gap = 0;
+			positions.push(offset);
+			break;
+		}
+	}
+	Some((score, positions))
+}
+
+#[cfg(test)]
+#[test]
+fn test_match_strategies() {
+	let candidates = ["format", "from_str", "for_each", "Formatter"].map(Candidate::from);
+	let texts = |matches: Vec<Match>| {
+		matches
+			.into_iter()
+			.map(|matched| matched.candidate.replacement)
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(
+		texts(MatchStrategy::Prefix.filter("for", &candidates, false)),
+		["format", "for_each"]
+	);
+	assert_eq!(
+		texts(MatchStrategy::Exact.filter("mat", &candidates, false)),
+		["format", "Formatter"]
+	);
+	// Runs and starts of words beat scattered characters
+	assert_eq!(
+		texts(MatchStrategy::Fuzzy.filter("fe", &candidates, false)),
+		["for_each", "Formatter"]
+	);
+	assert_eq!(
+		texts(MatchStrategy::Fuzzy.filter("Fo", &candidates, false)),
+		["Formatter"]
+	);
+
+	let matched = MatchStrategy::Fuzzy.filter("fs", &candidates, false);
+	assert_eq!(matched[0].candidate.replacement, "from_str");
+	assert_eq!(matched[0].positions, [0, 5]);
+	let matched = MatchStrategy::Exact.filter("é", &["café".into()], false);
+	assert_eq!(matched[0].positions, [3]);
+
+	// Ignoring case
+	assert_eq!(
+		texts(MatchStrategy::Prefix.filter("FOR", &candidates, true)),
+		["format", "for_each", "Formatter"]
+	);
+	let matched = MatchStrategy::Exact.filter("É", &["cafés".into()], true);
+	assert_eq!(matched[0].positions, [3]);
+}
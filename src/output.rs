@@ -0,0 +1,86 @@
+/// What happens to output that arrives while output is paused and the limit
+/// set with [`Readline::set_output_limit()`][crate::Readline::set_output_limit]
+/// has been reached
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputOverflow {
+	/// Stop receiving output, so writers wait (or get `WouldBlock`) once the
+	/// channel is full too
+	#[default]
+	Backpressure,
+	/// Discard further output, counting what was dropped
+	Drop,
+}
+
+/// Output received while paused, printed in one go when resumed
+#[derive(Debug)]
+pub(crate) struct HeldOutput {
+	data: Vec<u8>,
+	// Number of lines held
+	lines: usize,
+	pub dropped: usize,
+	pub limit: usize,
+	pub overflow: OutputOverflow,
+}
+
+impl Default for HeldOutput {
+	fn default() -> Self {
+		Self {
+			data: Vec::new(),
+			lines: 0,
+			dropped: 0,
+			limit: 1000,
+			overflow: OutputOverflow::default(),
+		}
+	}
+}
+
+impl HeldOutput {
+	/// Hold on to `data`, dropping it instead if `bounded` and over the limit
+	/// with [`OutputOverflow::Drop`]
+	pub fn push(&mut self, data: &[u8], bounded: bool) {
+		let lines = data.iter().filter(|b| **b == b'\n').count().max(1);
+		if bounded && self.lines >= self.limit && self.overflow == OutputOverflow::Drop {
+			self.dropped += lines;
+			return;
+		}
+		self.data.extend_from_slice(data);
+		self.lines += lines;
+	}
+
+	/// Whether the limit is reached and no more output should be received
+	pub fn is_full(&self) -> bool {
+		self.lines >= self.limit && self.overflow == OutputOverflow::Backpressure
+	}
+
+	/// Number of lines held
+	pub fn len(&self) -> usize {
+		self.lines
+	}
+
+	/// Take everything held so far
+	pub fn take(&mut self) -> Vec<u8> {
+		self.lines = 0;
+		std::mem::take(&mut self.data)
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_held_output_limit() {
+	let mut held = HeldOutput {
+		limit: 2,
+		overflow: OutputOverflow::Drop,
+		..Default::default()
+	};
+	for line in [&b"a\n"[..], b"b\n", b"c\n"] {
+		held.push(line, true);
+	}
+	assert_eq!(held.dropped, 1);
+	assert!(!held.is_full());
+	assert_eq!(held.take(), b"a\nb\n");
+
+	held.overflow = OutputOverflow::Backpressure;
+	held.push(b"a\n", true);
+	held.push(b"b\n", true);
+	assert!(held.is_full());
+}
@@ -0,0 +1,74 @@
+use std::{
+	io::{stdout, Write},
+	panic,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex, Once, PoisonError,
+	},
+};
+
+use crossterm::{
+	cursor,
+	terminal::{disable_raw_mode, is_raw_mode_enabled},
+	QueueableCommand,
+};
+
+static WRAP_HOOK: Once = Once::new();
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// Writes out what the `Readline` with the terminal in raw mode has queued and
+// leaves the alternate screen if it switched to it
+static FLUSH: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+
+/// Wrap the current panic hook so the terminal is restored before it runs
+///
+/// The hook is only wrapped once; calling this again just re-enables it.
+pub fn install() {
+	ENABLED.store(true, Ordering::SeqCst);
+	WRAP_HOOK.call_once(|| {
+		let previous = panic::take_hook();
+		panic::set_hook(Box::new(move |info| {
+			if ENABLED.load(Ordering::SeqCst) {
+				restore();
+			}
+			previous(info);
+		}));
+	});
+}
+
+/// Set how drawing queued by the `Readline` with the terminal in raw mode is
+/// written out, and its alternate screen left, before the terminal is
+/// restored, or `None` once it is dropped
+pub fn set_flush(flush: Option<Box<dyn Fn() + Send>>) {
+	*FLUSH.lock().unwrap_or_else(PoisonError::into_inner) = flush;
+}
+
+/// Stop restoring the terminal on panic
+///
+/// The wrapper stays installed (removing it could also remove hooks set
+/// after it), but only calls through to the previous hook.
+pub fn remove() {
+	ENABLED.store(false, Ordering::SeqCst);
+}
+
+fn restore() {
+	// Nothing to do if no Readline is active
+	if !is_raw_mode_enabled().unwrap_or(false) {
+		return;
+	}
+	// Queued drawing would otherwise be lost or land after the restore.  The
+	// flush only tries the Readline's screen lock, which may be held by the
+	// panicking thread, in which case the alternate screen stays too.
+	if let Ok(flush) = FLUSH.try_lock() {
+		if let Some(flush) = &*flush {
+			flush();
+		}
+	}
+	let _ = disable_raw_mode();
+	let mut stdout = stdout();
+	// Remove any scroll region
+	let _ = stdout.write_all(b"\x1b[r");
+	let _ = stdout.queue(cursor::SetCursorStyle::DefaultUserShape);
+	let _ = stdout.queue(cursor::Show);
+	let _ = stdout.write_all(b"\n");
+	let _ = stdout.flush();
+}
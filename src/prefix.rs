@@ -0,0 +1,196 @@
+use std::{borrow::Cow, fmt, io::Write, sync::Arc};
+
+use crossterm::style::{Color, Stylize};
+
+/// Format of the timestamp put in front of each line written to a
+/// [`SharedWriter`][crate::SharedWriter]
+#[derive(Clone)]
+pub enum TimestampFormat {
+	/// Local time, e.g. `[12:34:56]`
+	TimeOnly,
+	/// Local date and time, e.g. `[2024-01-31 12:34:56]`
+	DateTime,
+	/// Custom formatter, called once for every line
+	Custom(Arc<dyn Fn() -> String + Send + Sync>),
+}
+
+impl TimestampFormat {
+	/// Create a [`TimestampFormat::Custom`] from a closure
+	pub fn custom(format: impl Fn() -> String + Send + Sync + 'static) -> Self {
+		Self::Custom(Arc::new(format))
+	}
+
+	fn format(&self) -> String {
+		match self {
+			Self::TimeOnly => chrono::Local::now().format("[%H:%M:%S]").to_string(),
+			Self::DateTime => chrono::Local::now()
+				.format("[%Y-%m-%d %H:%M:%S]")
+				.to_string(),
+			Self::Custom(format) => format(),
+		}
+	}
+}
+
+impl fmt::Debug for TimestampFormat {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::TimeOnly => write!(f, "TimeOnly"),
+			Self::DateTime => write!(f, "DateTime"),
+			Self::Custom(_) => write!(f, "Custom(..)"),
+		}
+	}
+}
+
+/// Decorations inserted at the start of every line written to a
+/// `SharedWriter`, which also turns CRLF line endings into plain newlines
+#[derive(Default)]
+pub(crate) struct LinePrefix {
+	pub timestamps: Option<TimestampFormat>,
+	pub tag: Option<String>,
+	pub tag_color: Option<Color>,
+	// Whether the last data written did not end in a newline
+	mid_line: bool,
+	// Whether the last data written ended in a carriage return, which is
+	// held back in case a newline follows, until the next write or flush
+	pending_cr: bool,
+}
+
+impl Clone for LinePrefix {
+	fn clone(&self) -> Self {
+		// A clone starts with an empty buffer, so it is always at the start of a line
+		Self {
+			timestamps: self.timestamps.clone(),
+			tag: self.tag.clone(),
+			tag_color: self.tag_color,
+			mid_line: false,
+			pending_cr: false,
+		}
+	}
+}
+
+impl LinePrefix {
+	/// Append `data` to `buffer`, inserting the prefix in front of each new line
+	pub fn extend(&mut self, buffer: &mut Vec<u8>, data: &[u8]) {
+		if data.is_empty() {
+			return;
+		}
+		let data = self.normalize_line_endings(data);
+		let data = &data[..];
+		if data.is_empty() {
+			return;
+		}
+		if self.timestamps.is_none() && self.tag.is_none() {
+			buffer.extend_from_slice(data);
+		} else {
+			for line in data.split_inclusive(|b| *b == b'\n') {
+				if !self.mid_line {
+					self.write_prefix(buffer);
+				}
+				buffer.extend_from_slice(line);
+				self.mid_line = !line.ends_with(b"\n");
+			}
+		}
+		self.mid_line = !data.ends_with(b"\n");
+	}
+
+	/// Append a carriage return held back by [`extend()`][Self::extend], as
+	/// the data is flushed before a newline could follow it
+	pub fn flush(&mut self, buffer: &mut Vec<u8>) {
+		if std::mem::take(&mut self.pending_cr) {
+			buffer.push(b'\r');
+		}
+	}
+
+	/// Replace `\r\n` with `\n`, leaving other carriage returns alone
+	fn normalize_line_endings<'a>(&mut self, data: &'a [u8]) -> Cow<'a, [u8]> {
+		let pending_cr = std::mem::take(&mut self.pending_cr);
+		if !pending_cr && !data.contains(&b'\r') {
+			return Cow::Borrowed(data);
+		}
+		let mut normalized = Vec::with_capacity(data.len() + 1);
+		if pending_cr && data[0] != b'\n' {
+			normalized.push(b'\r');
+		}
+		let mut bytes = data.iter().peekable();
+		while let Some(&byte) = bytes.next() {
+			match (byte, bytes.peek()) {
+				(b'\r', Some(b'\n')) => {}
+				(b'\r', None) => self.pending_cr = true,
+				_ => normalized.push(byte),
+			}
+		}
+		Cow::Owned(normalized)
+	}
+
+	fn write_prefix(&self, buffer: &mut Vec<u8>) {
+		if let Some(timestamps) = &self.timestamps {
+			let _ = write!(buffer, "{} ", timestamps.format().dim());
+		}
+		if let Some(tag) = &self.tag {
+			let tag = format!("[{tag}]");
+			match self.tag_color {
+				Some(color) => {
+					let _ = write!(buffer, "{} ", tag.with(color));
+				}
+				None => {
+					let _ = write!(buffer, "{tag} ");
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_prefix_partial_lines() {
+	let mut prefix = LinePrefix {
+		timestamps: Some(TimestampFormat::custom(|| "T".into())),
+		..Default::default()
+	};
+	let mut buffer = Vec::new();
+	prefix.extend(&mut buffer, b"foo\nba");
+	prefix.extend(&mut buffer, b"r\n");
+	prefix.extend(&mut buffer, b"baz\n");
+	let dim = format!("{} ", "T".dim());
+	assert_eq!(
+		String::from_utf8(buffer).unwrap(),
+		format!("{dim}foo\n{dim}bar\n{dim}baz\n")
+	);
+}
+
+#[cfg(test)]
+#[test]
+fn test_prefix_tag() {
+	let mut prefix = LinePrefix {
+		tag: Some("net".into()),
+		..Default::default()
+	};
+	let mut buffer = Vec::new();
+	prefix.extend(&mut buffer, b"connected\nsent 3 bytes\n");
+	assert_eq!(buffer, b"[net] connected\n[net] sent 3 bytes\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_crlf() {
+	let mut prefix = LinePrefix::default();
+	let mut buffer = Vec::new();
+	prefix.extend(&mut buffer, b"foo\r\nbar\r");
+	prefix.extend(&mut buffer, b"\nprogress 1\r");
+	prefix.extend(&mut buffer, b"progress 2\r\n");
+	assert_eq!(buffer, b"foo\nbar\nprogress 1\rprogress 2\n");
+}
+
+#[cfg(test)]
+#[test]
+fn test_flush_after_cr() {
+	let mut prefix = LinePrefix::default();
+	let mut buffer = Vec::new();
+	prefix.extend(&mut buffer, b"progress 1\r");
+	assert_eq!(buffer, b"progress 1");
+	prefix.flush(&mut buffer);
+	assert_eq!(buffer, b"progress 1\r");
+	prefix.flush(&mut buffer);
+	prefix.extend(&mut buffer, b"progress 2\n");
+	assert_eq!(buffer, b"progress 1\rprogress 2\n");
+}
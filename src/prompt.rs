@@ -0,0 +1,121 @@
+use std::fmt::{self, Display};
+
+use crossterm::style::{ContentStyle, StyledContent};
+
+use crate::ColorSupport;
+
+/// A prompt built from pieces of text in their own style, see
+/// [`Readline::set_styled_prompt()`][crate::Readline::set_styled_prompt]
+///
+/// Each piece's style is downgraded to the [`ColorSupport`] of the terminal
+/// when drawn, and the width of the prompt is measured from its text.  Pieces
+/// may contain newlines for prompts spanning several rows.  Strings convert
+/// into a prompt of one plain piece, which may still contain escape sequences.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Prompt {
+	pieces: Vec<StyledContent<String>>,
+}
+
+impl Prompt {
+	pub fn new() -> Self {
+		Self::default()
+	}
+	/// Add `text` without any style
+	pub fn plain(self, text: impl Into<String>) -> Self {
+		self.styled(StyledContent::new(ContentStyle::new(), text.into()))
+	}
+	/// Add styled text, e.g. `"~/src".blue().bold()`
+	pub fn styled<D: Display>(mut self, content: StyledContent<D>) -> Self {
+		let text = content.content().to_string();
+		self.pieces.push(StyledContent::new(*content.style(), text));
+		self
+	}
+	/// The text of the prompt without the styles of its pieces
+	pub fn text(&self) -> String {
+		self.pieces
+			.iter()
+			.map(|piece| piece.content().as_str())
+			.collect()
+	}
+	/// The prompt with escape sequences for the style of each piece, on top
+	/// of `base`, with colors downgraded to `colors`
+	pub(crate) fn render(&self, base: ContentStyle, colors: ColorSupport) -> String {
+		self.pieces
+			.iter()
+			.map(|piece| {
+				let style = piece.style();
+				let mut attributes = base.attributes;
+				attributes.extend(style.attributes);
+				let style = ContentStyle {
+					foreground_color: style.foreground_color.or(base.foreground_color),
+					background_color: style.background_color.or(base.background_color),
+					underline_color: style.underline_color.or(base.underline_color),
+					attributes,
+				};
+				colors
+					.downgrade_style(style)
+					.apply(piece.content())
+					.to_string()
+			})
+			.collect()
+	}
+}
+
+impl Display for Prompt {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.pieces
+			.iter()
+			.try_for_each(|piece| write!(f, "{piece}"))
+	}
+}
+
+impl From<&str> for Prompt {
+	fn from(text: &str) -> Self {
+		Self::new().plain(text)
+	}
+}
+
+impl From<String> for Prompt {
+	fn from(text: String) -> Self {
+		Self::new().plain(text)
+	}
+}
+
+impl<D: Display> From<StyledContent<D>> for Prompt {
+	fn from(content: StyledContent<D>) -> Self {
+		Self::new().styled(content)
+	}
+}
+
+/// Provides the prompt whenever it is drawn, so that it can show state that
+/// changes, e.g. the current directory or a count of unread messages, see
+/// [`Readline::set_prompt_provider()`][crate::Readline::set_prompt_provider]
+///
+/// Implemented for closures with the same signature as
+/// [`prompt()`][PromptProvider::prompt].
+pub trait PromptProvider: Send {
+	/// The prompt to draw, which may span several rows like the one given to
+	/// [`Readline::new()`][crate::Readline::new]
+	fn prompt(&mut self) -> Prompt;
+}
+
+impl<F, P> PromptProvider for F
+where
+	F: FnMut() -> P + Send,
+	P: Into<Prompt>,
+{
+	fn prompt(&mut self) -> Prompt {
+		self().into()
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_styled_prompt() {
+	use crossterm::style::Stylize;
+
+	let prompt = Prompt::new().styled("~/src".blue().bold()).plain(" > ");
+	assert_eq!(prompt.text(), "~/src > ");
+	assert_eq!(prompt.to_string(), format!("{} > ", "~/src".blue().bold()));
+	assert_eq!(Prompt::from("> ").to_string(), "> ");
+}
@@ -0,0 +1,37 @@
+use crate::Prompt;
+
+/// An incremental history search, started with Ctrl-R or Ctrl-S
+pub(crate) struct HistorySearch {
+	pub query: String,
+	// Whether newer entries are searched, after Ctrl-S
+	pub forward: bool,
+	// Index of the history entry shown
+	pub found: Option<usize>,
+	// Whether nothing matched the query
+	pub failing: bool,
+	// History position, input and prompt from before the search, the input
+	// is put back if the search is cancelled
+	pub start: Option<usize>,
+	pub line: String,
+	pub prompt: Prompt,
+}
+
+impl HistorySearch {
+	pub fn new(forward: bool, start: Option<usize>, line: String, prompt: Prompt) -> Self {
+		Self {
+			query: String::new(),
+			forward,
+			found: start,
+			failing: false,
+			start,
+			line,
+			prompt,
+		}
+	}
+	/// The prompt shown in place of the usual one, like bash's
+	pub fn prompt(&self) -> String {
+		let failing = if self.failing { "failing " } else { "" };
+		let direction = if self.forward { "" } else { "reverse-" };
+		format!("({failing}{direction}i-search)`{}': ", self.query)
+	}
+}
@@ -0,0 +1,137 @@
+use std::{
+	io,
+	os::fd::RawFd,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex, OnceLock,
+	},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use signal_hook::{
+	consts::{SIGHUP, SIGQUIT, SIGTERM},
+	low_level,
+};
+
+// Terminal mode from before raw mode was first enabled
+struct SavedTerminal {
+	fd: RawFd,
+	termios: libc::termios,
+}
+
+static SAVED: OnceLock<SavedTerminal> = OnceLock::new();
+// Whether a Readline currently has the terminal in raw mode
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+// Whether that Readline's screen is switched to the alternate screen, as the
+// handlers can't take its lock to find out
+static ALTERNATE_SCREEN: AtomicBool = AtomicBool::new(false);
+static REGISTERED: Mutex<bool> = Mutex::new(false);
+
+/// Remember the current terminal mode so it can be restored from a signal
+/// handler.  Must be called before raw mode is enabled.
+pub fn save_terminal() {
+	if SAVED.get().is_some() {
+		return;
+	}
+	// Same choice of file descriptor as crossterm
+	let fd = if unsafe { libc::isatty(libc::STDIN_FILENO) } == 1 {
+		libc::STDIN_FILENO
+	} else {
+		match unsafe { libc::open(c"/dev/tty".as_ptr(), libc::O_RDWR) } {
+			-1 => return,
+			fd => fd,
+		}
+	};
+	let mut termios = unsafe { std::mem::zeroed() };
+	if unsafe { libc::tcgetattr(fd, &mut termios) } == 0 {
+		let _ = SAVED.set(SavedTerminal { fd, termios });
+	}
+}
+
+/// Record whether a `Readline` has the terminal in raw mode
+pub fn set_active(active: bool) {
+	ACTIVE.store(active, Ordering::SeqCst);
+}
+
+/// Record whether the `Readline` with the terminal in raw mode switched to
+/// the alternate screen
+pub fn set_alternate_screen(alternate_screen: bool) {
+	ALTERNATE_SCREEN.store(alternate_screen, Ordering::SeqCst);
+}
+
+#[cfg(test)]
+pub fn in_alternate_screen() -> bool {
+	ALTERNATE_SCREEN.load(Ordering::SeqCst)
+}
+
+/// Restore the saved terminal mode, leave the alternate screen, remove any
+/// scroll region, show the cursor and move to a new line
+///
+/// Only uses async-signal-safe operations, so it can be called from a signal
+/// handler.  That rules out taking the `Readline`'s screen lock, so drawing
+/// it has queued but not flushed yet is lost; it is flushed after every
+/// redraw, so this is at most part of one.
+pub fn restore_terminal() {
+	if !ACTIVE.swap(false, Ordering::SeqCst) {
+		return;
+	}
+	if let Some(saved) = SAVED.get() {
+		unsafe { libc::tcsetattr(saved.fd, libc::TCSANOW, &saved.termios) };
+	}
+	if ALTERNATE_SCREEN.swap(false, Ordering::SeqCst) {
+		const LEAVE_ALTERNATE_SCREEN: &[u8] = b"\x1b[?1049l";
+		unsafe {
+			libc::write(
+				libc::STDOUT_FILENO,
+				LEAVE_ALTERNATE_SCREEN.as_ptr().cast(),
+				LEAVE_ALTERNATE_SCREEN.len(),
+			)
+		};
+	}
+	// Remove any scroll region, reset the cursor's shape, show it and move to
+	// a new line
+	const RESET: &[u8] = b"\x1b[r\x1b[0 q\x1b[?25h\r\n";
+	unsafe { libc::write(libc::STDOUT_FILENO, RESET.as_ptr().cast(), RESET.len()) };
+}
+
+/// Whether `event` is the Ctrl-Z key press
+pub fn is_suspend_key(event: &Event) -> bool {
+	matches!(
+		event,
+		Event::Key(KeyEvent {
+			code: KeyCode::Char('z'),
+			modifiers: KeyModifiers::CONTROL,
+			kind: KeyEventKind::Press,
+			..
+		})
+	)
+}
+
+/// Stop the process group like the default Ctrl-Z behavior, returning once
+/// the process is continued
+pub fn suspend() -> io::Result<()> {
+	if unsafe { libc::kill(0, libc::SIGTSTP) } == -1 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(())
+}
+
+/// Install handlers for SIGTERM, SIGHUP and SIGQUIT that restore the terminal
+/// and then perform the signal's default action.  Only installs them once.
+pub fn register() -> io::Result<()> {
+	let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+	if *registered {
+		return Ok(());
+	}
+	for signal in [SIGTERM, SIGHUP, SIGQUIT] {
+		// SAFETY: the handler only calls async-signal-safe functions
+		unsafe {
+			low_level::register(signal, move || {
+				restore_terminal();
+				let _ = low_level::emulate_default_handler(signal);
+			})?;
+		}
+	}
+	*registered = true;
+	Ok(())
+}
@@ -0,0 +1,158 @@
+use std::{
+	io::{Stdout, Write},
+	ops::{Deref, DerefMut},
+	sync::{Arc, Mutex},
+};
+
+use thingbuf::mpsc::Receiver;
+
+use crate::{lock, print_output, print_pending, Readline, ReadlineError, Screen};
+
+/// The input half of a [`Readline`], see [`Readline::split()`]
+///
+/// Gives access to everything a `Readline` does, except that output from
+/// [`SharedWriter`][crate::SharedWriter]s is left to the
+/// [`ReadlinePrinter`].
+pub struct ReadlineReader<W: Write = Stdout> {
+	readline: Readline<W>,
+}
+
+/// The output half of a [`Readline`], see [`Readline::split()`]
+pub struct ReadlinePrinter<W: Write = Stdout> {
+	receiver: Arc<Receiver<Vec<u8>>>,
+	screen: Arc<Mutex<Screen<W>>>,
+	raw_mode: bool,
+	// Shared with the reader, `None` once recombined
+	halves: Option<Arc<()>>,
+}
+
+impl<W: Write> Readline<W> {
+	/// Split into a half that reads input and a half that prints output
+	/// from the associated [`SharedWriter`][crate::SharedWriter]s, which can
+	/// be used from different tasks
+	///
+	/// [`ReadlineReader::readline()`][Readline::readline] no longer prints
+	/// output, so that a busy input task doesn't hold it up.  Instead, the
+	/// printer prints it with [`flush()`][ReadlinePrinter::flush], or as it
+	/// arrives while [`run()`][ReadlinePrinter::run] is polled.  The prompt is
+	/// shared, so both halves keep it intact.
+	///
+	/// The terminal is restored once both halves are dropped.  Use
+	/// [`ReadlineReader::unsplit()`] to get the `Readline` back.
+	pub fn split(mut self) -> (ReadlineReader<W>, ReadlinePrinter<W>) {
+		let halves = Arc::new(());
+		let printer = ReadlinePrinter {
+			receiver: self.line_receiver.clone(),
+			screen: self.screen.clone(),
+			raw_mode: self.raw_mode,
+			halves: Some(halves.clone()),
+		};
+		self.halves = Some(halves);
+		(ReadlineReader { readline: self }, printer)
+	}
+}
+
+impl<W: Write> ReadlineReader<W> {
+	/// Put the halves of a split [`Readline`] back together
+	///
+	/// # Panics
+	///
+	/// If `printer` is from a different `Readline`.
+	pub fn unsplit(mut self, mut printer: ReadlinePrinter<W>) -> Readline<W> {
+		assert!(
+			Arc::ptr_eq(&self.readline.screen, &printer.screen),
+			"unsplit() called with the printer of a different Readline"
+		);
+		printer.halves = None;
+		self.readline.halves = None;
+		self.readline
+	}
+}
+
+impl<W: Write> Deref for ReadlineReader<W> {
+	type Target = Readline<W>;
+
+	fn deref(&self) -> &Readline<W> {
+		&self.readline
+	}
+}
+
+impl<W: Write> DerefMut for ReadlineReader<W> {
+	fn deref_mut(&mut self) -> &mut Readline<W> {
+		&mut self.readline
+	}
+}
+
+impl<W: Write> ReadlinePrinter<W> {
+	/// Print `data` above the prompt
+	pub fn print(&mut self, data: &[u8]) -> Result<(), ReadlineError> {
+		lock(&self.screen).print(data)
+	}
+
+	/// Print everything that writers have sent so far
+	pub fn flush(&mut self) -> Result<(), ReadlineError> {
+		let screen = &mut *lock(&self.screen);
+		print_pending(&self.receiver, |data| screen.print(data))?;
+		Ok(())
+	}
+
+	/// Print output as soon as it arrives
+	///
+	/// Completes once the reader and all writers are dropped, or when
+	/// printing fails.
+	pub async fn run(&mut self) -> Result<(), ReadlineError> {
+		print_output(&self.receiver, &self.screen).await
+	}
+}
+
+impl<W: Write> Drop for ReadlinePrinter<W> {
+	fn drop(&mut self) {
+		let Some(halves) = self.halves.take() else {
+			return;
+		};
+		let screen = &mut *lock(&self.screen);
+		let _ = print_pending(&self.receiver, |data| screen.print(data));
+		if Arc::into_inner(halves).is_some() {
+			// The reader is gone already
+			let _ = screen.restore(self.raw_mode);
+		}
+	}
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_split() {
+	use crossterm::event::KeyCode;
+
+	use futures_util::StreamExt;
+
+	use crate::{key, scripted, virtual_terminal::SharedTerminal, ReadlineEvent};
+
+	let events = [key(KeyCode::Char('x')), key(KeyCode::Enter)];
+	let events = futures_util::stream::iter(events).chain(futures_util::stream::pending());
+	let term = SharedTerminal::new(20, 4);
+	let (rl, mut writer) = scripted(events, term.clone());
+	let (mut reader, mut printer) = rl.split();
+
+	// Output is left to the printer
+	writeln!(writer, "hello").unwrap();
+	assert_eq!(
+		reader.readline().await.unwrap(),
+		ReadlineEvent::Line("x".into())
+	);
+	assert!(!term.screen_contents().contains(&"hello".to_string()));
+	printer.flush().unwrap();
+	assert_eq!(term.screen_contents()[..2], ["> x", "hello"]);
+
+	let mut rl = reader.unsplit(printer);
+	writeln!(writer, "world").unwrap();
+	rl.flush_and_redraw().unwrap();
+	assert_eq!(term.screen_contents()[..3], ["> x", "hello", "world"]);
+
+	// The terminal is restored when the last half is dropped
+	let (reader, printer) = rl.split();
+	drop(reader);
+	assert_eq!(term.screen_contents()[3], ">");
+	drop(printer);
+	assert_eq!(term.screen_contents()[3], "");
+}
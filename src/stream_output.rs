@@ -0,0 +1,140 @@
+use std::{
+	future,
+	io::{self, Write},
+	pin::Pin,
+	sync::{Arc, Mutex, MutexGuard, PoisonError},
+	task::{Context, Poll},
+};
+
+use futures_util::{ready, task::AtomicWaker, AsyncWrite};
+
+#[derive(Default)]
+struct Shared {
+	buffer: Mutex<Vec<u8>>,
+	// Woken when output is flushed
+	waker: AtomicWaker,
+}
+
+impl Shared {
+	fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+		self.buffer.lock().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+/// Output of a [`Readline`][crate::Readline] created with
+/// [`with_streams()`][crate::Readline::with_streams]
+///
+/// Everything drawn is collected here and sent to the stream while
+/// [`readline()`][crate::Readline::readline],
+/// [`flush_async()`][crate::Readline::flush_async] or
+/// [`close()`][crate::Readline::close] is running.
+pub struct StreamOutput {
+	shared: Arc<Shared>,
+}
+
+impl Write for StreamOutput {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.shared.lock().extend_from_slice(buf);
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.shared.waker.wake();
+		Ok(())
+	}
+}
+
+/// Sends what was written to a [`StreamOutput`] on to the stream
+pub(crate) struct OutputPump {
+	shared: Arc<Shared>,
+	stream: Pin<Box<dyn AsyncWrite + Send>>,
+	// Data being sent, kept here so that nothing is lost if sending is
+	// cancelled halfway
+	sending: Vec<u8>,
+	sent: usize,
+	needs_flush: bool,
+}
+
+impl OutputPump {
+	pub fn new(stream: impl AsyncWrite + Send + 'static) -> (Self, StreamOutput) {
+		let shared = Arc::new(Shared::default());
+		let pump = Self {
+			shared: shared.clone(),
+			stream: Box::pin(stream),
+			sending: Vec::new(),
+			sent: 0,
+			needs_flush: false,
+		};
+		(pump, StreamOutput { shared })
+	}
+
+	// Sends everything written so far
+	fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		loop {
+			if self.sent < self.sending.len() {
+				let len = ready!(self
+					.stream
+					.as_mut()
+					.poll_write(cx, &self.sending[self.sent..]))?;
+				if len == 0 {
+					return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+				}
+				self.sent += len;
+				continue;
+			}
+			let mut buffer = self.shared.lock();
+			if buffer.is_empty() {
+				drop(buffer);
+				if self.needs_flush {
+					ready!(self.stream.as_mut().poll_flush(cx))?;
+					self.needs_flush = false;
+				}
+				return Poll::Ready(Ok(()));
+			}
+			self.sending.clear();
+			std::mem::swap(&mut self.sending, &mut *buffer);
+			self.sent = 0;
+			self.needs_flush = true;
+		}
+	}
+
+	/// Send everything written so far
+	pub async fn flush(&mut self) -> io::Result<()> {
+		future::poll_fn(|cx| self.poll_send(cx)).await
+	}
+
+	/// Keep sending output as it is written, only completes on errors
+	pub fn poll_run(&mut self, cx: &mut Context<'_>) -> Poll<io::Error> {
+		self.shared.waker.register(cx.waker());
+		match self.poll_send(cx) {
+			Poll::Ready(Err(e)) => Poll::Ready(e),
+			_ => Poll::Pending,
+		}
+	}
+}
+
+#[cfg(test)]
+#[async_std::test]
+async fn test_output_pump() {
+	#[derive(Clone, Default)]
+	struct Sink(Arc<Mutex<Vec<u8>>>);
+	impl Write for Sink {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			// Accept a single byte at a time to exercise partial writes
+			self.0.lock().unwrap().push(buf[0]);
+			Ok(1)
+		}
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	let sink = Sink::default();
+	let (mut pump, mut output) = OutputPump::new(futures_util::io::AllowStdIo::new(sink.clone()));
+	write!(output, "hello").unwrap();
+	output.flush().unwrap();
+	write!(output, " world").unwrap();
+	assert!(sink.0.lock().unwrap().is_empty());
+	pump.flush().await.unwrap();
+	assert_eq!(&*sink.0.lock().unwrap(), b"hello world");
+}
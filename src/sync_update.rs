@@ -0,0 +1,79 @@
+use std::{
+	cell::Cell,
+	io::{self, Write},
+	ops::{Deref, DerefMut},
+};
+
+// Begin and end synchronized update (BSU/ESU), see
+// https://gist.github.com/christianparpart/d8a62cc1ab659194337d73e399004036
+const BEGIN: &[u8] = b"\x1b[?2026h";
+const END: &[u8] = b"\x1b[?2026l";
+
+thread_local! {
+	// Number of enabled guards alive on this thread, so that only the
+	// outermost one emits the markers
+	static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Makes everything written to the terminal while it is alive show up at
+/// once, on terminals that support synchronized output
+///
+/// The end marker is written on drop, so it is sent even if drawing fails
+/// halfway through.  Terminals without support ignore both markers.
+pub(crate) struct SyncUpdate<'a, W: Write> {
+	term: &'a mut W,
+	enabled: bool,
+}
+
+impl<'a, W: Write> SyncUpdate<'a, W> {
+	pub fn begin(term: &'a mut W, enabled: bool) -> io::Result<Self> {
+		if enabled {
+			if DEPTH.get() == 0 {
+				term.write_all(BEGIN)?;
+			}
+			DEPTH.set(DEPTH.get() + 1);
+		}
+		Ok(Self { term, enabled })
+	}
+}
+
+impl<W: Write> Deref for SyncUpdate<'_, W> {
+	type Target = W;
+	fn deref(&self) -> &W {
+		self.term
+	}
+}
+
+impl<W: Write> DerefMut for SyncUpdate<'_, W> {
+	fn deref_mut(&mut self) -> &mut W {
+		self.term
+	}
+}
+
+impl<W: Write> Drop for SyncUpdate<'_, W> {
+	fn drop(&mut self) {
+		if self.enabled {
+			DEPTH.set(DEPTH.get() - 1);
+			if DEPTH.get() == 0 {
+				let _ = self.term.write_all(END);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_sync_update_nesting() {
+	let mut term = Vec::new();
+	{
+		let mut outer = SyncUpdate::begin(&mut term, true).unwrap();
+		let inner = SyncUpdate::begin(&mut *outer, true).unwrap();
+		drop(inner);
+		outer.write_all(b"x").unwrap();
+	}
+	assert_eq!(term, b"\x1b[?2026hx\x1b[?2026l");
+
+	let mut term = Vec::new();
+	drop(SyncUpdate::begin(&mut term, false).unwrap());
+	assert!(term.is_empty());
+}
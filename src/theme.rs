@@ -0,0 +1,63 @@
+use crossterm::style::{ContentStyle, Stylize};
+
+/// Colors and text attributes of what is drawn around the input, see
+/// [`ReadlineBuilder::theme()`][crate::ReadlineBuilder::theme] and
+/// [`Readline::set_theme()`][crate::Readline::set_theme]
+///
+/// The default is plain text, with hints and descriptions dimmed, the
+/// selection in reverse video and invalid input in red.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+	/// The prompt, unless it is styled by a prompt highlighter
+	pub prompt: ContentStyle,
+	/// Hints and history suggestions after the input
+	pub hint: ContentStyle,
+	/// Candidates in the completion menu
+	pub menu: ContentStyle,
+	/// The selected candidate in the completion menu
+	pub selection: ContentStyle,
+	/// Characters of candidates that match what was typed, on top of the
+	/// style of the candidate
+	pub matched: ContentStyle,
+	/// Descriptions of candidates, on top of the style of the candidate
+	pub description: ContentStyle,
+	/// The indicator shown while output is frozen with Ctrl-S
+	pub indicator: ContentStyle,
+	/// Input that the validator finds valid, unless it is styled by a
+	/// highlighter
+	pub valid: ContentStyle,
+	/// Input that the validator finds invalid
+	pub invalid: ContentStyle,
+	/// Input that the validator finds incomplete
+	pub incomplete: ContentStyle,
+	/// Toasts shown with [`Readline::show_toast()`][crate::Readline::show_toast]
+	pub toast: ContentStyle,
+}
+
+impl Default for Theme {
+	fn default() -> Self {
+		Self {
+			prompt: ContentStyle::new(),
+			hint: ContentStyle::new().dim(),
+			menu: ContentStyle::new(),
+			selection: ContentStyle::new().reverse(),
+			matched: ContentStyle::new().bold(),
+			description: ContentStyle::new().dim(),
+			indicator: ContentStyle::new().dim(),
+			valid: ContentStyle::new(),
+			invalid: ContentStyle::new().red(),
+			incomplete: ContentStyle::new(),
+			toast: ContentStyle::new().dim().reverse(),
+		}
+	}
+}
+
+/// `style` with the colors and attributes of `over` added
+pub(crate) fn layer(style: ContentStyle, over: ContentStyle) -> ContentStyle {
+	ContentStyle {
+		foreground_color: over.foreground_color.or(style.foreground_color),
+		background_color: over.background_color.or(style.background_color),
+		underline_color: over.underline_color.or(style.underline_color),
+		attributes: style.attributes | over.attributes,
+	}
+}
@@ -0,0 +1,62 @@
+use std::io;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::{prefix::LinePrefix, SharedWriter};
+
+/// Writer handed out by [`SharedWriter`]'s `MakeWriter` implementation for
+/// each tracing event.
+///
+/// Unlike writing to a `SharedWriter` directly, this never fails with
+/// `WouldBlock`: when the channel is full the event stays buffered in the
+/// `SharedWriter` given to the subscriber, and is printed along with the
+/// next event or by [`Readline::flush()`][crate::Readline::flush].
+pub struct TracingWriter<'a> {
+	writer: &'a SharedWriter,
+	prefix: LinePrefix,
+}
+
+impl io::Write for TracingWriter<'_> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.writer.write_buffered(&mut self.prefix, buf)
+	}
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+impl<'a> MakeWriter<'a> for SharedWriter {
+	type Writer = TracingWriter<'a>;
+
+	fn make_writer(&'a self) -> Self::Writer {
+		TracingWriter {
+			writer: self,
+			// Events are whole lines, which always start with the prefix
+			prefix: self.prefix.clone(),
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_full_channel_keeps_events() {
+	let (sender, receiver) = thingbuf::mpsc::channel(1);
+	let writer = SharedWriter::new(sender, Default::default());
+	let subscriber = tracing_subscriber::fmt()
+		.with_writer(writer)
+		.without_time()
+		.with_ansi(false)
+		.finish();
+	tracing::subscriber::with_default(subscriber, || {
+		tracing::info!("fills the channel");
+		tracing::info!("waits in the buffer");
+		assert!(receiver
+			.try_recv_ref()
+			.unwrap()
+			.ends_with(b"fills the channel\n"));
+		tracing::info!("sent with the one before");
+	});
+	let lines = String::from_utf8(receiver.try_recv_ref().unwrap().clone()).unwrap();
+	assert!(lines.contains("waits in the buffer\n"));
+	assert!(lines.ends_with("sent with the one before\n"));
+}
@@ -0,0 +1,98 @@
+/// Most changes that can be undone
+const MAX_UNDO: usize = 100;
+
+/// Input and cursor (as grapheme index) before or after a change
+pub(crate) type Snapshot = (String, usize);
+
+/// Changes to the input since it was started, for undo and redo
+#[derive(Debug, Default)]
+pub(crate) struct UndoStack {
+	undo: Vec<Snapshot>,
+	redo: Vec<Snapshot>,
+	// Whether the last change typed a character, characters typed one after
+	// another are undone together
+	typing: bool,
+	// Input before a history search, which changes it with every key
+	held: Option<Snapshot>,
+	// Set by undo and redo, whose own changes aren't recorded
+	restored: bool,
+}
+
+impl UndoStack {
+	/// Note the input after a key, `before` being what it was before
+	///
+	/// `typing` is set for a character typed at the cursor.  While `busy`,
+	/// e.g. searching the history, changes are collected into one.
+	pub fn record(&mut self, before: Snapshot, after: &str, typing: bool, busy: bool) {
+		if std::mem::take(&mut self.restored) {
+			return;
+		}
+		if busy {
+			self.held.get_or_insert(before);
+			return;
+		}
+		let before = self.held.take().unwrap_or(before);
+		if before.0 == after {
+			// Moving the cursor ends the characters being typed
+			self.typing = false;
+			return;
+		}
+		if !(typing && self.typing) {
+			self.undo.push(before);
+			if self.undo.len() > MAX_UNDO {
+				self.undo.remove(0);
+			}
+		}
+		self.typing = typing;
+		self.redo.clear();
+	}
+	/// The input before the last change, `current` being the input now
+	pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+		let previous = self.undo.pop()?;
+		self.redo.push(current);
+		self.typing = false;
+		self.restored = true;
+		Some(previous)
+	}
+	/// The input before the last undo, `current` being the input now
+	pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+		let next = self.redo.pop()?;
+		self.undo.push(current);
+		self.typing = false;
+		self.restored = true;
+		Some(next)
+	}
+	/// Forget the changes, for the next input
+	pub fn clear(&mut self) {
+		*self = Self::default();
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_undo_stack() {
+	let mut stack = UndoStack::default();
+	let snapshot = |text: &str| (text.to_owned(), text.len());
+	stack.record(snapshot(""), "l", true, false);
+	stack.record(snapshot("l"), "ls", true, false);
+	stack.record(snapshot("ls"), "ls ", false, false);
+	stack.record(snapshot("ls "), "ls -", true, false);
+	// Searching the history is one change
+	stack.record(snapshot("ls -"), "cat", false, true);
+	stack.record(snapshot("cat"), "cargo", false, true);
+	stack.record(snapshot("cargo"), "cargo", false, false);
+
+	assert_eq!(stack.undo(snapshot("cargo")), Some(snapshot("ls -")));
+	stack.record(snapshot("cargo"), "ls -", false, false);
+	assert_eq!(stack.undo(snapshot("ls -")), Some(snapshot("ls ")));
+	assert_eq!(stack.undo(snapshot("ls ")), Some(snapshot("ls")));
+	assert_eq!(stack.undo(snapshot("ls")), Some(snapshot("")));
+	assert_eq!(stack.undo(snapshot("")), None);
+	assert_eq!(stack.redo(snapshot("")), Some(snapshot("ls")));
+	stack.record(snapshot(""), "ls", false, false);
+
+	// A new change can't be redone past
+	stack.record(snapshot("ls"), "l", false, false);
+	assert_eq!(stack.redo(snapshot("l")), None);
+	assert_eq!(stack.undo(snapshot("l")), Some(snapshot("ls")));
+}
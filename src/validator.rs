@@ -0,0 +1,31 @@
+/// How a [`Validator`] classifies the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validity {
+	/// The input can be run as it is
+	Valid,
+	/// The input is wrong, e.g. an unknown command
+	Invalid,
+	/// The input isn't finished yet, e.g. an unclosed bracket
+	Incomplete,
+}
+
+/// Classifies the input so that it is colored by the [`Theme`] as it is
+/// typed, see [`Readline::set_validator()`][crate::Readline::set_validator]
+///
+/// Implemented for closures with the same signature as
+/// [`validate()`][Validator::validate].
+///
+/// [`Theme`]: crate::Theme
+pub trait Validator: Send {
+	/// Whether the input `line` is valid, invalid or incomplete
+	fn validate(&mut self, line: &str) -> Validity;
+}
+
+impl<F> Validator for F
+where
+	F: FnMut(&str) -> Validity + Send,
+{
+	fn validate(&mut self, line: &str) -> Validity {
+		self(line)
+	}
+}
@@ -0,0 +1,437 @@
+use std::ops::Range;
+
+use crate::{input::InputLine, keymap::EditAction};
+
+/// Mode of the vi keys, see [`EditMode::Vi`][crate::EditMode::Vi]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ViMode {
+	/// Keys type text and do what they are bound to
+	#[default]
+	Insert,
+	/// Letters are commands
+	Normal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+	Delete,
+	Change,
+	Yank,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+	Left,
+	Right,
+	WordForward,
+	WordBackward,
+	WordEnd,
+	Start,
+	FirstNonBlank,
+	End,
+	// `dd`, `cc` and `yy`
+	WholeLine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InsertAt {
+	Cursor,
+	After,
+	FirstNonBlank,
+	End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+	Move(Motion),
+	Operate(Operator, Motion),
+	DeleteChar,
+	Insert(InsertAt),
+	Paste { before: bool },
+}
+
+impl Command {
+	// Whether `.` repeats it
+	fn is_change(self) -> bool {
+		!matches!(self, Self::Move(_) | Self::Operate(Operator::Yank, _))
+	}
+}
+
+/// What a key in normal mode did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ViOutcome {
+	/// Waiting for the rest of the command
+	Pending,
+	/// The input, cursor or mode changed
+	Changed,
+	/// Not a command
+	Bell,
+	/// Do what the key bound to this action does
+	Action(EditAction),
+}
+
+/// State of the vi keys, see [`EditMode::Vi`][crate::EditMode::Vi]
+#[derive(Debug, Default)]
+pub(crate) struct ViState {
+	pub mode: ViMode,
+	// Count typed so far
+	count: Option<usize>,
+	// Operator waiting for its motion, with the count typed before it
+	operator: Option<(Operator, Option<usize>)>,
+	// Text deleted or yanked last, for `p` and `P`
+	register: String,
+	// Last change with its count, and the text typed after it in insert
+	// mode, for `.`
+	last_change: Option<(usize, Command)>,
+	inserted: String,
+	// Whether typed text is added to `inserted`
+	recording: bool,
+}
+
+impl ViState {
+	/// Back to normal mode after Esc, with the cursor on the last character
+	/// typed
+	pub fn escape(&mut self, cursor: &mut usize) {
+		if self.mode == ViMode::Insert {
+			*cursor = cursor.saturating_sub(1);
+		}
+		self.mode = ViMode::Normal;
+		self.count = None;
+		self.operator = None;
+		self.recording = false;
+	}
+	/// Start the next input in insert mode
+	pub fn reset(&mut self) {
+		self.mode = ViMode::Insert;
+		self.count = None;
+		self.operator = None;
+		self.recording = false;
+	}
+	/// Note a character typed in insert mode, for repeating with `.`
+	pub fn record(&mut self, c: char) {
+		if self.recording {
+			self.inserted.push(c);
+		}
+	}
+	/// Note Backspace in insert mode, for repeating with `.`
+	pub fn record_backspace(&mut self) {
+		if self.recording {
+			self.inserted.pop();
+		}
+	}
+	/// Handle a character typed in normal mode
+	pub fn normal_key(&mut self, c: char, line: &mut InputLine, cursor: &mut usize) -> ViOutcome {
+		if let Some(digit) = c.to_digit(10).filter(|&d| d != 0 || self.count.is_some()) {
+			let count = self.count.unwrap_or(0);
+			self.count = Some(count.saturating_mul(10).saturating_add(digit as usize));
+			return ViOutcome::Pending;
+		}
+		let count = self.count.take();
+		if let Some((operator, operator_count)) = self.operator.take() {
+			let motion = match (operator, c) {
+				(Operator::Delete, 'd') | (Operator::Change, 'c') | (Operator::Yank, 'y') => {
+					Motion::WholeLine
+				}
+				_ => match motion(c) {
+					Some(motion) => motion,
+					None => return ViOutcome::Bell,
+				},
+			};
+			let count = operator_count.unwrap_or(1) * count.unwrap_or(1);
+			return self.run(count, Command::Operate(operator, motion), line, cursor);
+		}
+		let command = match c {
+			'd' | 'c' | 'y' => {
+				let operator = match c {
+					'd' => Operator::Delete,
+					'c' => Operator::Change,
+					_ => Operator::Yank,
+				};
+				self.operator = Some((operator, count));
+				return ViOutcome::Pending;
+			}
+			'x' => Command::DeleteChar,
+			'D' => Command::Operate(Operator::Delete, Motion::End),
+			'C' => Command::Operate(Operator::Change, Motion::End),
+			'i' => Command::Insert(InsertAt::Cursor),
+			'a' => Command::Insert(InsertAt::After),
+			'I' => Command::Insert(InsertAt::FirstNonBlank),
+			'A' => Command::Insert(InsertAt::End),
+			'p' => Command::Paste { before: false },
+			'P' => Command::Paste { before: true },
+			'j' => return ViOutcome::Action(EditAction::HistoryNext),
+			'k' => return ViOutcome::Action(EditAction::HistoryPrevious),
+			'.' => {
+				let Some((last_count, command)) = self.last_change else {
+					return ViOutcome::Bell;
+				};
+				self.execute(count.unwrap_or(last_count), command, line, cursor);
+				if self.mode == ViMode::Insert {
+					// Type the same text again
+					let offset = line.offset(*cursor);
+					let before = line.count();
+					line.replace(offset..offset, &self.inserted);
+					*cursor += line.count() - before;
+					self.escape(cursor);
+				}
+				return ViOutcome::Changed;
+			}
+			_ => match motion(c) {
+				Some(motion) => Command::Move(motion),
+				None => return ViOutcome::Bell,
+			},
+		};
+		self.run(count.unwrap_or(1), command, line, cursor)
+	}
+	// Executes a command typed in normal mode, remembering changes for `.`
+	fn run(
+		&mut self,
+		count: usize,
+		command: Command,
+		line: &mut InputLine,
+		cursor: &mut usize,
+	) -> ViOutcome {
+		if command.is_change() {
+			self.last_change = Some((count, command));
+			self.inserted.clear();
+		}
+		self.execute(count, command, line, cursor);
+		self.recording = self.mode == ViMode::Insert;
+		ViOutcome::Changed
+	}
+	fn execute(
+		&mut self,
+		count: usize,
+		command: Command,
+		line: &mut InputLine,
+		cursor: &mut usize,
+	) {
+		match command {
+			Command::Move(motion) => *cursor = target(line, *cursor, motion, count),
+			Command::Operate(operator, motion) => {
+				let range = operator_range(line, *cursor, operator, motion, count);
+				let bytes = line.offset(range.start)..line.offset(range.end);
+				self.register = line.as_str()[bytes.clone()].to_owned();
+				if operator != Operator::Yank {
+					line.replace(bytes, "");
+				}
+				*cursor = range.start;
+				if operator == Operator::Change {
+					self.mode = ViMode::Insert;
+				}
+			}
+			Command::DeleteChar => {
+				let end = (*cursor + count).min(line.count());
+				let bytes = line.offset(*cursor)..line.offset(end);
+				if !bytes.is_empty() {
+					self.register = line.as_str()[bytes.clone()].to_owned();
+					line.replace(bytes, "");
+				}
+			}
+			Command::Insert(at) => {
+				*cursor = match at {
+					InsertAt::Cursor => *cursor,
+					InsertAt::After => (*cursor + 1).min(line.count()),
+					InsertAt::FirstNonBlank => first_non_blank(line),
+					InsertAt::End => line.count(),
+				};
+				self.mode = ViMode::Insert;
+			}
+			Command::Paste { before } => {
+				if self.register.is_empty() {
+					return;
+				}
+				let at = match before || line.is_empty() {
+					true => *cursor,
+					false => *cursor + 1,
+				};
+				let offset = line.offset(at);
+				let count_before = line.count();
+				line.replace(offset..offset, &self.register.repeat(count));
+				// On the last character pasted
+				*cursor = at + (line.count() - count_before).saturating_sub(1);
+			}
+		}
+	}
+}
+
+fn motion(c: char) -> Option<Motion> {
+	Some(match c {
+		'h' => Motion::Left,
+		'l' | ' ' => Motion::Right,
+		'w' => Motion::WordForward,
+		'b' => Motion::WordBackward,
+		'e' => Motion::WordEnd,
+		'0' => Motion::Start,
+		'^' => Motion::FirstNonBlank,
+		'$' => Motion::End,
+		_ => return None,
+	})
+}
+
+// Blanks, letters and digits, and other characters make up different words
+fn class(line: &InputLine, index: usize) -> u8 {
+	let c = line
+		.grapheme(index)
+		.and_then(|(_, grapheme)| grapheme.chars().next())
+		.unwrap_or(' ');
+	match c {
+		_ if c.is_whitespace() => 0,
+		_ if c.is_alphanumeric() || c == '_' => 1,
+		_ => 2,
+	}
+}
+
+fn first_non_blank(line: &InputLine) -> usize {
+	(0..line.count())
+		.find(|&i| class(line, i) != 0)
+		.unwrap_or(line.count())
+}
+
+// Start of the next word
+fn next_word(line: &InputLine, index: usize) -> usize {
+	let count = line.count();
+	let mut next = index;
+	let start = class(line, index);
+	if start != 0 {
+		while next < count && class(line, next) == start {
+			next += 1;
+		}
+	}
+	while next < count && class(line, next) == 0 {
+		next += 1;
+	}
+	next
+}
+
+// Start of the word before
+fn previous_word(line: &InputLine, index: usize) -> usize {
+	let mut previous = index;
+	while previous > 0 && class(line, previous - 1) == 0 {
+		previous -= 1;
+	}
+	if previous > 0 {
+		let word = class(line, previous - 1);
+		while previous > 0 && class(line, previous - 1) == word {
+			previous -= 1;
+		}
+	}
+	previous
+}
+
+// Last character of the word after the cursor
+fn word_end(line: &InputLine, index: usize) -> usize {
+	let count = line.count();
+	let mut end = index + 1;
+	while end < count && class(line, end) == 0 {
+		end += 1;
+	}
+	if end >= count {
+		return count.saturating_sub(1).max(index);
+	}
+	let word = class(line, end);
+	while end + 1 < count && class(line, end + 1) == word {
+		end += 1;
+	}
+	end
+}
+
+// Where `motion` typed `count` times moves the cursor
+fn target(line: &InputLine, cursor: usize, motion: Motion, count: usize) -> usize {
+	let repeat = |step: fn(&InputLine, usize) -> usize| {
+		(0..count).fold(cursor, |index, _| step(line, index))
+	};
+	match motion {
+		Motion::Left => cursor.saturating_sub(count),
+		Motion::Right => (cursor + count).min(line.count()),
+		Motion::WordForward => repeat(next_word),
+		Motion::WordBackward => repeat(previous_word),
+		Motion::WordEnd => repeat(word_end),
+		Motion::Start | Motion::WholeLine => 0,
+		Motion::FirstNonBlank => first_non_blank(line),
+		Motion::End => line.count(),
+	}
+}
+
+// Graphemes an operator with `motion` applies to
+fn operator_range(
+	line: &InputLine,
+	cursor: usize,
+	operator: Operator,
+	motion: Motion,
+	count: usize,
+) -> Range<usize> {
+	match motion {
+		Motion::WholeLine => 0..line.count(),
+		// `cw` changes up to the end of the word, like `ce`
+		Motion::WordForward if operator == Operator::Change && class(line, cursor) != 0 => {
+			cursor..(target(line, cursor, Motion::WordEnd, count) + 1).min(line.count())
+		}
+		// Includes the character it ends on
+		Motion::WordEnd => cursor..(target(line, cursor, motion, count) + 1).min(line.count()),
+		_ => {
+			let target = target(line, cursor, motion, count);
+			cursor.min(target)..cursor.max(target)
+		}
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_vi_commands() {
+	let mut vi = ViState::default();
+	let mut line = InputLine::default();
+	line.set("git commit --amend".into());
+	let mut cursor = line.count();
+	let keys = |vi: &mut ViState, line: &mut InputLine, cursor: &mut usize, keys: &str| {
+		keys.chars()
+			.map(|c| vi.normal_key(c, line, cursor))
+			.last()
+			.unwrap()
+	};
+	vi.escape(&mut cursor);
+	assert_eq!(cursor, 17);
+	keys(&mut vi, &mut line, &mut cursor, "0w");
+	assert_eq!(cursor, 4);
+	keys(&mut vi, &mut line, &mut cursor, "e");
+	assert_eq!(cursor, 9);
+	keys(&mut vi, &mut line, &mut cursor, "2b");
+	assert_eq!(cursor, 0);
+	keys(&mut vi, &mut line, &mut cursor, "$");
+	assert_eq!(cursor, 18);
+
+	// Operators with counts and motions
+	keys(&mut vi, &mut line, &mut cursor, "0d2w");
+	assert_eq!(line.as_str(), "--amend");
+	keys(&mut vi, &mut line, &mut cursor, "P");
+	assert_eq!(line.as_str(), "git commit --amend");
+	assert_eq!(cursor, 10);
+	keys(&mut vi, &mut line, &mut cursor, "0cw");
+	assert_eq!(vi.mode, ViMode::Insert);
+	assert_eq!(line.as_str(), " commit --amend");
+	for c in "hg".chars() {
+		let offset = line.offset(cursor);
+		line.replace(offset..offset, c.encode_utf8(&mut [0; 4]));
+		cursor += 1;
+		vi.record(c);
+	}
+	vi.escape(&mut cursor);
+	assert_eq!(line.as_str(), "hg commit --amend");
+
+	// `.` changes the next word the same way
+	keys(&mut vi, &mut line, &mut cursor, "w.");
+	assert_eq!(line.as_str(), "hg hg --amend");
+	assert_eq!(cursor, 4);
+	keys(&mut vi, &mut line, &mut cursor, "x");
+	assert_eq!(line.as_str(), "hg h --amend");
+	keys(&mut vi, &mut line, &mut cursor, "yyA");
+	assert_eq!(cursor, line.count());
+	vi.escape(&mut cursor);
+	keys(&mut vi, &mut line, &mut cursor, "dd");
+	assert_eq!(line.as_str(), "");
+	assert_eq!(keys(&mut vi, &mut line, &mut cursor, "dz"), ViOutcome::Bell);
+	assert_eq!(
+		keys(&mut vi, &mut line, &mut cursor, "k"),
+		ViOutcome::Action(EditAction::HistoryPrevious)
+	);
+}
@@ -0,0 +1,331 @@
+use std::{
+	io::{self, Write},
+	sync::{Arc, Mutex},
+};
+
+use unicode_width::UnicodeWidthChar;
+
+/// A small terminal emulator for testing what gets drawn
+///
+/// Only understands the sequences this crate emits: printing with line wrap,
+/// carriage returns and line feeds, cursor movement, saving and restoring the
+/// cursor, erasing and scroll regions.  Colors and other modes are ignored.
+pub(crate) struct VirtualTerminal {
+	width: u16,
+	height: u16,
+	// Each cell holds a character and any combining characters after it.  The
+	// cell after a wide character holds an empty string.
+	cells: Vec<Vec<String>>,
+	cursor: (u16, u16),
+	// The last column was printed to, the next character goes on a new row
+	pending_wrap: bool,
+	saved_cursor: (u16, u16),
+	// First and last row of the scroll region
+	scroll_region: (u16, u16),
+	// Start of a sequence or character not yet complete
+	incomplete: Vec<u8>,
+}
+
+impl VirtualTerminal {
+	pub fn new(width: u16, height: u16) -> Self {
+		Self {
+			width,
+			height,
+			cells: vec![Self::blank_row(width); height as usize],
+			cursor: (0, 0),
+			pending_wrap: false,
+			saved_cursor: (0, 0),
+			scroll_region: (0, height - 1),
+			incomplete: Vec::new(),
+		}
+	}
+
+	/// The text on each row, without trailing spaces
+	pub fn screen_contents(&self) -> Vec<String> {
+		self.cells
+			.iter()
+			.map(|row| {
+				let text: String = row.concat();
+				text.trim_end().to_string()
+			})
+			.collect()
+	}
+
+	/// Column and row of the cursor
+	pub fn cursor_pos(&self) -> (u16, u16) {
+		self.cursor
+	}
+
+	/// Change the size, like a terminal window that doesn't reflow its text
+	pub fn resize(&mut self, width: u16, height: u16) {
+		for row in &mut self.cells {
+			row.resize(width as usize, " ".into());
+		}
+		if height < self.height {
+			// Rows scroll off the top, keeping the cursor on its row of text
+			let removed = (self.height - height).min(self.cursor.1);
+			self.cells.drain(..removed as usize);
+			self.cursor.1 -= removed;
+		}
+		self.cells.resize(height as usize, Self::blank_row(width));
+		self.width = width;
+		self.height = height;
+		self.cursor.0 = self.cursor.0.min(width - 1);
+		self.cursor.1 = self.cursor.1.min(height - 1);
+		self.scroll_region = (0, height - 1);
+		self.pending_wrap = false;
+	}
+
+	fn blank_row(width: u16) -> Vec<String> {
+		vec![" ".into(); width as usize]
+	}
+
+	// Processes complete sequences from the start of `data`, returning how
+	// many bytes were used
+	fn process(&mut self, data: &[u8]) -> usize {
+		let mut used = 0;
+		while used < data.len() {
+			let len = match data[used] {
+				0x1b => self.escape(&data[used..]),
+				b'\r' => {
+					self.cursor.0 = 0;
+					self.pending_wrap = false;
+					1
+				}
+				b'\n' => {
+					self.line_feed();
+					1
+				}
+				0x08 => {
+					self.move_cursor(-1, 0);
+					1
+				}
+				0x00..=0x1f => 1,
+				_ => {
+					let len = match data[used] {
+						0xf0.. => 4,
+						0xe0.. => 3,
+						0xc0.. => 2,
+						_ => 1,
+					};
+					let Some(bytes) = data.get(used..used + len) else {
+						return used;
+					};
+					for c in String::from_utf8_lossy(bytes).chars() {
+						self.print(c);
+					}
+					len
+				}
+			};
+			if len == 0 {
+				break;
+			}
+			used += len;
+		}
+		used
+	}
+
+	// Handles the escape sequence at the start of `data`, returning its
+	// length or 0 if it is incomplete
+	fn escape(&mut self, data: &[u8]) -> usize {
+		match data.get(1) {
+			None => 0,
+			Some(b'7') => {
+				self.saved_cursor = self.cursor;
+				2
+			}
+			Some(b'8') => {
+				self.cursor = self.saved_cursor;
+				self.pending_wrap = false;
+				2
+			}
+			Some(b'[') => {
+				let Some(end) = data[2..].iter().position(|b| (0x40..=0x7e).contains(b)) else {
+					return 0;
+				};
+				self.csi(&data[2..2 + end], data[2 + end]);
+				end + 3
+			}
+			// Strings such as window titles, ended by BEL or ST
+			Some(b']' | b'P' | b'_') => {
+				let bel = data.iter().position(|&b| b == 0x07).map(|i| i + 1);
+				let st = data.windows(2).position(|w| w == b"\x1b\\").map(|i| i + 2);
+				bel.into_iter().chain(st).min().unwrap_or(0)
+			}
+			Some(_) => 2,
+		}
+	}
+
+	fn csi(&mut self, params: &[u8], command: u8) {
+		// Private modes (`?`) and cursor styles (` q`) don't change the screen
+		if params.first() == Some(&b'?') || params.contains(&b' ') {
+			return;
+		}
+		let params: Vec<u16> = std::str::from_utf8(params)
+			.unwrap_or_default()
+			.split(';')
+			.map(|param| param.parse().unwrap_or(0))
+			.collect();
+		// Movements treat 0 like 1, like real terminals do
+		let count = params[0].max(1) as i32;
+		let (column, row) = self.cursor;
+		match command {
+			b'A' => self.move_cursor(0, -count),
+			b'B' => self.move_cursor(0, count),
+			b'C' => self.move_cursor(count, 0),
+			b'D' => self.move_cursor(-count, 0),
+			b'E' => {
+				self.move_cursor(0, count);
+				self.cursor.0 = 0;
+			}
+			b'G' => self.move_to(count as u16 - 1, row),
+			b'H' | b'f' => {
+				let column = params.get(1).copied().unwrap_or(0).max(1);
+				self.move_to(column - 1, count as u16 - 1);
+			}
+			b'J' => match params[0] {
+				0 => self.erase(column, row, self.width, self.height - 1),
+				1 => self.erase(0, 0, column + 1, row),
+				_ => self.erase(0, 0, self.width, self.height - 1),
+			},
+			b'K' => match params[0] {
+				0 => self.erase(column, row, self.width, row),
+				1 => self.erase(0, row, column + 1, row),
+				_ => self.erase(0, row, self.width, row),
+			},
+			b'r' => {
+				let top = params[0].max(1) - 1;
+				let bottom = params.get(1).copied().filter(|&b| b != 0);
+				let bottom = bottom.unwrap_or(self.height).min(self.height) - 1;
+				self.scroll_region = (top, bottom);
+				self.move_to(0, 0);
+			}
+			_ => {}
+		}
+	}
+
+	fn move_cursor(&mut self, columns: i32, rows: i32) {
+		let column = (self.cursor.0 as i32 + columns).max(0) as u16;
+		let row = (self.cursor.1 as i32 + rows).max(0) as u16;
+		self.move_to(column, row);
+	}
+
+	fn move_to(&mut self, column: u16, row: u16) {
+		self.cursor = (column.min(self.width - 1), row.min(self.height - 1));
+		self.pending_wrap = false;
+	}
+
+	// Clears from `(column, row)` up to, but not including, `end_column` on
+	// `end_row`
+	fn erase(&mut self, column: u16, row: u16, end_column: u16, end_row: u16) {
+		for r in row..=end_row {
+			let start = if r == row { column } else { 0 };
+			let end = if r == end_row { end_column } else { self.width };
+			for cell in &mut self.cells[r as usize][start as usize..end as usize] {
+				*cell = " ".into();
+			}
+		}
+	}
+
+	fn line_feed(&mut self) {
+		self.pending_wrap = false;
+		let (top, bottom) = self.scroll_region;
+		if self.cursor.1 == bottom {
+			self.cells.remove(top as usize);
+			self.cells
+				.insert(bottom as usize, Self::blank_row(self.width));
+		} else if self.cursor.1 < self.height - 1 {
+			self.cursor.1 += 1;
+		}
+	}
+
+	fn print(&mut self, c: char) {
+		let width = UnicodeWidthChar::width(c).unwrap_or(0) as u16;
+		if width == 0 {
+			// Combining characters join the previous cell
+			let (column, row) = self.cursor;
+			let column = if self.pending_wrap {
+				column
+			} else {
+				column.saturating_sub(1)
+			};
+			self.cells[row as usize][column as usize].push(c);
+			return;
+		}
+		if self.pending_wrap || self.cursor.0 + width > self.width {
+			self.cursor.0 = 0;
+			self.line_feed();
+		}
+		let (column, row) = self.cursor;
+		let cells = &mut self.cells[row as usize];
+		cells[column as usize] = c.to_string();
+		if width == 2 {
+			cells[column as usize + 1].clear();
+		}
+		if column + width >= self.width {
+			self.cursor.0 = self.width - 1;
+			self.pending_wrap = true;
+		} else {
+			self.cursor.0 = column + width;
+		}
+	}
+}
+
+impl Write for VirtualTerminal {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.incomplete.extend_from_slice(buf);
+		let data = std::mem::take(&mut self.incomplete);
+		let used = self.process(&data);
+		self.incomplete = data[used..].to_vec();
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// A `VirtualTerminal` that can still be inspected after being handed to a
+/// `Readline`
+#[derive(Clone)]
+pub(crate) struct SharedTerminal(pub Arc<Mutex<VirtualTerminal>>);
+
+impl SharedTerminal {
+	pub fn new(width: u16, height: u16) -> Self {
+		Self(Arc::new(Mutex::new(VirtualTerminal::new(width, height))))
+	}
+
+	pub fn screen_contents(&self) -> Vec<String> {
+		self.0.lock().unwrap().screen_contents()
+	}
+}
+
+impl Write for SharedTerminal {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.lock().unwrap().write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_virtual_terminal() {
+	let mut term = VirtualTerminal::new(5, 3);
+	write!(term, "hello world\r\n\x1b[1;31m日本\x1b[0m").unwrap();
+	assert_eq!(term.screen_contents(), [" worl", "d", "日本"]);
+	assert_eq!(term.cursor_pos(), (4, 2));
+
+	// Sequences split over several writes
+	term.write_all(b"\x1b[2").unwrap();
+	term.write_all(b"A\x1b[1G\x1b[K").unwrap();
+	term.write_all(&"é".as_bytes()[..1]).unwrap();
+	term.write_all(&"é".as_bytes()[1..]).unwrap();
+	assert_eq!(term.screen_contents(), ["é", "d", "日本"]);
+	assert_eq!(term.cursor_pos(), (1, 0));
+
+	write!(term, "\x1b7\x1b[3;1H\x1b[J\x1b8x").unwrap();
+	assert_eq!(term.screen_contents(), ["éx", "d", ""]);
+}
@@ -0,0 +1,162 @@
+/// A word of a line split like a shell does, see [`split_words()`] and
+/// [`current_word()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+	/// Byte offset in the line where the word starts, including any quote
+	/// that opens it
+	pub start: usize,
+	/// Byte offset in the line after the end of the word
+	pub end: usize,
+	/// The word with quotes and escapes removed
+	pub text: String,
+	/// The quote still open at the end of the word, if any
+	pub quote: Option<char>,
+}
+
+impl Word {
+	/// Quote or escape `text` to replace this word with, so that it is read
+	/// back as `text`, e.g. a completion candidate
+	///
+	/// A quote that was opened is kept open, otherwise spaces, quotes and
+	/// backslashes are escaped with backslashes.
+	pub fn escape(&self, text: &str) -> String {
+		let mut escaped = String::new();
+		match self.quote {
+			Some('\'') => {
+				escaped.push('\'');
+				// Nothing can be escaped in single quotes, so close them
+				escaped += &text.replace('\'', r"'\''");
+			}
+			Some(quote) => {
+				escaped.push(quote);
+				for c in text.chars() {
+					if c == quote || c == '\\' {
+						escaped.push('\\');
+					}
+					escaped.push(c);
+				}
+			}
+			None => {
+				for c in text.chars() {
+					if c.is_whitespace() || matches!(c, '\'' | '"' | '\\') {
+						escaped.push('\\');
+					}
+					escaped.push(c);
+				}
+			}
+		}
+		escaped
+	}
+}
+
+/// Split `line` into words at unquoted whitespace, handling single and
+/// double quotes and backslash escapes
+///
+/// Backslashes escape any character outside of quotes, and only quotes and
+/// backslashes inside double quotes.  An unterminated quote or escape runs to
+/// the end of the line.
+pub fn split_words(line: &str) -> Vec<Word> {
+	let mut words = Vec::new();
+	let mut word: Option<Word> = None;
+	let mut chars = line.char_indices().peekable();
+	while let Some((offset, c)) = chars.next() {
+		let quote = word.as_ref().and_then(|word| word.quote);
+		if quote.is_none() && c.is_whitespace() {
+			if let Some(mut word) = word.take() {
+				word.end = offset;
+				words.push(word);
+			}
+			continue;
+		}
+		let word = word.get_or_insert_with(|| Word {
+			start: offset,
+			end: line.len(),
+			text: String::new(),
+			quote: None,
+		});
+		match (quote, c) {
+			(None, '\'' | '"') => word.quote = Some(c),
+			(Some(quote), _) if c == quote => word.quote = None,
+			(None, '\\') => {
+				if let Some((_, escaped)) = chars.next() {
+					word.text.push(escaped);
+				}
+			}
+			(Some('"'), '\\') => match chars.peek() {
+				Some(&(_, escaped @ ('"' | '\\'))) => {
+					chars.next();
+					word.text.push(escaped);
+				}
+				_ => word.text.push(c),
+			},
+			_ => word.text.push(c),
+		}
+	}
+	words.extend(word);
+	words
+}
+
+/// The word that the cursor at byte offset `pos` completes, i.e. the part of
+/// the word before the cursor
+///
+/// An empty word at `pos` is returned if the cursor isn't right after a word,
+/// e.g. after a space.  Completers can replace the line from
+/// [`start`][Word::start] up to the cursor with a candidate passed through
+/// [`Word::escape()`].
+pub fn current_word(line: &str, pos: usize) -> Word {
+	match split_words(&line[..pos]).pop() {
+		// Words ended by whitespace end before the cursor
+		Some(word) if word.end == pos => word,
+		_ => Word {
+			start: pos,
+			end: pos,
+			text: String::new(),
+			quote: None,
+		},
+	}
+}
+
+#[cfg(test)]
+#[test]
+fn test_split_words() {
+	let texts = |line| {
+		split_words(line)
+			.into_iter()
+			.map(|word| word.text)
+			.collect::<Vec<_>>()
+	};
+	assert_eq!(
+		texts("  cp a\\ b  'c d' \"e\\\"f\"g"),
+		["cp", "a b", "c d", "e\"fg"]
+	);
+	assert_eq!(texts(r#"'a\b' "a\b""#), [r"a\b", r"a\b"]);
+	assert_eq!(
+		texts("unterminated 'quote and\\"),
+		["unterminated", "quote and\\"]
+	);
+
+	let words = split_words("ls 'my dir");
+	assert_eq!(words[1].start, 3);
+	assert_eq!(words[1].end, 10);
+	assert_eq!(words[1].quote, Some('\''));
+}
+
+#[cfg(test)]
+#[test]
+fn test_current_word() {
+	let line = r#"open "My Documents/Fi" other"#;
+	let word = current_word(line, 21);
+	assert_eq!((word.start, word.text.as_str()), (5, "My Documents/Fi"));
+	assert_eq!(word.quote, Some('"'));
+	assert_eq!(
+		word.escape("My Documents/File \"1\""),
+		r#""My Documents/File \"1\""#
+	);
+
+	let word = current_word("cd my\\ ", 7);
+	assert_eq!((word.start, word.text.as_str()), (3, "my "));
+	assert_eq!(word.escape("my dir"), r"my\ dir");
+	assert_eq!(current_word("cd ", 3).start, 3);
+	assert_eq!(current_word("cd ", 3).text, "");
+	assert_eq!(current_word("echo 'it", 8).escape("it's"), r"'it'\''s");
+}
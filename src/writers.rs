@@ -0,0 +1,96 @@
+use std::{
+	io,
+	sync::{
+		atomic::{AtomicU64, AtomicUsize, Ordering},
+		Arc, Mutex, MutexGuard, PoisonError, Weak,
+	},
+};
+
+/// Snapshot of the write counters shared by all clones of a
+/// [`SharedWriter`][crate::SharedWriter], returned by
+/// [`SharedWriter::stats()`][crate::SharedWriter::stats] and
+/// [`Readline::writer_stats()`][crate::Readline::writer_stats].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WriterStats {
+	/// Number of complete lines sent to the `Readline`
+	pub lines_sent: u64,
+	/// Number of bytes sent to the `Readline`
+	pub bytes_sent: u64,
+	/// Number of writes that found the channel full, either failing with
+	/// `WouldBlock` or giving up after waiting
+	pub writes_failed_full: u64,
+	/// Number of writes that failed because the `Readline` was dropped
+	pub writes_failed_closed: u64,
+}
+
+/// Buffer of a single `SharedWriter`, holding data until a full line is written
+pub(crate) type WriterBuffer = Arc<Mutex<Vec<u8>>>;
+
+pub(crate) fn lock_buffer(buffer: &Mutex<Vec<u8>>) -> MutexGuard<'_, Vec<u8>> {
+	buffer.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// State shared by all clones of a `SharedWriter` and their `Readline`
+#[derive(Debug, Default)]
+pub(crate) struct Writers {
+	lines_sent: AtomicU64,
+	bytes_sent: AtomicU64,
+	writes_failed_full: AtomicU64,
+	writes_failed_closed: AtomicU64,
+	// Number of live `SharedWriter`s
+	writers: AtomicUsize,
+	// Buffers of the live `SharedWriter`s, so that partial lines can be
+	// flushed from the `Readline` side
+	buffers: Mutex<Vec<Weak<Mutex<Vec<u8>>>>>,
+}
+
+impl Writers {
+	/// Register a new writer and return its buffer
+	pub fn add_writer(&self) -> WriterBuffer {
+		self.writers.fetch_add(1, Ordering::Relaxed);
+		let buffer = WriterBuffer::default();
+		let mut buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+		buffers.retain(|buffer| buffer.strong_count() > 0);
+		buffers.push(Arc::downgrade(&buffer));
+		buffer
+	}
+	/// Returns the number of writers left
+	pub fn remove_writer(&self) -> usize {
+		self.writers.fetch_sub(1, Ordering::Relaxed) - 1
+	}
+	pub fn writers(&self) -> usize {
+		self.writers.load(Ordering::Relaxed)
+	}
+	/// Take the data buffered in all writers
+	pub fn take_buffers(&self) -> Vec<Vec<u8>> {
+		let buffers = self.buffers.lock().unwrap_or_else(PoisonError::into_inner);
+		buffers
+			.iter()
+			.filter_map(Weak::upgrade)
+			.map(|buffer| std::mem::take(&mut *lock_buffer(&buffer)))
+			.filter(|data| !data.is_empty())
+			.collect()
+	}
+	pub fn record_sent(&self, data: &[u8]) {
+		let lines = data.iter().filter(|b| **b == b'\n').count();
+		self.lines_sent.fetch_add(lines as u64, Ordering::Relaxed);
+		self.bytes_sent
+			.fetch_add(data.len() as u64, Ordering::Relaxed);
+	}
+	pub fn record_full(&self) {
+		self.writes_failed_full.fetch_add(1, Ordering::Relaxed);
+	}
+	/// Record a write to a closed channel and return the error for it
+	pub fn closed(&self) -> io::Error {
+		self.writes_failed_closed.fetch_add(1, Ordering::Relaxed);
+		io::Error::other("thingbuf receiver has closed")
+	}
+	pub fn snapshot(&self) -> WriterStats {
+		WriterStats {
+			lines_sent: self.lines_sent.load(Ordering::Relaxed),
+			bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+			writes_failed_full: self.writes_failed_full.load(Ordering::Relaxed),
+			writes_failed_closed: self.writes_failed_closed.load(Ordering::Relaxed),
+		}
+	}
+}